@@ -1,6 +1,8 @@
-pub use butane_codegen::{butane_type, dataresult, model};
+pub use butane_codegen::{butane_type, dataresult, model, FieldType};
+pub use butane_core::backref::BackRef;
 pub use butane_core::custom;
 pub use butane_core::fkey::ForeignKey;
+pub use butane_core::json::Json;
 pub use butane_core::many::Many;
 pub use butane_core::migrations;
 pub use butane_core::query;
@@ -31,10 +33,60 @@ pub mod db {
 /// To refer to values from the surrounding rust function, enclose
 /// them in braces, like `filter!(Foo, bar == {bar})`
 ///
+/// # Arithmetic and column comparisons
+/// A field may be compared with another field, and either side of a
+/// comparison may use the arithmetic operators `+`, `-`, `*`, `/` and
+/// `%`, e.g. `filter!(Foo, likes > dislikes * 2)`. These comparisons
+/// are evaluated by the database. As with comparisons with values,
+/// the types of both sides must be compatible, so for example an
+/// `i32` field may not be compared with an `i64` field.
+///
+/// # Foreign key paths
+/// A field of the model referred to by a [`ForeignKey`] field may be
+/// compared with a value using a dotted path, such as
+/// `filter!(Post, blog.name == "Cats")`. Paths may follow several
+/// foreign keys, e.g. `filter!(Comment, post.blog.owner.country == "NZ")`,
+/// and are evaluated with a single subquery joining the models along
+/// the path. Every field of the path but the last must be a
+/// `ForeignKey`, and a model may appear only once along a path.
+///
+/// # Logical operators
+/// Conditions may be combined with `&&` and `||`, negated with `!`
+/// and grouped with parentheses, e.g. `filter!(Foo, !(a == 1 || b == 2))`.
+/// A `bool` field may be used as a condition by itself, e.g.
+/// `filter!(Foo, published && !archived)`.
+///
 /// # Function-like operations
 /// Filters support some operations for which Rust does not have operators and which are instead
 /// represented syntactically as function calls.
 /// * `like`: parameter is a SQL LIKE expression string, e.g. `title.like("M%").
+/// * `ilike`: case-insensitive version of `like`.
+/// * `starts_with`, `ends_with` and `contains_str`: parameter is a
+///   string which must appear at the start, end or anywhere in the
+///   field, e.g. `title.starts_with("M")`. Unlike with `like`, `%` and
+///   `_` in the parameter are not treated as wildcards, and the
+///   comparison is case-sensitive on every backend.
+/// * `is_none` and `is_some`: Use with an `Option` field to test
+///   whether it is NULL, e.g. `pub_time.is_none()`.
+/// * `in_` and `not_in`: Parameter is a Rust expression evaluating to
+///   an array, slice, or other iterable of values, e.g.
+///   `id.in_([1, 2, 3])` or `id.not_in(&ids)`. As with `!=`, an
+///   object whose field is NULL matches neither; combine `not_in` with
+///   `is_none` to include it.
+/// * `get`: Use with a [`Json`] field to refer to the value of a key
+///   of the JSON object, e.g. `meta.get("color") == "red"`. Calls may
+///   be chained to reach nested objects. Numbers compare as numbers
+///   and strings as strings.
+/// * `raw`: a fragment of raw SQL, with `?` placeholders for the
+///   following parameters, which are Rust expressions. For example,
+///   `raw("length(title) > ?", 10)`. The number of placeholders is
+///   checked at compile time.
+/// * `exists`: `exists(Model, expr)` is true if any object of `Model`
+///   matches `expr`. Within `expr`, fields of `Model` are referred to
+///   as usual and fields of the model being filtered as `self.field`.
+///   For example, to find blogs with an unpublished post,
+///   `filter!(Blog, exists(Post, blog == self.id && !published))`.
+///   `Model` must not be the model being filtered.
 /// * `matches`: Parameter is a sub-expression. Use with a
 ///   [`ForeignKey`] field to evaluate as true if the referent
 ///   matches. For example, to find all posts made in blogs by people
@@ -46,7 +98,8 @@ pub mod db {
 ///    `tags: Many<Tag>` we could filter to posts with a "cats" with
 ///    the following `tags.contains(tag == "cats"). If the expression
 ///    is single literal, it is assumed to be used to match the
-///    primary key.
+///    primary key. `contains` may also be used with a [`BackRef`]
+///    field to match the objects referring to this one.
 ///
 /// # Examples
 /// ```
@@ -64,12 +117,129 @@ pub mod db {
 /// let first_place = 1;
 /// let e2 = filter!(Contestant, rank == { first_place });
 /// let e3 = filter!(Contestant, name.like("A%"));
+/// let e4 = filter!(Contestant, rank * 2 < rank + 10);
+/// let e5 = filter!(Contestant, rank.in_([1, 2, 3]) && !(nationality == "US"));
+///```
+///
+/// Comparing fields of incompatible types fails to compile:
+/// ```compile_fail
+/// # use butane::query::BoolExpr;
+/// # use butane_codegen::model;
+/// # use butane_codegen::filter;
+/// #[model]
+/// struct Contestant {
+///   #[pk]
+///   name: String,
+///   rank: i32,
+///   nationality: String
+/// }
+/// let e: BoolExpr = filter!(Contestant, rank < nationality);
 ///```
 ///
 /// [`BoolExpr`]: crate::query::BoolExpr
 /// [`Query`]: crate::query::Query
 pub use butane_codegen::filter;
 
+/// Queries some of the columns of a model, loading each row as a tuple.
+///
+/// Use as `select!(Foo, (a, b, ...))` or `select!(Foo, (a, b, ...), expr)`,
+/// where `Foo` is a model type, `a` and `b` are fields of `Foo`, and
+/// `expr` is a filter expression as for [`filter`]. Returns a
+/// [`Select`] query which loads rows as tuples of the field types,
+/// without declaring a `dataresult` type. Referring to a field which
+/// does not exist is a compiler error.
+///
+/// # Examples
+/// ```
+/// # use butane::query::*;
+/// # use butane_codegen::model;
+/// # use butane::select;
+/// # use butane::prelude::*;
+/// #[model]
+/// struct Contestant {
+///   #[pk]
+///   name: String,
+///   rank: i32,
+///   nationality: String
+/// }
+/// let top_tier: Select<_> = select!(Contestant, (name, rank), rank <= 10);
+///```
+///
+/// [`filter`]: crate::filter
+/// [`Select`]: crate::query::Select
+pub use butane_codegen::select;
+
+/// Constructs a list of order terms for a query.
+///
+/// Use as `order_by!(Foo, term, ...)`, where `Foo` is a model type.
+/// Returns a `Vec<`[`Order`]`>`, typically passed to
+/// [`Query::order_by`]. Each term is an expression optionally
+/// followed by `asc` (the default) or `desc`, and then optionally by
+/// `nulls first` or `nulls last`. The expression may be
+/// * a field of `Foo`, such as `title`.
+/// * a field of the model referred to by a [`ForeignKey`] field, such
+///   as `blog.name`. The object is found with a subquery, so no join
+///   is needed.
+/// * a call of a SQL function, such as `lower(title)` or `random()`.
+///   The function name is passed through to the database unchanged.
+/// * an arithmetic expression, such as `likes * 2`.
+///
+/// Postgres supports the placement of nulls natively. On SQLite,
+/// `nulls first` and `nulls last` are emulated by first sorting on
+/// whether the expression is null.
+///
+/// # Examples
+/// ```
+/// # use butane::query::*;
+/// # use butane_codegen::model;
+/// # use butane::order_by;
+/// # use butane::prelude::*;
+/// #[model]
+/// struct Contestant {
+///   #[pk]
+///   name: String,
+///   rank: Option<i32>,
+///   nationality: String
+/// }
+/// let order: Vec<Order> = order_by!(Contestant, rank desc nulls last, lower(name));
+/// let q: Query<Contestant> = Contestant::query().order_by(order);
+///```
+///
+/// A term must refer to a column; constants such as `1` or
+/// `lower("x")` fail to compile:
+/// ```compile_fail
+/// # use butane::query::*;
+/// # use butane_codegen::model;
+/// # use butane::order_by;
+/// # use butane::prelude::*;
+/// #[model]
+/// struct Contestant {
+///   #[pk]
+///   name: String,
+///   rank: Option<i32>,
+///   nationality: String
+/// }
+/// let order: Vec<Order> = order_by!(Contestant, 1);
+///```
+/// ```compile_fail
+/// # use butane::query::*;
+/// # use butane_codegen::model;
+/// # use butane::order_by;
+/// # use butane::prelude::*;
+/// #[model]
+/// struct Contestant {
+///   #[pk]
+///   name: String,
+///   rank: Option<i32>,
+///   nationality: String
+/// }
+/// let order: Vec<Order> = order_by!(Contestant, lower("x"));
+///```
+///
+/// [`Order`]: crate::query::Order
+/// [`Query::order_by`]: crate::query::Query::order_by
+pub use butane_codegen::order_by;
+
 /// Constructs a filtered database query.
 ///
 /// Use as `query!(Foo, expr)`, where `Foo` is a model type. Returns [`Query`]`<Foo>`.
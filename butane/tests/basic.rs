@@ -1,7 +1,8 @@
 use butane::db::Connection;
 use butane::prelude::*;
-use butane::{butane_type, find, model, query};
+use butane::{butane_type, find, model, query, FieldType, FromSql, SqlType, SqlVal};
 use butane::{ForeignKey, ObjectState};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeZone, Utc};
 use paste;
 #[cfg(feature = "pg")]
 use postgres;
@@ -96,6 +97,40 @@ impl SelfReferential {
     }
 }
 
+#[model]
+#[derive(Debug, PartialEq, Clone)]
+struct Appointment {
+    id: i64,
+    day: NaiveDate,
+    start: NaiveTime,
+    created: DateTime<Utc>,
+    cancelled: Option<DateTime<Utc>>,
+}
+impl Appointment {
+    fn new(id: i64, day: NaiveDate, start: NaiveTime) -> Self {
+        Appointment {
+            id,
+            day,
+            start,
+            created: Utc.ymd(2022, 3, 4).and_hms_micro(5, 6, 7, 891011),
+            cancelled: None,
+            state: ObjectState::default(),
+        }
+    }
+}
+
+#[model]
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct IntWidths {
+    id: i64,
+    tiny: i8,
+    small: u8,
+    short: i16,
+    word: u16,
+    big: u32,
+    huge: u64,
+}
+
 fn basic_crud(conn: Connection) {
     //create
     let mut foo = Foo::new(1);
@@ -323,3 +358,93 @@ fn fkey_same_type(conn: Connection) {
     assert!(inner.reference.is_none());
 }
 testall!(fkey_same_type);
+
+fn date_and_time_types(conn: Connection) {
+    let mut early = Appointment::new(
+        1,
+        NaiveDate::from_ymd(2022, 1, 31),
+        NaiveTime::from_hms_micro(9, 30, 0, 250),
+    );
+    early.cancelled = Some(Utc.ymd(2022, 1, 30).and_hms(23, 0, 0));
+    early.save(&conn).unwrap();
+    let mut late = Appointment::new(
+        2,
+        NaiveDate::from_ymd(2022, 2, 1),
+        NaiveTime::from_hms(14, 0, 0),
+    );
+    late.save(&conn).unwrap();
+
+    assert_eq!(Appointment::get(&conn, 1).unwrap(), early);
+    assert_eq!(Appointment::get(&conn, 2).unwrap(), late);
+
+    let found = query!(Appointment, day < { NaiveDate::from_ymd(2022, 2, 1) })
+        .load(&conn)
+        .unwrap();
+    assert_eq!(found, vec![early.clone()]);
+    let found = query!(Appointment, start >= { NaiveTime::from_hms(9, 30, 1) })
+        .load(&conn)
+        .unwrap();
+    assert_eq!(found, vec![late.clone()]);
+    let found = query!(
+        Appointment,
+        cancelled < { Utc.ymd(2022, 1, 31).and_hms(0, 0, 0) }
+    )
+    .load(&conn)
+    .unwrap();
+    assert_eq!(found, vec![early]);
+}
+testall!(date_and_time_types);
+
+fn integer_widths(mut conn: Connection) {
+    assert_eq!(i16::SQLTYPE, SqlType::SmallInt);
+    assert_eq!(u16::SQLTYPE, SqlType::Int);
+    assert_eq!(u64::SQLTYPE, SqlType::BigInt);
+
+    let mut widths = IntWidths {
+        id: 1,
+        tiny: i8::MIN,
+        small: u8::MAX,
+        short: i16::MIN,
+        word: u16::MAX,
+        big: u32::MAX,
+        huge: i64::MAX as u64,
+        state: ObjectState::default(),
+    };
+    widths.save(&conn).unwrap();
+    assert_eq!(IntWidths::get(&conn, 1).unwrap(), widths);
+
+    // Values which do not fit are an error rather than being truncated
+    assert!(matches!(
+        u8::from_sql(SqlVal::Int(256)),
+        Err(butane::Error::OutOfRange)
+    ));
+    conn.execute("UPDATE IntWidths SET huge = -1;").unwrap();
+    assert!(matches!(
+        IntWidths::get(&conn, 1),
+        Err(butane::Error::OutOfRange)
+    ));
+
+    // A u64 too large for a BigInt cannot be saved
+    widths.huge = u64::MAX;
+    assert!(matches!(widths.save(&conn), Err(butane::Error::OutOfRange)));
+    widths.id = 2;
+    widths.state = ObjectState::default();
+    assert!(matches!(widths.save(&conn), Err(butane::Error::OutOfRange)));
+    assert!(IntWidths::get(&conn, 2).is_err());
+
+    // Nor does any stored value equal one, or can one be assigned
+    conn.execute("UPDATE IntWidths SET huge = 1;").unwrap();
+    let found = query!(IntWidths, huge == { u64::MAX }).load(&conn).unwrap();
+    assert!(found.is_empty());
+    let found = IntWidths::query()
+        .filter(IntWidths::fields().huge().in_([1, u64::MAX]))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    assert!(matches!(
+        IntWidths::query().update(&conn, |f| f.huge().set(u64::MAX)),
+        Err(butane::Error::OutOfRange)
+    ));
+    assert_eq!(IntWidths::get(&conn, 1).unwrap().huge, 1);
+}
+testall!(integer_widths);
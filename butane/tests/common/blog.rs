@@ -1,6 +1,6 @@
 use butane::prelude::*;
 use butane::{dataresult, model};
-use butane::{db::Connection, ForeignKey, Many, ObjectState};
+use butane::{db::Connection, BackRef, ForeignKey, Many, ObjectState};
 use chrono::{naive::NaiveDateTime, offset::Utc};
 
 #[model]
@@ -8,12 +8,15 @@ use chrono::{naive::NaiveDateTime, offset::Utc};
 pub struct Blog {
     pub id: i64,
     pub name: String,
+    #[backref(blog)]
+    pub posts: BackRef<Post>,
 }
 impl Blog {
     pub fn new(id: i64, name: &str) -> Self {
         Blog {
             id,
             name: name.to_string(),
+            posts: BackRef::new(),
             state: ObjectState::default(),
         }
     }
@@ -66,6 +66,38 @@ impl HasCustomField {
     }
 }
 
+#[derive(FieldType, PartialEq, Eq, Debug, Clone)]
+enum Whatsit {
+    Foo,
+    #[rename = "bar"]
+    Bar,
+}
+
+#[derive(FieldType, PartialEq, Eq, Debug, Clone)]
+#[sqltype(Int)]
+enum Priority {
+    Low = 1,
+    High = 10,
+}
+
+#[model]
+#[derive(PartialEq, Eq, Debug, Clone)]
+struct HasDerivedFields {
+    id: i64,
+    whatsit: Whatsit,
+    priority: Priority,
+}
+impl HasDerivedFields {
+    fn new(id: i64, whatsit: Whatsit, priority: Priority) -> Self {
+        HasDerivedFields {
+            id,
+            whatsit,
+            priority,
+            state: ObjectState::default(),
+        }
+    }
+}
+
 fn roundtrip_custom_type(conn: Connection) {
     //create
     let mut obj = HasCustomField::new(1, Frobnozzle::Foo);
@@ -92,3 +124,32 @@ fn query_custom_type(conn: Connection) {
     assert_eq!(results[0], obj_bar)
 }
 testall!(query_custom_type);
+
+fn roundtrip_derived_type(conn: Connection) {
+    assert_eq!(Whatsit::Bar.to_sql(), SqlVal::Text("bar".to_string()));
+    assert_eq!(Priority::High.to_sql(), SqlVal::Int(10));
+    assert!(Priority::from_sql(SqlVal::Int(2)).is_err());
+
+    let mut obj = HasDerivedFields::new(1, Whatsit::Bar, Priority::High);
+    obj.save(&conn).unwrap();
+    let obj2 = HasDerivedFields::get(&conn, 1).unwrap();
+    assert_eq!(obj, obj2);
+}
+testall!(roundtrip_derived_type);
+
+fn query_derived_type(conn: Connection) {
+    let mut obj1 = HasDerivedFields::new(1, Whatsit::Foo, Priority::High);
+    obj1.save(&conn).unwrap();
+    let mut obj2 = HasDerivedFields::new(2, Whatsit::Bar, Priority::Low);
+    obj2.save(&conn).unwrap();
+
+    let results = query!(HasDerivedFields, whatsit == { Whatsit::Bar })
+        .load(&conn)
+        .unwrap();
+    assert_eq!(results, vec![obj2]);
+    let results = query!(HasDerivedFields, priority == { Priority::High })
+        .load(&conn)
+        .unwrap();
+    assert_eq!(results, vec![obj1]);
+}
+testall!(query_derived_type);
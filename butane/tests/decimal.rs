@@ -0,0 +1,151 @@
+#![cfg(feature = "decimal")]
+use butane::db::Connection;
+use butane::prelude::*;
+use butane::{colname, model, query, ObjectState};
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+mod common;
+
+#[model]
+#[derive(PartialEq, Debug, Clone)]
+struct Invoice {
+    id: i64,
+    #[numeric(20, 2)]
+    total: Decimal,
+    discount: Option<Decimal>,
+}
+impl Invoice {
+    fn new(id: i64, total: &str) -> Self {
+        Invoice {
+            id,
+            total: Decimal::from_str(total).unwrap(),
+            discount: None,
+            state: ObjectState::default(),
+        }
+    }
+}
+
+fn dec(s: &str) -> Decimal {
+    Decimal::from_str(s).unwrap()
+}
+
+fn roundtrip_decimal(conn: Connection) {
+    let mut invoice = Invoice::new(1, "1234567890.12");
+    invoice.discount = Some(dec("0.1"));
+    invoice.save(&conn).unwrap();
+
+    let invoice2 = Invoice::get(&conn, 1).unwrap();
+    assert_eq!(invoice2.total, dec("1234567890.12"));
+    assert_eq!(invoice2.discount, Some(dec("0.1")));
+
+    // More significant digits than a double holds
+    Invoice::new(2, "1234567890123456.78").save(&conn).unwrap();
+    let invoice3 = Invoice::get(&conn, 2).unwrap();
+    assert_eq!(invoice3.total, dec("1234567890123456.78"));
+}
+testall!(roundtrip_decimal);
+
+fn invoice_ids(invoices: &[Invoice]) -> Vec<i64> {
+    invoices.iter().map(|i| i.id).collect()
+}
+
+fn query_decimal(conn: Connection) {
+    Invoice::new(1, "9.99").save(&conn).unwrap();
+    Invoice::new(2, "10.00").save(&conn).unwrap();
+    Invoice::new(3, "100.50").save(&conn).unwrap();
+
+    // As text, "10.00" and "100.50" would sort before "9.99"
+    let invoices = query!(Invoice, total > { dec("9.99") })
+        .order_asc(colname!(Invoice, id))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(invoice_ids(&invoices), vec![2, 3]);
+
+    let invoices = query!(Invoice, total <= { dec("9.99") })
+        .load(&conn)
+        .unwrap();
+    assert_eq!(invoices.len(), 1);
+    assert_eq!(invoices[0].id, 1);
+
+    let invoices = query!(Invoice, total == { dec("10") }).load(&conn).unwrap();
+    assert_eq!(invoices.len(), 1);
+    assert_eq!(invoices[0].id, 2);
+}
+testall!(query_decimal);
+
+fn order_decimal(conn: Connection) {
+    Invoice::new(1, "9.99").save(&conn).unwrap();
+    Invoice::new(2, "100.50").save(&conn).unwrap();
+    Invoice::new(3, "10.00").save(&conn).unwrap();
+    Invoice::new(4, "-20.25").save(&conn).unwrap();
+
+    let invoices = Invoice::query()
+        .order_asc(colname!(Invoice, total))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(invoice_ids(&invoices), vec![4, 1, 3, 2]);
+
+    let invoices = Invoice::query()
+        .order_desc(colname!(Invoice, total))
+        .limit(2)
+        .load(&conn)
+        .unwrap();
+    assert_eq!(invoice_ids(&invoices), vec![2, 3]);
+}
+testall!(order_decimal);
+
+fn compare_long_decimal(conn: Connection) {
+    // As doubles, all of these would be 1234567890123456.8
+    Invoice::new(1, "1234567890123456.79").save(&conn).unwrap();
+    Invoice::new(2, "1234567890123456.8").save(&conn).unwrap();
+    Invoice::new(3, "1234567890123456.78").save(&conn).unwrap();
+
+    let invoices = query!(Invoice, total > { dec("1234567890123456.78") })
+        .order_asc(colname!(Invoice, id))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(invoice_ids(&invoices), vec![1, 2]);
+
+    let invoices = query!(Invoice, total == { dec("1234567890123456.80") })
+        .load(&conn)
+        .unwrap();
+    assert_eq!(invoice_ids(&invoices), vec![2]);
+
+    let invoices = Invoice::query()
+        .order_asc(colname!(Invoice, total))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(invoice_ids(&invoices), vec![3, 1, 2]);
+
+    let sum = Invoice::query()
+        .sum(&conn, Invoice::fields().total())
+        .unwrap();
+    assert_eq!(sum, Some(dec("3703703670370370.37")));
+}
+testall!(compare_long_decimal);
+
+fn aggregate_decimal(conn: Connection) {
+    Invoice::new(1, "9.99").save(&conn).unwrap();
+    Invoice::new(2, "100.50").save(&conn).unwrap();
+    Invoice::new(3, "10.01").save(&conn).unwrap();
+
+    let max: Option<Decimal> = Invoice::query()
+        .max(&conn, Invoice::fields().total())
+        .unwrap();
+    assert_eq!(max, Some(dec("100.50")));
+    let min: Option<Decimal> = Invoice::query()
+        .min(&conn, Invoice::fields().total())
+        .unwrap();
+    assert_eq!(min, Some(dec("9.99")));
+    let sum = Invoice::query()
+        .sum(&conn, Invoice::fields().total())
+        .unwrap();
+    assert_eq!(sum, Some(dec("120.50")));
+    let avg = Invoice::query()
+        .avg(&conn, Invoice::fields().total())
+        .unwrap()
+        .unwrap();
+    assert!((avg - 40.1666).abs() < 0.001);
+}
+testall!(aggregate_decimal);
@@ -0,0 +1,151 @@
+use butane::prelude::*;
+use butane::{db::Connection, ObjectState};
+use butane::{model, query, Json};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+mod common;
+
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+struct Settings {
+    color: String,
+    size: i32,
+}
+
+#[model]
+#[derive(PartialEq, Debug, Clone)]
+struct HasJson {
+    id: i64,
+    settings: Json<Settings>,
+    meta: Option<Json<serde_json::Value>>,
+}
+impl HasJson {
+    fn new(id: i64, color: &str, meta: Option<serde_json::Value>) -> Self {
+        HasJson {
+            id,
+            settings: Json(Settings {
+                color: color.to_string(),
+                size: 10,
+            }),
+            meta: meta.map(Json),
+            state: ObjectState::default(),
+        }
+    }
+}
+
+#[model]
+#[derive(Debug, Clone)]
+struct HasJsonMap {
+    id: i64,
+    points: Json<BTreeMap<(i32, i32), String>>,
+}
+
+fn roundtrip_json(conn: Connection) {
+    let mut obj = HasJson::new(1, "red", Some(json!({"tags": ["a", "b"]})));
+    obj.save(&conn).unwrap();
+    let mut obj2 = HasJson::get(&conn, 1).unwrap();
+    assert_eq!(obj, obj2);
+
+    obj2.settings.size = 12;
+    obj2.meta = None;
+    obj2.save(&conn).unwrap();
+    let obj3 = HasJson::get(&conn, 1).unwrap();
+    assert_eq!(obj3.settings.size, 12);
+    assert_eq!(obj3.meta, None);
+}
+testall!(roundtrip_json);
+
+fn save_unserializable_json(conn: Connection) {
+    // JSON object keys must be strings, so this map cannot be saved
+    let mut points = BTreeMap::new();
+    points.insert((1, 2), "a".to_string());
+    let mut obj = HasJsonMap {
+        id: 1,
+        points: Json(points),
+        state: ObjectState::default(),
+    };
+    assert!(matches!(obj.save(&conn), Err(butane::Error::SerdeJson(_))));
+    assert!(HasJsonMap::get(&conn, 1).is_err());
+}
+testall!(save_unserializable_json);
+
+fn query_json_path(conn: Connection) {
+    HasJson::new(1, "red", Some(json!({"owner": {"name": "Pete"}})))
+        .save(&conn)
+        .unwrap();
+    HasJson::new(2, "blue", Some(json!({"owner": {"name": "Alice"}})))
+        .save(&conn)
+        .unwrap();
+    HasJson::new(3, "red", None).save(&conn).unwrap();
+
+    let mut objs = query!(HasJson, settings.get("color") == "red")
+        .load(&conn)
+        .unwrap();
+    objs.sort_by_key(|o| o.id);
+    let ids: Vec<i64> = objs.iter().map(|o| o.id).collect();
+    assert_eq!(ids, vec![1, 3]);
+
+    let key = "name";
+    let objs = query!(HasJson, meta.get("owner").get({ key }) == "Alice")
+        .load(&conn)
+        .unwrap();
+    assert_eq!(objs.len(), 1);
+    assert_eq!(objs[0].id, 2);
+}
+testall!(query_json_path);
+
+fn query_json_numeric(conn: Connection) {
+    let mut obj = HasJson::new(1, "red", Some(json!({"rank": 2, "score": 1.5})));
+    obj.settings.size = 8;
+    obj.save(&conn).unwrap();
+    HasJson::new(2, "blue", Some(json!({"rank": 10, "score": 20.25})))
+        .save(&conn)
+        .unwrap();
+
+    // Numbers compare as numbers, not as text
+    let objs = query!(HasJson, settings.get("size") > 9)
+        .load(&conn)
+        .unwrap();
+    assert_eq!(objs.len(), 1);
+    assert_eq!(objs[0].id, 2);
+
+    let objs = query!(HasJson, meta.get("rank") < 9).load(&conn).unwrap();
+    assert_eq!(objs.len(), 1);
+    assert_eq!(objs[0].id, 1);
+
+    let objs = query!(HasJson, meta.get("score") == 20.25)
+        .load(&conn)
+        .unwrap();
+    assert_eq!(objs.len(), 1);
+    assert_eq!(objs[0].id, 2);
+
+    let objs = query!(HasJson, settings.get("size") == 8 && meta.get("rank") >= 2)
+        .load(&conn)
+        .unwrap();
+    assert_eq!(objs.len(), 1);
+    assert_eq!(objs[0].id, 1);
+}
+testall!(query_json_numeric);
+
+fn query_json_quoted_key(conn: Connection) {
+    HasJson::new(1, "red", Some(json!({"say \"hi\"": {"to": "Pete"}})))
+        .save(&conn)
+        .unwrap();
+    HasJson::new(2, "blue", Some(json!({"say hi": {"to": "Alice"}})))
+        .save(&conn)
+        .unwrap();
+
+    let objs = query!(HasJson, meta.get("say \"hi\"").get("to") == "Pete")
+        .load(&conn)
+        .unwrap();
+    assert_eq!(objs.len(), 1);
+    assert_eq!(objs[0].id, 1);
+
+    let objs = query!(HasJson, meta.get("say \"hi\"") == None)
+        .load(&conn)
+        .unwrap();
+    assert_eq!(objs.len(), 1);
+    assert_eq!(objs[0].id, 2);
+}
+testall!(query_json_quoted_key);
@@ -259,6 +259,29 @@ fn migration_delete_table_pg() {
     );
 }
 
+#[cfg(feature = "sqlite")]
+#[test]
+fn migration_change_int_width_sqlite() {
+    migration_change_int_width(
+        &mut common::sqlite_connection(),
+        // SQLite stores every integer in the same way, so there is
+        // nothing to change
+        "",
+        "",
+    );
+}
+
+#[cfg(feature = "pg")]
+#[test]
+fn migration_change_int_width_pg() {
+    let (mut conn, _data) = common::pg_connection();
+    migration_change_int_width(
+        &mut conn,
+        "CREATE TABLE Foo__butane_tmp (id BIGINT NOT NULL PRIMARY KEY,bar SMALLINT NOT NULL);INSERT INTO Foo__butane_tmp SELECT id, bar FROM Foo;DROP TABLE Foo;ALTER TABLE Foo__butane_tmp RENAME TO Foo;",
+        "CREATE TABLE Foo__butane_tmp (id BIGINT NOT NULL PRIMARY KEY,bar INTEGER NOT NULL);INSERT INTO Foo__butane_tmp SELECT id, bar FROM Foo;DROP TABLE Foo;ALTER TABLE Foo__butane_tmp RENAME TO Foo;",
+    );
+}
+
 fn test_migrate(
     conn: &mut Connection,
     init_tokens: TokenStream,
@@ -360,6 +383,23 @@ fn migration_add_and_remove_field(conn: &mut Connection, up_sql: &str, down_sql:
     test_migrate(conn, init, v2, up_sql, down_sql);
 }
 
+fn migration_change_int_width(conn: &mut Connection, up_sql: &str, down_sql: &str) {
+    let init = quote! {
+        struct Foo {
+            id: i64,
+            bar: i32,
+        }
+    };
+
+    let v2 = quote! {
+        struct Foo {
+            id: i64,
+            bar: i16,
+        }
+    };
+    test_migrate(conn, init, v2, up_sql, down_sql);
+}
+
 fn migration_delete_table(conn: &mut Connection, expected_up_sql: &str, expected_down_sql: &str) {
     let init_tokens = quote! {
         struct Foo {
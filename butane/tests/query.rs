@@ -1,7 +1,10 @@
 use butane::db::Connection;
 use butane::prelude::*;
-use butane::query::BoolExpr;
-use butane::{colname, filter, find, query, Many};
+use butane::query::{BoolExpr, Expr, FallibleIterator, Order};
+use butane::{
+    colname, dataresult, filter, find, model, order_by, query, select, ForeignKey, Many,
+    ObjectState, SqlVal,
+};
 use chrono::{TimeZone, Utc};
 use paste;
 use serde_json;
@@ -10,6 +13,55 @@ mod common;
 use common::blog;
 use common::blog::{Blog, Post, PostMetadata, Tag};
 
+#[dataresult(Post)]
+struct BlogPostStats {
+    blog: ForeignKey<Blog>,
+    #[count]
+    count: i64,
+    #[sum(likes)]
+    total_likes: Option<i64>,
+    #[sum(likes)]
+    total_likes_int: Option<i32>,
+    #[max(likes)]
+    max_likes: Option<i32>,
+}
+
+#[model]
+struct Comment {
+    #[pk]
+    id: i64,
+    post: ForeignKey<Post>,
+    text: String,
+}
+impl Comment {
+    fn new(id: i64, post: i64, text: &str) -> Self {
+        Comment {
+            id,
+            post: ForeignKey::from_pk(post),
+            text: text.to_string(),
+            state: ObjectState::default(),
+        }
+    }
+}
+
+#[model]
+struct Employee {
+    #[pk]
+    id: i64,
+    name: String,
+    manager: ForeignKey<Employee>,
+}
+impl Employee {
+    fn new(id: i64, name: &str, manager: i64) -> Self {
+        Employee {
+            id,
+            name: name.to_string(),
+            manager: ForeignKey::from_pk(manager),
+            state: ObjectState::default(),
+        }
+    }
+}
+
 fn equality(conn: Connection) {
     blog::setup_blog(&conn);
     let mut posts = query!(Post, published == true).load(&conn).unwrap();
@@ -45,6 +97,82 @@ fn ordered(conn: Connection) {
 }
 testall!(ordered);
 
+fn ordered_nulls(conn: Connection) {
+    blog::setup_blog(&conn);
+    // Only "The Tiger" has a pub_time
+    let posts = Post::query()
+        .order_by(order_by!(Post, pub_time nulls first, id desc))
+        .load(&conn)
+        .unwrap();
+    let ids: Vec<i64> = posts.iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![4, 3, 2, 1]);
+
+    let posts = Post::query()
+        .order_by(order_by!(Post, pub_time desc nulls last, id))
+        .load(&conn)
+        .unwrap();
+    let ids: Vec<i64> = posts.iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![1, 2, 3, 4]);
+}
+testall!(ordered_nulls);
+
+fn ordered_by_related(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = Post::query()
+        .order_by(order_by!(Post, blog.name desc, title))
+        .load(&conn)
+        .unwrap();
+    let titles: Vec<&str> = posts.iter().map(|p| p.title.as_str()).collect();
+    assert_eq!(
+        titles,
+        vec!["Mount Doom", "Mt. Everest", "Sir Charles", "The Tiger"]
+    );
+}
+testall!(ordered_by_related);
+
+fn ordered_by_expression(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = Post::query()
+        .order_by(order_by!(Post, lower(title) desc))
+        .load(&conn)
+        .unwrap();
+    let titles: Vec<&str> = posts.iter().map(|p| p.title.as_str()).collect();
+    assert_eq!(
+        titles,
+        vec!["The Tiger", "Sir Charles", "Mt. Everest", "Mount Doom"]
+    );
+
+    // likes: 4, 20, 10, 0
+    let posts = query!(Post, published == true)
+        .order_by(order_by!(Post, likes % 8, id))
+        .load(&conn)
+        .unwrap();
+    let ids: Vec<i64> = posts.iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![3, 1, 2]);
+}
+testall!(ordered_by_expression);
+
+fn ordered_randomly(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut ids: Vec<i64> = Post::query()
+        .order_by(vec![Order::random()])
+        .load(&conn)
+        .unwrap()
+        .iter()
+        .map(|p| p.id)
+        .collect();
+    ids.sort_unstable();
+    assert_eq!(ids, vec![1, 2, 3, 4]);
+
+    // A function without arguments is not a constant
+    let posts = Post::query()
+        .order_by(order_by!(Post, random()))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 4);
+}
+testall!(ordered_randomly);
+
 fn comparison(conn: Connection) {
     blog::setup_blog(&conn);
     let mut posts = query!(Post, likes < 5).load(&conn).unwrap();
@@ -55,6 +183,150 @@ fn comparison(conn: Connection) {
 }
 testall!(comparison);
 
+fn column_comparison(conn: Connection) {
+    Employee::new(1, "Alice", 1).save(&conn).unwrap();
+    Employee::new(2, "Bob", 1).save(&conn).unwrap();
+    let employees = query!(Employee, manager == id).load(&conn).unwrap();
+    assert_eq!(employees.len(), 1);
+    assert_eq!(employees[0].name, "Alice");
+    let employees = query!(Employee, manager != id).load(&conn).unwrap();
+    assert_eq!(employees.len(), 1);
+    assert_eq!(employees[0].name, "Bob");
+}
+testall!(column_comparison);
+
+fn arithmetic_comparison(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = query!(Post, likes * 2 > likes + 15).load(&conn).unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Sir Charles");
+
+    let posts = query!(Post, likes - 7 >= 3)
+        .order_asc(colname!(Post, id))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].title, "Sir Charles");
+    assert_eq!(posts[1].title, "Mount Doom");
+}
+testall!(arithmetic_comparison);
+
+fn arithmetic_parens_and_values(conn: Connection) {
+    blog::setup_blog(&conn);
+    let ids: Vec<i64> = query!(Post, (likes + 2) % 4 == 2)
+        .order_asc(colname!(Post, id))
+        .load(&conn)
+        .unwrap()
+        .into_iter()
+        .map(|p| p.id)
+        .collect();
+    assert_eq!(ids, vec![1, 2, 4]);
+
+    let base = 8;
+    let posts = query!(Post, likes > { base } + likes / 2)
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Sir Charles");
+}
+testall!(arithmetic_parens_and_values);
+
+fn negation(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = query!(Post, !(published == true)).load(&conn).unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Mt. Everest");
+
+    let posts = query!(Post, !(likes > 5 || id == 1)).load(&conn).unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Mt. Everest");
+}
+testall!(negation);
+
+fn grouped_or(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = query!(Post, (published == false || likes > 15) && id != 4)
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Sir Charles");
+}
+testall!(grouped_or);
+
+fn is_none_is_some(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = query!(Post, pub_time.is_none()).load(&conn).unwrap();
+    assert_eq!(posts.len(), 3);
+    let posts = query!(Post, pub_time.is_some()).load(&conn).unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "The Tiger");
+}
+testall!(is_none_is_some);
+
+fn in_list(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = query!(Post, id.in_([1, 3]))
+        .order_asc(colname!(Post, id))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].title, "The Tiger");
+    assert_eq!(posts[1].title, "Mount Doom");
+
+    let titles = vec!["Sir Charles".to_string(), "Mt. Everest".to_string()];
+    let posts = query!(Post, title.in_(&titles) && published == true)
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Sir Charles");
+
+    let ids: Vec<i64> = Vec::new();
+    let posts = query!(Post, id.in_(&ids)).load(&conn).unwrap();
+    assert!(posts.is_empty());
+}
+testall!(in_list);
+
+fn not_in_list(conn: Connection) {
+    blog::setup_blog(&conn);
+    let ids = vec![1, 2, 3];
+    let posts = query!(Post, id.not_in(ids.iter())).load(&conn).unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Mt. Everest");
+
+    let posts = query!(Post, id.not_in(Vec::<i64>::new()))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 4);
+
+    // Posts without a pub_time are matched only when asked for
+    let times = vec![chrono::NaiveDate::from_ymd_opt(2000, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap()];
+    let not_in = Post::fields()
+        .pub_time()
+        .not_in::<chrono::NaiveDateTime, _>(&times);
+    let posts = Post::query().filter(not_in.clone()).load(&conn).unwrap();
+    assert_eq!(posts.len(), 1);
+    let posts = Post::query()
+        .filter(BoolExpr::Or(
+            Box::new(not_in),
+            Box::new(filter!(Post, pub_time.is_none())),
+        ))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 4);
+}
+testall!(not_in_list);
+
+fn in_list_many_values(conn: Connection) {
+    blog::setup_blog(&conn);
+    // More placeholders than fit in an i8
+    let posts = query!(Post, id.in_(0..300)).load(&conn).unwrap();
+    assert_eq!(posts.len(), 4);
+}
+testall!(in_list_many_values);
+
 fn like(conn: Connection) {
     blog::setup_blog(&conn);
     let mut posts = query!(Post, title.like("M%")).load(&conn).unwrap();
@@ -65,6 +337,108 @@ fn like(conn: Connection) {
 }
 testall!(like);
 
+fn ilike(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = query!(Post, title.ilike("mOUNT%")).load(&conn).unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Mount Doom");
+}
+testall!(ilike);
+
+fn string_predicates(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut posts = query!(Post, title.starts_with("M")).load(&conn).unwrap();
+    assert_eq!(posts.len(), 2);
+    posts.sort_by(|p1, p2| p1.id.partial_cmp(&p2.id).unwrap());
+    assert_eq!(posts[0].title, "Mount Doom");
+    assert_eq!(posts[1].title, "Mt. Everest");
+
+    let posts = query!(Post, title.ends_with("Doom")).load(&conn).unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Mount Doom");
+
+    let name = "t. E";
+    let posts = query!(Post, title.contains_str({ name }))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Mt. Everest");
+}
+testall!(string_predicates);
+
+fn string_predicates_escaped(conn: Connection) {
+    blog::setup_blog(&conn);
+    let blog = Blog::get(&conn, 1).unwrap();
+    Post::new(5, "50% off_sale", "", &blog).save(&conn).unwrap();
+    Post::new(6, "50 percent offXsale", "", &blog)
+        .save(&conn)
+        .unwrap();
+    Post::new(7, "back\\slash", "", &blog).save(&conn).unwrap();
+
+    let posts = query!(Post, title.contains_str("0% off_"))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].id, 5);
+
+    let posts = query!(Post, title.starts_with("back\\"))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].id, 7);
+}
+testall!(string_predicates_escaped);
+
+fn string_predicates_case_sensitive(conn: Connection) {
+    blog::setup_blog(&conn);
+    let blog = Blog::get(&conn, 1).unwrap();
+    Post::new(5, "Glob *wild? [chars]", "", &blog)
+        .save(&conn)
+        .unwrap();
+
+    assert_eq!(
+        query!(Post, title.starts_with("m"))
+            .load(&conn)
+            .unwrap()
+            .len(),
+        0
+    );
+    assert_eq!(
+        query!(Post, title.ends_with("DOOM"))
+            .load(&conn)
+            .unwrap()
+            .len(),
+        0
+    );
+    let posts = query!(Post, title.contains_str("Doom"))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Mount Doom");
+    assert_eq!(
+        query!(Post, title.contains_str("doom"))
+            .load(&conn)
+            .unwrap()
+            .len(),
+        0
+    );
+
+    // GLOB's wildcards are matched literally
+    let posts = query!(Post, title.contains_str("*wild? [chars]"))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].id, 5);
+    assert_eq!(
+        query!(Post, title.contains_str("*w?ld"))
+            .load(&conn)
+            .unwrap()
+            .len(),
+        0
+    );
+}
+testall!(string_predicates_case_sensitive);
+
 fn combination(conn: Connection) {
     blog::setup_blog(&conn);
     let posts = query!(Post, published == true && likes < 5)
@@ -230,3 +604,974 @@ fn offset(conn: Connection) {
     assert_eq!(posts[1].title, "The Tiger");
 }
 testall!(offset);
+
+fn count(conn: Connection) {
+    blog::setup_blog(&conn);
+    assert_eq!(Post::query().count(&conn).unwrap(), 4);
+    assert_eq!(query!(Post, published == true).count(&conn).unwrap(), 3);
+    assert_eq!(query!(Post, likes > 100).count(&conn).unwrap(), 0);
+}
+testall!(count);
+
+fn aggregates(conn: Connection) {
+    blog::setup_blog(&conn);
+    let sum: Option<i64> = Post::query().sum(&conn, Post::fields().likes()).unwrap();
+    assert_eq!(sum, Some(34));
+    let avg = query!(Post, published == true)
+        .avg(&conn, Post::fields().likes())
+        .unwrap()
+        .unwrap();
+    assert!((avg - 34.0 / 3.0).abs() < 0.0001);
+    let min: Option<i32> = Post::query().min(&conn, Post::fields().likes()).unwrap();
+    assert_eq!(min, Some(0));
+    let max: Option<String> = Post::query().max(&conn, Post::fields().title()).unwrap();
+    assert_eq!(max.unwrap(), "The Tiger");
+}
+testall!(aggregates);
+
+fn aggregates_no_rows(conn: Connection) {
+    blog::setup_blog(&conn);
+    assert_eq!(
+        query!(Post, likes > 100)
+            .sum(&conn, Post::fields().likes())
+            .unwrap(),
+        None
+    );
+    assert_eq!(
+        query!(Post, likes > 100)
+            .avg(&conn, Post::fields().likes())
+            .unwrap(),
+        None
+    );
+    let max: Option<i32> = query!(Post, likes > 100)
+        .max(&conn, Post::fields().likes())
+        .unwrap();
+    assert_eq!(max, None);
+}
+testall!(aggregates_no_rows);
+
+fn group_by(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut stats = BlogPostStats::query()
+        .group_by(&[colname!(Post, blog)])
+        .load(&conn)
+        .unwrap();
+    stats.sort_by_key(|s| s.blog.pk());
+    assert_eq!(stats.len(), 2);
+    assert_eq!(stats[0].blog.pk(), 1);
+    assert_eq!(stats[0].count, 2);
+    assert_eq!(stats[0].total_likes, Some(24));
+    assert_eq!(stats[0].total_likes_int, Some(24));
+    assert_eq!(stats[0].max_likes, Some(20));
+    assert_eq!(stats[1].blog.pk(), 2);
+    assert_eq!(stats[1].count, 2);
+    assert_eq!(stats[1].total_likes, Some(10));
+    assert_eq!(stats[1].max_likes, Some(10));
+}
+testall!(group_by);
+
+fn group_by_having(conn: Connection) {
+    blog::setup_blog(&conn);
+    let stats = query!(BlogPostStats, published == true)
+        .group_by(&[colname!(Post, blog)])
+        .having(BlogPostStats::fields().total_likes().gt(&20))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].blog.pk(), 1);
+    assert_eq!(stats[0].count, 2);
+}
+testall!(group_by_having);
+
+fn update(conn: Connection) {
+    blog::setup_blog(&conn);
+    let cnt = query!(Post, published == false)
+        .update(&conn, |f| f.published().set(true))
+        .unwrap();
+    assert_eq!(cnt, 1);
+    assert_eq!(query!(Post, published == true).count(&conn).unwrap(), 4);
+}
+testall!(update);
+
+fn update_multiple_fields(conn: Connection) {
+    blog::setup_blog(&conn);
+    let cnt = query!(Post, likes >= 10)
+        .update(&conn, |f| [f.likes().set(0), f.title().set("Popular")])
+        .unwrap();
+    assert_eq!(cnt, 2);
+    let posts = query!(Post, title == "Popular").load(&conn).unwrap();
+    assert_eq!(posts.len(), 2);
+    assert!(posts.iter().all(|p| p.likes == 0));
+    // Unmatched posts are left alone
+    assert_eq!(Post::get(&conn, 1).unwrap().title, "The Tiger");
+}
+testall!(update_multiple_fields);
+
+fn update_no_match(conn: Connection) {
+    blog::setup_blog(&conn);
+    let cnt = query!(Post, likes > 100)
+        .update(&conn, |f| f.published().set(false))
+        .unwrap();
+    assert_eq!(cnt, 0);
+    assert_eq!(query!(Post, published == true).count(&conn).unwrap(), 3);
+}
+testall!(update_no_match);
+
+fn prefetch_foreign_key(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut posts = Post::query().prefetch(|f| f.blog()).load(&conn).unwrap();
+    posts.sort_by_key(|p| p.id);
+    assert_eq!(posts.len(), 4);
+    assert_eq!(posts[0].blog.get().unwrap().name, "Cats");
+    assert_eq!(posts[1].blog.get().unwrap().name, "Cats");
+    assert_eq!(posts[2].blog.get().unwrap().name, "Mountains");
+    assert_eq!(posts[3].blog.get().unwrap().name, "Mountains");
+
+    // Without prefetching, the values are not loaded
+    let post = Post::get(&conn, 1).unwrap();
+    assert!(post.blog.get().is_err());
+}
+testall!(prefetch_foreign_key);
+
+fn prefetch_load_first(conn: Connection) {
+    blog::setup_blog(&conn);
+    let post = query!(Post, title == "Mount Doom")
+        .prefetch(|f| f.blog())
+        .load_first(&conn)
+        .unwrap()
+        .unwrap();
+    assert_eq!(post.blog.get().unwrap().name, "Mountains");
+}
+testall!(prefetch_load_first);
+
+fn prefetch_dataresult(conn: Connection) {
+    blog::setup_blog(&conn);
+    let stats = query!(BlogPostStats, published == true)
+        .group_by(&[colname!(Post, blog)])
+        .having(BlogPostStats::fields().total_likes().gt(&20))
+        .prefetch(|f| f.blog())
+        .load(&conn)
+        .unwrap();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].blog.get().unwrap().name, "Cats");
+}
+testall!(prefetch_dataresult);
+
+fn prefetch_many(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut posts = Post::query().prefetch(|f| f.tags()).load(&conn).unwrap();
+    posts.sort_by_key(|p| p.id);
+    let tag_names = |post: &Post| {
+        let mut tags: Vec<String> = post.tags.get().unwrap().map(|t| t.tag.clone()).collect();
+        tags.sort();
+        tags
+    };
+    assert_eq!(tag_names(&posts[0]), vec!["asia", "danger"]);
+    assert!(tag_names(&posts[1]).is_empty());
+    assert_eq!(tag_names(&posts[2]), vec!["danger"]);
+    assert_eq!(tag_names(&posts[3]), vec!["danger"]);
+
+    // Without prefetching, the values are not loaded
+    let post = Post::get(&conn, 1).unwrap();
+    assert!(post.tags.get().is_err());
+}
+testall!(prefetch_many);
+
+fn prefetch_many_and_foreign_key(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = query!(Post, title == "Mount Doom")
+        .prefetch(|f| f.tags())
+        .prefetch(|f| f.blog())
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].blog.get().unwrap().name, "Mountains");
+    assert_eq!(posts[0].tags.get().unwrap().count(), 1);
+}
+testall!(prefetch_many_and_foreign_key);
+
+fn backref_load(conn: Connection) {
+    blog::setup_blog(&conn);
+    let blog: Blog = find!(Blog, name == "Cats", &conn).unwrap();
+    assert!(blog.posts.get().is_err());
+    let mut titles: Vec<&str> = blog
+        .posts
+        .load(&conn)
+        .unwrap()
+        .map(|p| p.title.as_str())
+        .collect();
+    titles.sort_unstable();
+    assert_eq!(titles, vec!["Sir Charles", "The Tiger"]);
+}
+testall!(backref_load);
+
+fn backref_after_save(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut blog = Blog::new(3, "Empty");
+    blog.save(&conn).unwrap();
+    assert_eq!(blog.posts.load(&conn).unwrap().count(), 0);
+}
+testall!(backref_after_save);
+
+fn backref_contains(conn: Connection) {
+    blog::setup_blog(&conn);
+    let blogs = query!(Blog, posts.contains(likes > 15))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(blogs.len(), 1);
+    assert_eq!(blogs[0].name, "Cats");
+
+    let blogs = query!(Blog, posts.contains(3)).load(&conn).unwrap();
+    assert_eq!(blogs.len(), 1);
+    assert_eq!(blogs[0].name, "Mountains");
+}
+testall!(backref_contains);
+
+fn prefetch_backref(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut blogs = Blog::query().prefetch(|f| f.posts()).load(&conn).unwrap();
+    blogs.sort_by_key(|b| b.id);
+    assert_eq!(blogs.len(), 2);
+    let mut ids: Vec<i64> = blogs[0].posts.get().unwrap().map(|p| p.id).collect();
+    ids.sort_unstable();
+    assert_eq!(ids, vec![1, 2]);
+    let mut ids: Vec<i64> = blogs[1].posts.get().unwrap().map(|p| p.id).collect();
+    ids.sort_unstable();
+    assert_eq!(ids, vec![3, 4]);
+}
+testall!(prefetch_backref);
+
+fn iter(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut titles: Vec<String> = query!(Post, published == true)
+        .order_asc(colname!(Post, title))
+        .iter(&conn)
+        .unwrap()
+        .map(|post| Ok(post.title))
+        .collect()
+        .unwrap();
+    assert_eq!(titles, vec!["Mount Doom", "Sir Charles", "The Tiger"]);
+
+    // The iterator may be abandoned early
+    let mut it = Post::query().iter(&conn).unwrap();
+    assert!(it.next().unwrap().is_some());
+    drop(it);
+    titles = Post::query()
+        .load(&conn)
+        .unwrap()
+        .into_iter()
+        .map(|p| p.title)
+        .collect();
+    assert_eq!(titles.len(), 4);
+}
+testall!(iter);
+
+fn iter_empty(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut it = query!(Post, likes > 100).iter(&conn).unwrap();
+    assert!(it.next().unwrap().is_none());
+}
+testall!(iter_empty);
+
+fn iter_many(conn: Connection) {
+    // More rows than the Postgres backend fetches in one batch
+    for i in 0..600 {
+        Tag::new(&format!("tag{i:03}")).save(&conn).unwrap();
+    }
+    let tags: Vec<String> = Tag::query()
+        .order_asc(colname!(Tag, tag))
+        .iter(&conn)
+        .unwrap()
+        .map(|tag| Ok(tag.tag))
+        .collect()
+        .unwrap();
+    assert_eq!(tags.len(), 600);
+    assert_eq!(tags[0], "tag000");
+    assert_eq!(tags[599], "tag599");
+}
+testall!(iter_many);
+
+fn iter_busy_connection(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut it = Post::query().iter(&conn).unwrap();
+    assert!(it.next().unwrap().is_some());
+    if conn.backend_name() == "pg" {
+        // The connection is in use until the iterator is dropped
+        assert!(Blog::query().load(&conn).is_err());
+    }
+    drop(it);
+    assert_eq!(Blog::query().load(&conn).unwrap().len(), 2);
+}
+testall!(iter_busy_connection);
+
+fn iter_prefetch(conn: Connection) {
+    blog::setup_blog(&conn);
+    let result = Post::query().prefetch(|f| f.blog()).iter(&conn);
+    assert!(matches!(
+        result.err(),
+        Some(butane::Error::UnsupportedForPrefetch(_))
+    ));
+}
+testall!(iter_prefetch);
+
+fn join(conn: Connection) {
+    blog::setup_blog(&conn);
+    let pairs = query!(Post, published == true)
+        .order_asc(colname!(Post, id))
+        .join::<Blog>(|f| f.blog())
+        .load(&conn)
+        .unwrap();
+    assert_eq!(pairs.len(), 3);
+    assert_eq!(pairs[0].0.title, "The Tiger");
+    assert_eq!(pairs[0].1.name, "Cats");
+    assert_eq!(pairs[1].0.title, "Sir Charles");
+    assert_eq!(pairs[1].1.name, "Cats");
+    assert_eq!(pairs[2].0.title, "Mount Doom");
+    assert_eq!(pairs[2].1.name, "Mountains");
+    // Columns with the same name in both tables are kept distinct
+    assert_eq!(pairs[2].0.id, 3);
+    assert_eq!(pairs[2].1.id, 2);
+}
+testall!(join);
+
+fn left_join(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut orphan = Post::new(
+        5,
+        "Orphan",
+        "This post's blog does not exist",
+        &Blog::new(99, "Missing"),
+    );
+    orphan.save(&conn).unwrap();
+
+    let pairs = query!(Post, id >= 4)
+        .order_asc(colname!(Post, id))
+        .left_join::<Blog>(|f| f.blog())
+        .load(&conn)
+        .unwrap();
+    assert_eq!(pairs.len(), 2);
+    assert_eq!(pairs[0].1.as_ref().unwrap().name, "Mountains");
+    assert_eq!(pairs[1].0.title, "Orphan");
+    assert!(pairs[1].1.is_none());
+
+    // An inner join omits the orphan
+    let pairs = query!(Post, id >= 4)
+        .join::<Blog>(|f| f.blog())
+        .load(&conn)
+        .unwrap();
+    assert_eq!(pairs.len(), 1);
+}
+testall!(left_join);
+
+fn join_limit_offset(conn: Connection) {
+    blog::setup_blog(&conn);
+    let pairs = Post::query()
+        .order_desc(colname!(Post, id))
+        .limit(2)
+        .offset(1)
+        .join::<Blog>(|f| f.blog())
+        .load(&conn)
+        .unwrap();
+    assert_eq!(pairs.len(), 2);
+    assert_eq!(pairs[0].0.id, 3);
+    assert_eq!(pairs[1].0.id, 2);
+    assert_eq!(pairs[1].1.name, "Cats");
+}
+testall!(join_limit_offset);
+
+fn join_self_referential(conn: Connection) {
+    // The first employee is their own manager
+    Employee::new(1, "Alice", 1).save(&conn).unwrap();
+    Employee::new(2, "Bob", 1).save(&conn).unwrap();
+    Employee::new(3, "Carol", 2).save(&conn).unwrap();
+    let pairs = query!(Employee, id > 1)
+        .order_asc(colname!(Employee, id))
+        .join::<Employee>(|f| f.manager())
+        .load(&conn)
+        .unwrap();
+    assert_eq!(pairs.len(), 2);
+    assert_eq!(pairs[0].0.name, "Bob");
+    assert_eq!(pairs[0].1.name, "Alice");
+    assert_eq!(pairs[1].0.name, "Carol");
+    assert_eq!(pairs[1].1.name, "Bob");
+}
+testall!(join_self_referential);
+
+fn join_unsupported(conn: Connection) {
+    blog::setup_blog(&conn);
+    let result = Post::query()
+        .distinct()
+        .join::<Blog>(|f| f.blog())
+        .load(&conn);
+    assert!(matches!(
+        result.err(),
+        Some(butane::Error::UnsupportedForJoin("distinct"))
+    ));
+    let result = Post::query()
+        .prefetch(|f| f.tags())
+        .join::<Blog>(|f| f.blog())
+        .load(&conn);
+    assert!(matches!(
+        result.err(),
+        Some(butane::Error::UnsupportedForJoin("prefetch"))
+    ));
+}
+testall!(join_unsupported);
+
+fn select(conn: Connection) {
+    blog::setup_blog(&conn);
+    let rows: Vec<(i64, String)> = select!(Post, (id, title), published == true)
+        .order_asc(colname!(Post, id))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(
+        rows,
+        vec![
+            (1, "The Tiger".to_string()),
+            (2, "Sir Charles".to_string()),
+            (3, "Mount Doom".to_string())
+        ]
+    );
+}
+testall!(select);
+
+fn select_all_rows(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut rows = select!(Post, (likes,)).load(&conn).unwrap();
+    rows.sort_unstable();
+    assert_eq!(rows, vec![(0,), (4,), (10,), (20,)]);
+}
+testall!(select_all_rows);
+
+fn select_nullable_and_fkey(conn: Connection) {
+    blog::setup_blog(&conn);
+    let rows = select!(Post, (blog, pub_time), title == "Sir Charles")
+        .load(&conn)
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].0.pk(), 1);
+    assert!(rows[0].1.is_none());
+}
+testall!(select_nullable_and_fkey);
+
+fn select_distinct(conn: Connection) {
+    blog::setup_blog(&conn);
+    let rows: Vec<(bool,)> = select!(Post, (published))
+        .distinct()
+        .order_asc(colname!(Post, published))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(rows, vec![(false,), (true,)]);
+}
+testall!(select_distinct);
+
+fn exists(conn: Connection) {
+    blog::setup_blog(&conn);
+    assert!(query!(Post, likes > 15).exists(&conn).unwrap());
+    assert!(!query!(Post, likes > 100).exists(&conn).unwrap());
+    assert!(Tag::query().exists(&conn).unwrap());
+}
+testall!(exists);
+
+fn exists_correlated(conn: Connection) {
+    blog::setup_blog(&conn);
+    let blogs = query!(Blog, exists(Post, blog == self.id && !published))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(blogs.len(), 1);
+    assert_eq!(blogs[0].name, "Mountains");
+
+    let blogs = query!(Blog, exists(Post, blog == self.id && likes > 15))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(blogs.len(), 1);
+    assert_eq!(blogs[0].name, "Cats");
+
+    let blogs = query!(Blog, !exists(Post, blog == self.id && likes > 100))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(blogs.len(), 2);
+
+    // The subquery may be on the same table as the enclosing query
+    Employee::new(1, "Alice", 1).save(&conn).unwrap();
+    Employee::new(2, "Bob", 1).save(&conn).unwrap();
+    Employee::new(3, "Carol", 2).save(&conn).unwrap();
+    let managers = query!(
+        Employee,
+        exists(Employee, manager == self.id && id != self.id)
+    )
+    .order_asc(colname!(Employee, id))
+    .load(&conn)
+    .unwrap();
+    assert_eq!(managers.len(), 2);
+    assert_eq!(managers[0].name, "Alice");
+    assert_eq!(managers[1].name, "Bob");
+}
+testall!(exists_correlated);
+
+fn filter_fkey_path(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut posts = query!(Post, blog.name == "Cats").load(&conn).unwrap();
+    posts.sort_by_key(|p| p.id);
+    let ids: Vec<i64> = posts.iter().map(|p| p.id).collect();
+    assert_eq!(ids, vec![1, 2]);
+}
+testall!(filter_fkey_path);
+
+fn filter_fkey_path_multi_hop(conn: Connection) {
+    blog::setup_blog(&conn);
+    Comment::new(1, 1, "Grr").save(&conn).unwrap();
+    Comment::new(2, 3, "Brr").save(&conn).unwrap();
+    Comment::new(3, 2, "Purr").save(&conn).unwrap();
+
+    let mut comments = query!(Comment, post.blog.name == "Cats")
+        .load(&conn)
+        .unwrap();
+    comments.sort_by_key(|c| c.id);
+    let ids: Vec<i64> = comments.iter().map(|c| c.id).collect();
+    assert_eq!(ids, vec![1, 3]);
+
+    // Both Post and Blog have an id column.
+    let blog_id = 2;
+    let comments = query!(Comment, post.blog.id == { blog_id } && text != "Grr")
+        .load(&conn)
+        .unwrap();
+    assert_eq!(comments.len(), 1);
+    assert_eq!(comments[0].text, "Brr");
+}
+testall!(filter_fkey_path_multi_hop);
+
+fn post_ids(posts: &[Post]) -> Vec<i64> {
+    posts.iter().map(|p| p.id).collect()
+}
+
+fn union(conn: Connection) {
+    blog::setup_blog(&conn);
+    // Each side keeps its own order and limit
+    let most_liked = || Post::query().order_desc(colname!(Post, likes)).limit(1);
+    let first_titles = || Post::query().order_asc(colname!(Post, title)).limit(2);
+    let posts = most_liked()
+        .union(first_titles())
+        .order_asc(colname!(Post, id))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(post_ids(&posts), vec![2, 3, 4]);
+
+    // The order and limit of the union apply to the combined results
+    let posts = most_liked()
+        .union(first_titles())
+        .order_desc(colname!(Post, id))
+        .limit(2)
+        .load(&conn)
+        .unwrap();
+    assert_eq!(post_ids(&posts), vec![4, 3]);
+
+    // Duplicates are omitted
+    let posts = query!(Post, likes > 5)
+        .union(query!(Post, published == true))
+        .order_asc(colname!(Post, id))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(post_ids(&posts), vec![1, 2, 3]);
+}
+testall!(union);
+
+fn intersect_except(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = query!(Post, published == true)
+        .intersect(query!(Post, likes < 15))
+        .order_asc(colname!(Post, id))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(post_ids(&posts), vec![1, 3]);
+
+    let posts = Post::query()
+        .except(query!(Post, published == true))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(post_ids(&posts), vec![4]);
+
+    // Compound queries may be combined further and filtered
+    let posts = query!(Post, likes > 15)
+        .union(query!(Post, likes < 5))
+        .except(query!(Post, published == false))
+        .filter(filter!(Post, likes > 1))
+        .order_asc(colname!(Post, id))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(post_ids(&posts), vec![1, 2]);
+
+    assert!(Post::query().union(Post::query()).count(&conn).is_err());
+}
+testall!(intersect_except);
+
+fn compound_qualified_columns(conn: Connection) {
+    blog::setup_blog(&conn);
+    // Filters and order terms which refer to the table by name apply
+    // to the combined results
+    let posts = query!(Post, likes > 15)
+        .union(query!(Post, likes < 5))
+        .order_by(order_by!(Post, blog.name desc, id))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(post_ids(&posts), vec![4, 1, 2]);
+
+    let posts = query!(Post, likes > 15)
+        .union(query!(Post, likes < 5))
+        .filter(filter!(Post, blog.name == "Cats"))
+        .order_asc(colname!(Post, id))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(post_ids(&posts), vec![1, 2]);
+
+    let blogs = Blog::query()
+        .except(query!(Blog, name == "Cats"))
+        .filter(filter!(Blog, exists(Post, blog == self.id && !published)))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(blogs.len(), 1);
+    assert_eq!(blogs[0].name, "Mountains");
+
+    // Relationships prefetched for an operand are prefetched for the
+    // combined results
+    let posts = query!(Post, likes > 15)
+        .prefetch(|f| f.blog())
+        .union(query!(Post, likes < 5))
+        .order_asc(colname!(Post, id))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(post_ids(&posts), vec![1, 2, 4]);
+    assert_eq!(posts[0].blog.get().unwrap().name, "Cats");
+    assert_eq!(posts[2].blog.get().unwrap().name, "Mountains");
+}
+testall!(compound_qualified_columns);
+
+fn to_sql(conn: Connection) {
+    let (sql, values) = query!(Post, likes > 5 && title == "The Tiger")
+        .order_asc(colname!(Post, title))
+        .limit(2)
+        .to_sql(&conn.backend())
+        .unwrap();
+    let placeholder = match conn.backend_name() {
+        "pg" => "$1",
+        _ => "?",
+    };
+    assert_eq!(
+        sql,
+        format!(
+            "SELECT id,title,body,published,pub_time,likes,blog FROM Post \
+             WHERE likes > 5 AND title = {} ORDER BY title ASC LIMIT 2",
+            placeholder
+        )
+    );
+    assert_eq!(values, vec![SqlVal::Text("The Tiger".to_string())]);
+}
+testall!(to_sql);
+
+fn explain(conn: Connection) {
+    blog::setup_blog(&conn);
+    let plan = query!(Post, likes > 5).explain(&conn).unwrap();
+    assert!(!plan.is_empty());
+}
+testall!(explain);
+
+fn page_ids(page: &butane::query::Page<Post>) -> Vec<i64> {
+    page.objects.iter().map(|p| p.id).collect()
+}
+
+fn keyset_pagination(conn: Connection) {
+    blog::setup_blog(&conn);
+    let page = Post::query().limit(2).load_page(&conn).unwrap();
+    assert_eq!(page_ids(&page), vec![1, 2]);
+
+    let page = Post::query()
+        .limit(2)
+        .after(page.last.unwrap())
+        .load_page(&conn)
+        .unwrap();
+    assert_eq!(page_ids(&page), vec![3, 4]);
+
+    let page = Post::query()
+        .limit(2)
+        .after(page.last.unwrap())
+        .load_page(&conn)
+        .unwrap();
+    assert!(page.objects.is_empty());
+    assert!(page.first.is_none());
+    assert!(page.last.is_none());
+}
+testall!(keyset_pagination);
+
+fn keyset_pagination_ordered(conn: Connection) {
+    blog::setup_blog(&conn);
+    let query = || Post::query().order_desc(colname!(Post, likes)).limit(2);
+    let page = query().load_page(&conn).unwrap();
+    assert_eq!(page_ids(&page), vec![2, 3]);
+
+    let page = query().after(page.last.unwrap()).load_page(&conn).unwrap();
+    assert_eq!(page_ids(&page), vec![1, 4]);
+
+    // Paging backwards returns the objects immediately preceding the
+    // cursor, still in the order of the query.
+    let prev = query()
+        .limit(1)
+        .before(page.first.clone().unwrap())
+        .load_page(&conn)
+        .unwrap();
+    assert_eq!(page_ids(&prev), vec![3]);
+    let prev = query()
+        .before(page.first.clone().unwrap())
+        .load_page(&conn)
+        .unwrap();
+    assert_eq!(page_ids(&prev), vec![2, 3]);
+
+    // Cursors may also be used with load
+    let posts = query()
+        .filter(filter!(Post, published == true))
+        .after(prev.first.unwrap())
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.iter().map(|p| p.id).collect::<Vec<i64>>(), vec![3, 1]);
+}
+testall!(keyset_pagination_ordered);
+
+fn keyset_cursor_other_operations(conn: Connection) {
+    blog::setup_blog(&conn);
+    let query = || Post::query().order_desc(colname!(Post, likes));
+    let page = query().limit(2).load_page(&conn).unwrap();
+    let cursor = page.last.unwrap();
+    let page2 = query().after(cursor.clone()).load_page(&conn).unwrap();
+    assert_eq!(page_ids(&page2), vec![1, 4]);
+
+    let first = query().after(cursor.clone()).load_first(&conn).unwrap();
+    assert_eq!(first.unwrap().id, 1);
+    let first = query()
+        .before(page2.first.clone().unwrap())
+        .load_first(&conn)
+        .unwrap();
+    assert_eq!(first.unwrap().id, 3);
+
+    assert_eq!(query().after(cursor.clone()).count(&conn).unwrap(), 2);
+    assert!(query().after(cursor.clone()).exists(&conn).unwrap());
+    assert!(!query()
+        .after(page2.last.clone().unwrap())
+        .exists(&conn)
+        .unwrap());
+    let max = query()
+        .after(cursor.clone())
+        .max(&conn, Post::fields().likes())
+        .unwrap();
+    assert_eq!(max, Some(4));
+
+    let pairs = query()
+        .after(cursor.clone())
+        .join::<Blog>(|f| f.blog())
+        .load(&conn)
+        .unwrap();
+    assert_eq!(
+        pairs.iter().map(|p| p.0.id).collect::<Vec<i64>>(),
+        vec![1, 4]
+    );
+    let pairs = query()
+        .limit(1)
+        .before(page2.first.unwrap())
+        .join::<Blog>(|f| f.blog())
+        .load(&conn)
+        .unwrap();
+    assert_eq!(pairs.iter().map(|p| p.0.id).collect::<Vec<i64>>(), vec![3]);
+
+    let updated = query()
+        .after(cursor.clone())
+        .update(&conn, |f| f.published().set(true))
+        .unwrap();
+    assert_eq!(updated, 2);
+    assert_eq!(query().after(cursor).delete(&conn).unwrap(), 2);
+    assert_eq!(Post::query().count(&conn).unwrap(), 2);
+}
+testall!(keyset_cursor_other_operations);
+
+fn cursor_token(conn: Connection) {
+    blog::setup_blog(&conn);
+    let page = Post::query()
+        .order_asc(colname!(Post, title))
+        .limit(1)
+        .load_page(&conn)
+        .unwrap();
+    let cursor = page.last.unwrap();
+    let token = cursor.to_token();
+    let decoded = butane::query::Cursor::from_token(&token).unwrap();
+    assert_eq!(decoded, cursor);
+
+    let posts = Post::query()
+        .order_asc(colname!(Post, title))
+        .after(decoded)
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 3);
+    assert_eq!(posts[0].title, "Mt. Everest");
+
+    assert!(butane::query::Cursor::from_token("not a token").is_err());
+}
+testall!(cursor_token);
+
+fn prepared_query(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut query = Post::query()
+        .filter(BoolExpr::Gt(colname!(Post, likes), Expr::Placeholder))
+        .order_asc(colname!(Post, id))
+        .prepare(&conn)
+        .unwrap();
+    let ids = |posts: Vec<Post>| posts.iter().map(|p| p.id).collect::<Vec<i64>>();
+    assert_eq!(
+        ids(query.bind([SqlVal::Int(5)]).load().unwrap()),
+        vec![2, 3]
+    );
+    assert_eq!(ids(query.bind([SqlVal::Int(15)]).load().unwrap()), vec![2]);
+    assert!(query.bind([SqlVal::Int(100)]).load().unwrap().is_empty());
+    // The number of values must match the number of placeholders
+    assert!(query.bind([]).load().is_err());
+}
+testall!(prepared_query);
+
+fn prepared_query_mixed_values(conn: Connection) {
+    blog::setup_blog(&conn);
+    // Placeholders are interleaved with values given in the filter
+    let filter = BoolExpr::And(
+        Box::new(BoolExpr::And(
+            Box::new(filter!(Post, title != "Mount Doom")),
+            Box::new(BoolExpr::Gt(colname!(Post, likes), Expr::Placeholder)),
+        )),
+        Box::new(BoolExpr::Lt(colname!(Post, id), Expr::Placeholder)),
+    );
+    let mut query = Post::query()
+        .filter(filter)
+        .order_asc(colname!(Post, id))
+        .prepare(&conn)
+        .unwrap();
+    let ids = |posts: Vec<Post>| posts.iter().map(|p| p.id).collect::<Vec<i64>>();
+    // Integer widths need not match the columns exactly
+    let posts = query
+        .bind([SqlVal::BigInt(3), SqlVal::Int(4)])
+        .load()
+        .unwrap();
+    assert_eq!(ids(posts), vec![1, 2]);
+    let posts = query
+        .bind([SqlVal::Int(5), SqlVal::BigInt(5)])
+        .load()
+        .unwrap();
+    assert_eq!(ids(posts), vec![2]);
+}
+testall!(prepared_query_mixed_values);
+
+fn prepared_query_like_escape(conn: Connection) {
+    blog::setup_blog(&conn);
+    let blog = Blog::get(&conn, 1).unwrap();
+    Post::new(5, "Glob *wild? [chars] 100%", "", &blog)
+        .save(&conn)
+        .unwrap();
+    // The pattern of a prepared query is case-sensitive, as for a
+    // pattern given in the filter
+    let mut query = Post::query()
+        .filter(BoolExpr::LikeEscape(
+            colname!(Post, title),
+            Expr::Placeholder,
+        ))
+        .order_asc(colname!(Post, id))
+        .prepare(&conn)
+        .unwrap();
+    let titles = |posts: Vec<Post>| posts.into_iter().map(|p| p.title).collect::<Vec<_>>();
+    let mut load = |pattern: &str| titles(query.bind([SqlVal::from(pattern)]).load().unwrap());
+    assert_eq!(load("Mo%"), vec!["Mount Doom"]);
+    assert!(load("mo%").is_empty());
+    assert_eq!(load("M_. %"), vec!["Mt. Everest"]);
+    assert_eq!(load("%*wild?%"), vec!["Glob *wild? [chars] 100%"]);
+    assert_eq!(load("%[chars]%"), vec!["Glob *wild? [chars] 100%"]);
+    assert_eq!(load("%100\\%"), vec!["Glob *wild? [chars] 100%"]);
+    assert!(load("%1\\%").is_empty());
+    assert!(load("%\\_%").is_empty());
+}
+testall!(prepared_query_like_escape);
+
+fn prepared_query_no_placeholders(conn: Connection) {
+    blog::setup_blog(&conn);
+    let mut query = query!(Post, published == true).prepare(&conn).unwrap();
+    assert_eq!(query.load().unwrap().len(), 3);
+    Post::get(&conn, 4).unwrap().delete(&conn).unwrap();
+    let mut post = Post::get(&conn, 3).unwrap();
+    post.published = false;
+    post.save(&conn).unwrap();
+    assert_eq!(query.load().unwrap().len(), 2);
+}
+testall!(prepared_query_no_placeholders);
+
+fn from_sql_query(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = Post::from_sql_query(
+        &conn,
+        "SELECT id, title, body, published, pub_time, likes, blog FROM Post \
+         WHERE likes > ? AND title <> 'Why?' ORDER BY id",
+        &[SqlVal::Int(5)],
+    )
+    .unwrap();
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].title, "Sir Charles");
+    assert_eq!(posts[1].title, "Mount Doom");
+    assert_eq!(posts[1].blog.pk(), 2);
+
+    let posts = PostMetadata::from_sql_query(
+        &conn,
+        "SELECT id, title, pub_time FROM Post WHERE published = ?",
+        &[SqlVal::Bool(false)],
+    )
+    .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Mt. Everest");
+}
+testall!(from_sql_query);
+
+fn from_sql_query_mismatched_columns(conn: Connection) {
+    blog::setup_blog(&conn);
+    // Too few columns
+    assert!(PostMetadata::from_sql_query(&conn, "SELECT id, title FROM Post", &[]).is_err());
+    // Wrong types
+    assert!(
+        PostMetadata::from_sql_query(&conn, "SELECT title, id, pub_time FROM Post", &[]).is_err()
+    );
+    // Wrong declared type, even though every value is NULL
+    assert!(PostMetadata::from_sql_query(
+        &conn,
+        "SELECT id, pub_time AS title, pub_time FROM Post WHERE pub_time IS NULL",
+        &[]
+    )
+    .is_err());
+}
+testall!(from_sql_query_mismatched_columns);
+
+fn raw_filter(conn: Connection) {
+    blog::setup_blog(&conn);
+    let posts = query!(Post, title != "Mount Doom" && raw("likes > ?", 5))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 1);
+    assert_eq!(posts[0].title, "Sir Charles");
+
+    let (min, max) = (1, 15);
+    let mut posts = query!(Post, raw("likes BETWEEN ? AND ?", min, max) || id == 4)
+        .order_asc(colname!(Post, id))
+        .load(&conn)
+        .unwrap();
+    assert_eq!(posts.len(), 3);
+    assert_eq!(posts.remove(0).title, "The Tiger");
+    assert_eq!(posts.remove(0).title, "Mount Doom");
+    assert_eq!(posts.remove(0).title, "Mt. Everest");
+
+    // Question marks inside literals and comments are not placeholders
+    let posts = query!(
+        Post,
+        raw("title <> 'Why?' /* skip? */ AND likes > ? -- really?\n", 5)
+    )
+    .load(&conn)
+    .unwrap();
+    assert_eq!(posts.len(), 2);
+}
+testall!(raw_filter);
@@ -2,20 +2,75 @@ use super::*;
 use proc_macro2::Span;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::{quote, quote_spanned, ToTokens};
-use syn::{spanned::Spanned, BinOp, Expr, ExprBinary, ExprMethodCall, ExprPath, Ident, LitStr};
+use syn::{
+    spanned::Spanned, BinOp, Expr, ExprBinary, ExprCall, ExprField, ExprMethodCall, ExprPath,
+    ExprUnary, Ident, Lit, LitStr, Member, UnOp,
+};
+
+/// The fields which an expression may refer to. Within `exists`,
+/// `outer` is the scope of the enclosing expression, whose fields are
+/// referred to as `self.field`.
+#[derive(Clone)]
+pub struct Scope {
+    fields: TokenStream2,
+    table: Option<TokenStream2>,
+    outer: Option<Box<Scope>>,
+}
+
+impl Scope {
+    fn new(fields: TokenStream2) -> Self {
+        Scope {
+            fields,
+            table: None,
+            outer: None,
+        }
+    }
+
+    fn for_model(dbres: &impl ToTokens) -> Self {
+        Scope {
+            fields: quote!(<#dbres as butane::DataResult>::DBO::fields()),
+            table: Some(quote!(
+                <<#dbres as butane::DataResult>::DBO as butane::DataObject>::TABLE
+            )),
+            outer: None,
+        }
+    }
+}
+
+impl ToTokens for Scope {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        self.fields.to_tokens(tokens)
+    }
+}
 
 pub fn for_expr(dbres: &Ident, expr: &Expr) -> TokenStream2 {
-    handle_expr(&quote!(<#dbres as butane::DataResult>::DBO::fields()), expr)
+    handle_condition(&Scope::for_model(dbres), expr)
 }
 
-pub fn handle_expr(fields: &impl ToTokens, expr: &Expr) -> TokenStream2 {
+/// Handles an expression which must be a condition. A bare field is
+/// treated as a boolean field compared with `true`.
+fn handle_condition(fields: &Scope, expr: &Expr) -> TokenStream2 {
+    match strip_group(expr) {
+        Expr::Path(path) if !path.path.is_ident("None") => {
+            let fex = fieldexpr(fields, &path.path);
+            quote!(#fex.eq(&true))
+        }
+        Expr::Paren(paren) => handle_condition(fields, &paren.expr),
+        _ => handle_expr(fields, expr),
+    }
+}
+
+pub fn handle_expr(fields: &Scope, expr: &Expr) -> TokenStream2 {
     match expr {
         Expr::Binary(binop) => handle_bin_op(fields, binop),
         Expr::MethodCall(mcall) => handle_call(fields, mcall),
+        Expr::Call(call) => handle_fn_call(fields, call),
         Expr::Path(path) => handle_path(fields, path),
         Expr::Lit(lit) => lit.lit.clone().into_token_stream(),
         Expr::Block(block) => handle_block(&block.block),
         Expr::Group(group) => handle_expr(fields, group.expr.as_ref()),
+        Expr::Paren(paren) => handle_expr(fields, paren.expr.as_ref()),
+        Expr::Unary(unary) => handle_unary(fields, unary),
         _ => {
             let lit = LitStr::new(
                 &format!(
@@ -39,7 +94,35 @@ fn handle_block(block: &syn::Block) -> TokenStream2 {
     quote!(#(#stmts)*)
 }
 
-fn handle_bin_op(fields: &impl ToTokens, binop: &ExprBinary) -> TokenStream2 {
+fn handle_unary(fields: &Scope, unary: &ExprUnary) -> TokenStream2 {
+    match unary.op {
+        UnOp::Not(_) => {
+            let inner = handle_condition(fields, &unary.expr);
+            quote!(butane::query::BoolExpr::Not(Box::new(#inner)))
+        }
+        _ => make_compile_error!(unary.span()=> "Unsupported unary operator"),
+    }
+}
+
+fn handle_bin_op(fields: &Scope, binop: &ExprBinary) -> TokenStream2 {
+    if let Some(path) = field_path(&binop.left) {
+        return handle_path_compare(fields, binop, &path);
+    }
+    if let Some(op) = compare_op(&binop.op) {
+        if !is_simple_comparison(binop) {
+            return handle_compare(fields, binop, op);
+        }
+    }
+    if let BinOp::And(_) | BinOp::Or(_) = binop.op {
+        let left = handle_condition(fields, &binop.left);
+        let right = handle_condition(fields, &binop.right);
+        return match binop.op {
+            BinOp::And(_) => {
+                quote!(butane::query::BoolExpr::And(Box::new(#left), Box::new(#right)))
+            }
+            _ => quote!(butane::query::BoolExpr::Or(Box::new(#left), Box::new(#right))),
+        };
+    }
     let left = handle_expr(fields, &binop.left);
     let right = handle_expr(fields, &binop.right);
     match binop.op {
@@ -49,31 +132,250 @@ fn handle_bin_op(fields: &impl ToTokens, binop: &ExprBinary) -> TokenStream2 {
         BinOp::Gt(_) => quote!(#left.gt(&#right)),
         BinOp::Le(_) => quote!(#left.le(&#right)),
         BinOp::Ge(_) => quote!(#left.ge(&#right)),
-        BinOp::And(_) => quote!(butane::query::BoolExpr::And(Box::new(#left), Box::new(#right))),
-        BinOp::Or(_) => quote!(butane::query::BoolExpr::Or(Box::new(#left), Box::new(#right))),
         _ => quote!(compile_error!("Unsupported binary operator")),
     }
 }
 
-fn handle_call(fields: &impl ToTokens, mcall: &ExprMethodCall) -> TokenStream2 {
+/// Whether `binop` compares a field directly with a value, in which
+/// case the comparison methods of the field expression are used so
+/// that the types are checked.
+fn is_simple_comparison(binop: &ExprBinary) -> bool {
+    matches!(strip_group(&binop.left), Expr::Path(_)) && !is_value_expr(&binop.right)
+}
+
+/// Whether `expr` must be evaluated by the database, i.e. it refers
+/// to a column or is an arithmetic expression.
+fn is_value_expr(expr: &Expr) -> bool {
+    match strip_group(expr) {
+        Expr::Path(path) => !path.path.is_ident("None"),
+        Expr::Field(field) => is_self(&field.base),
+        Expr::Binary(binop) => arith_op(&binop.op).is_some(),
+        Expr::Paren(_) => true,
+        _ => false,
+    }
+}
+
+/// The segments of a path of fields through foreign keys, like
+/// `blog.owner.country`, or `None` if `expr` is not such a path.
+fn field_path(expr: &Expr) -> Option<Vec<&Ident>> {
+    let field = match strip_group(expr) {
+        Expr::Field(field) if !is_self(&field.base) => field,
+        _ => return None,
+    };
+    let mut path = match strip_group(&field.base) {
+        Expr::Path(base) => vec![base.path.get_ident()?],
+        base => field_path(base)?,
+    };
+    match &field.member {
+        Member::Named(name) => path.push(name),
+        Member::Unnamed(_) => return None,
+    }
+    Some(path)
+}
+
+/// A comparison of a field reached through a path of foreign keys
+/// with a value, e.g. `blog.owner.country == "NZ"`. The referent of
+/// the first foreign key is joined with the referents of the others,
+/// and the comparison is made on the last.
+fn handle_path_compare(fields: &Scope, binop: &ExprBinary, path: &[&Ident]) -> TokenStream2 {
+    let method = match binop.op {
+        BinOp::Eq(_) => "qualified_eq",
+        BinOp::Ne(_) => "qualified_ne",
+        BinOp::Lt(_) => "qualified_lt",
+        BinOp::Gt(_) => "qualified_gt",
+        BinOp::Le(_) => "qualified_le",
+        BinOp::Ge(_) => "qualified_ge",
+        _ => return make_compile_error!(binop.op.span()=> "Unsupported operator for a field path"),
+    };
+    if is_value_expr(&binop.right) {
+        return make_compile_error!(binop.right.span()=> "a field path may only be compared with a value");
+    }
+    let (column, fkeys) = path.split_last().expect("field path is never empty");
+    let mut hops: Vec<TokenStream2> = Vec::with_capacity(fkeys.len());
+    for fkey in fkeys {
+        let span = fkey.span();
+        let hop = match hops.last() {
+            None => fieldexpr(fields, fkey),
+            Some(prev) => quote_spanned!(span=> #prev.fields().#fkey()),
+        };
+        hops.push(hop);
+    }
+    let joins = hops.windows(2).map(|pair| {
+        let (prev, next) = (&pair[0], &pair[1]);
+        quote!(#prev.join_to(&#next))
+    });
+    let first = &hops[0];
+    let last = &hops[hops.len() - 1];
+    let method = Ident::new(method, column.span());
+    let val = handle_expr(fields, &binop.right);
+    let span = column.span();
+    let leaf = quote_spanned!(span=>
+        #last.fields().#column().#method(#last.referent_table(), &#val)
+    );
+    let span = fkeys[0].span();
+    quote_spanned!(span=> #first.subfilter_joined(vec![#(#joins),*], #leaf))
+}
+
+fn strip_group(expr: &Expr) -> &Expr {
+    match expr {
+        Expr::Group(group) => strip_group(&group.expr),
+        _ => expr,
+    }
+}
+
+/// The comparison method of `butane::query::ValueExpr` for `op`,
+/// along with the method for the same comparison with its operands
+/// swapped.
+fn compare_op(op: &BinOp) -> Option<(&'static str, &'static str)> {
+    Some(match op {
+        BinOp::Eq(_) => ("eq", "eq"),
+        BinOp::Ne(_) => ("ne", "ne"),
+        BinOp::Lt(_) => ("lt", "gt"),
+        BinOp::Gt(_) => ("gt", "lt"),
+        BinOp::Le(_) => ("le", "ge"),
+        BinOp::Ge(_) => ("ge", "le"),
+        _ => return None,
+    })
+}
+
+pub fn arith_op(op: &BinOp) -> Option<TokenStream2> {
+    let op = match op {
+        BinOp::Add(_) => quote!(Add),
+        BinOp::Sub(_) => quote!(Sub),
+        BinOp::Mul(_) => quote!(Mul),
+        BinOp::Div(_) => quote!(Div),
+        BinOp::Rem(_) => quote!(Rem),
+        _ => return None,
+    };
+    Some(quote!(butane::query::ArithOp::#op))
+}
+
+/// A comparison of two values, at least one of which is evaluated by
+/// the database. The comparison is made with the methods of
+/// `butane::query::ValueExpr`, which check that the types of the
+/// operands are compatible.
+fn handle_compare(
+    fields: &Scope,
+    binop: &ExprBinary,
+    (method, swapped): (&str, &str),
+) -> TokenStream2 {
+    let (value, other, method) = if is_none(&binop.right) {
+        (&binop.left, &binop.right, method)
+    } else if is_none(&binop.left) || (is_json_get(&binop.right) && !is_json_get(&binop.left)) {
+        // JSON values and NULL checks are handled by the methods of
+        // the other operand, so swap the operands.
+        (&binop.right, &binop.left, swapped)
+    } else {
+        (&binop.left, &binop.right, method)
+    };
+    let value_tokens = handle_value(fields, value);
+    if is_none(other) {
+        return match method {
+            "eq" => quote!(#value_tokens.is_null()),
+            "ne" => quote!(#value_tokens.is_not_null()),
+            _ => make_compile_error!(other.span()=> "None may only be compared with == or !="),
+        };
+    }
+    if is_json_get(other) {
+        return make_compile_error!(other.span()=> "a JSON value may not be compared with another JSON value");
+    }
+    let other = handle_value(fields, other);
+    let method = Ident::new(method, binop.op.span());
+    quote!(#value_tokens.#method(#other))
+}
+
+fn is_none(expr: &Expr) -> bool {
+    matches!(strip_group(expr), Expr::Path(path) if path.path.is_ident("None"))
+}
+
+fn is_json_get(expr: &Expr) -> bool {
+    matches!(strip_group(expr), Expr::MethodCall(mcall) if mcall.method == "get")
+}
+
+/// Handles an operand of a comparison or arithmetic expression,
+/// producing a `butane::query::ValueExpr`, or a
+/// `butane::query::JsonValueExpr` for a value within a JSON field.
+fn handle_value(fields: &Scope, expr: &Expr) -> TokenStream2 {
+    match expr {
+        Expr::Binary(binop) => match arith_op(&binop.op) {
+            Some(op) => {
+                let left = handle_value(fields, &binop.left);
+                let right = handle_value(fields, &binop.right);
+                quote!(#left.arith(#op, #right))
+            }
+            None => make_compile_error!(binop.span()=> "Unsupported arithmetic operator"),
+        },
+        Expr::Paren(paren) => handle_value(fields, &paren.expr),
+        Expr::Group(group) => handle_value(fields, &group.expr),
+        Expr::Path(path) if path.path.is_ident("None") => {
+            make_compile_error!(path.span()=> "None may only be compared with == or !=")
+        }
+        Expr::Path(path) => {
+            let fex = fieldexpr(fields, &path.path);
+            quote!(#fex.value())
+        }
+        Expr::Field(field) if is_self(&field.base) => handle_self_field(fields, field),
+        Expr::MethodCall(mcall) if mcall.method == "get" => handle_json_get(fields, mcall),
+        Expr::Lit(lit) => quote!(butane::query::ValueExpr::val(&#lit)),
+        Expr::Block(block) => {
+            let val = handle_block(&block.block);
+            quote!(butane::query::ValueExpr::val(&{#val}))
+        }
+        _ => handle_expr(fields, expr),
+    }
+}
+
+/// `field.get("key")`, possibly chained: a value within a JSON field.
+fn handle_json_get(fields: &Scope, mcall: &ExprMethodCall) -> TokenStream2 {
+    let mut keys = Vec::new();
+    let mut expr = mcall;
+    loop {
+        if expr.args.len() != 1 {
+            return make_compile_error!(expr.span()=> "get expects the key as a single argument");
+        }
+        let key = handle_expr(fields, &expr.args[0]);
+        keys.push(quote!(::std::string::ToString::to_string(&{#key})));
+        match strip_group(&expr.receiver) {
+            Expr::MethodCall(inner) if inner.method == "get" => expr = inner,
+            _ => break,
+        }
+    }
+    keys.reverse();
+    let fex = fieldexpr(fields, &expr.receiver);
+    let span = expr.method.span();
+    quote_spanned!(span=> #fex.json_get(vec![#(#keys),*]))
+}
+
+fn handle_call(fields: &Scope, mcall: &ExprMethodCall) -> TokenStream2 {
     let method = mcall.method.to_string();
     match method.as_str() {
-        "contains" | "matches" => {
-            if mcall.args.len() != 1 {
-                return make_compile_error!(mcall.span()=> "expected one argument to '{}'", method);
-            };
+        "contains" | "matches" | "in_" | "not_in" | "like" | "ilike" | "starts_with"
+        | "ends_with" | "contains_str"
+            if mcall.args.len() != 1 =>
+        {
+            return make_compile_error!(mcall.span()=> "expected one argument to '{}'", method);
+        }
+        "is_none" | "is_some" if !mcall.args.is_empty() => {
+            return make_compile_error!(mcall.span()=> "expected no arguments to '{}'", method);
         }
         _ => (),
     };
     match method.as_str() {
         "matches" => handle_in(fields, &mcall.receiver, mcall.args.first().unwrap()),
         "contains" => handle_contains(fields, &mcall.receiver, mcall.args.first().unwrap()),
-        "like" => handle_like(fields, &mcall.receiver, mcall.args.first().unwrap()),
-        _ => make_compile_error!("Unknown method call {}", method),
+        "like" | "ilike" | "starts_with" | "ends_with" | "contains_str" => handle_like(
+            fields,
+            &mcall.method,
+            &mcall.receiver,
+            mcall.args.first().unwrap(),
+        ),
+        "in_" | "not_in" => handle_in_list(fields, mcall),
+        "is_none" | "is_some" => handle_null_check(fields, mcall),
+        _ => make_compile_error!(mcall.method.span()=> "Unknown method call {}", method),
     }
 }
 
-fn handle_in(fields: &impl ToTokens, receiver: &Expr, expr: &Expr) -> TokenStream2 {
+fn handle_in(fields: &Scope, receiver: &Expr, expr: &Expr) -> TokenStream2 {
     let fex = fieldexpr(fields, receiver);
     match expr {
         Expr::Lit(lit) => {
@@ -82,14 +384,14 @@ fn handle_in(fields: &impl ToTokens, receiver: &Expr, expr: &Expr) -> TokenStrea
         }
         _ => {
             // Arbitrary expression
-            let q = handle_expr(&quote!(#fex.fields()), expr);
+            let q = handle_condition(&Scope::new(quote!(#fex.fields())), expr);
             let span = receiver.span();
             quote_spanned!(span=> #fex.subfilter(#q))
         }
     }
 }
 
-fn handle_contains(fields: &impl ToTokens, receiver: &Expr, expr: &Expr) -> TokenStream2 {
+fn handle_contains(fields: &Scope, receiver: &Expr, expr: &Expr) -> TokenStream2 {
     let fex = fieldexpr(fields, receiver);
     match expr {
         Expr::Lit(lit) => {
@@ -98,35 +400,131 @@ fn handle_contains(fields: &impl ToTokens, receiver: &Expr, expr: &Expr) -> Toke
         }
         _ => {
             // Arbitrary expression
-            let q = handle_expr(&quote!(#fex.fields()), expr);
+            let q = handle_condition(&Scope::new(quote!(#fex.fields())), expr);
             let span = receiver.span();
             quote_spanned!(span=> #fex.contains(#q))
         }
     }
 }
 
-fn handle_like(fields: &impl ToTokens, receiver: &Expr, expr: &Expr) -> TokenStream2 {
+fn handle_like(fields: &Scope, method: &Ident, receiver: &Expr, expr: &Expr) -> TokenStream2 {
     let fex = fieldexpr(fields, receiver);
     match expr {
-        Expr::Binary(_) => make_compile_error!("Unexpected binary expression as parameter to like"),
-        Expr::Call(_) => make_compile_error!("Unexpected call expression as parameter to like"),
+        Expr::Binary(_) => {
+            make_compile_error!("Unexpected binary expression as parameter to {}", method)
+        }
+        Expr::Call(_) => {
+            make_compile_error!("Unexpected call expression as parameter to {}", method)
+        }
         _ => {
             // Arbitrary expression
             let q = handle_expr(fields, expr);
             let span = receiver.span();
-            quote_spanned!(span=> #fex.like(#q))
+            quote_spanned!(span=> #fex.#method(#q))
         }
     }
 }
 
-fn handle_path(fields: &impl ToTokens, expr: &ExprPath) -> TokenStream2 {
+fn is_self(expr: &Expr) -> bool {
+    matches!(strip_group(expr), Expr::Path(path) if path.path.is_ident("self"))
+}
+
+/// `self.field` within `exists`: a field of the enclosing model.
+fn handle_self_field(fields: &Scope, field: &ExprField) -> TokenStream2 {
+    let outer = match &fields.outer {
+        Some(outer) => outer,
+        None => return make_compile_error!(field.span()=> "`self` may only be used within exists"),
+    };
+    let table = match &outer.table {
+        Some(table) => table,
+        None => return make_compile_error!(field.span()=> "`self` may not be used here"),
+    };
+    let fex = match &field.member {
+        Member::Named(name) => fieldexpr(outer, name),
+        member => return make_compile_error!(member.span()=> "expected a field name"),
+    };
+    quote!(#fex.qualified_value(#table))
+}
+
+fn handle_fn_call(fields: &Scope, call: &ExprCall) -> TokenStream2 {
+    match call.func.as_ref() {
+        Expr::Path(path) if path.path.is_ident("raw") => handle_raw(call),
+        Expr::Path(path) if path.path.is_ident("exists") => handle_exists(fields, call),
+        func => make_compile_error!(func.span()=> "Unknown function {}", func.to_token_stream()),
+    }
+}
+
+/// `exists(Model, expr)`: true if any object of `Model` matches
+/// `expr`, in which `self.field` refers to the enclosing model.
+fn handle_exists(fields: &Scope, call: &ExprCall) -> TokenStream2 {
+    let (model, expr) = match (call.args.first(), call.args.iter().nth(1)) {
+        (Some(Expr::Path(model)), Some(expr)) if call.args.len() == 2 => (&model.path, expr),
+        _ => return make_compile_error!(call.span()=> "expected exists(Model, expression)"),
+    };
+    let scope = Scope {
+        outer: Some(Box::new(fields.clone())),
+        ..Scope::for_model(model)
+    };
+    let table = scope.table.as_ref().unwrap();
+    let cond = handle_condition(&scope, expr);
+    quote!(butane::query::BoolExpr::Exists {
+        tbl2: std::borrow::Cow::Borrowed(#table),
+        expr: Box::new(#cond),
+    })
+}
+
+/// `raw("sql", params...)`: a fragment of raw SQL with `?`
+/// placeholders for the parameters, which are Rust expressions.
+fn handle_raw(call: &ExprCall) -> TokenStream2 {
+    let mut args = call.args.iter();
+    let sql = match args.next() {
+        Some(Expr::Lit(syn::ExprLit {
+            lit: Lit::Str(sql), ..
+        })) => sql,
+        _ => {
+            return make_compile_error!(call.span()=> "expected a string literal as the first argument to raw")
+        }
+    };
+    let params: Vec<&Expr> = args.collect();
+    let nplaceholders = butane_core::query::raw_sql_placeholders(&sql.value()).len();
+    if nplaceholders != params.len() {
+        return make_compile_error!(sql.span()=>
+            "raw SQL has {} placeholders but {} parameters were given",
+            nplaceholders,
+            params.len()
+        );
+    }
+    quote!(butane::query::BoolExpr::Raw(
+        #sql.to_string(),
+        vec![#(butane::ToSql::to_sql(&#params)),*]
+    ))
+}
+
+fn handle_in_list(fields: &Scope, mcall: &ExprMethodCall) -> TokenStream2 {
+    // The argument is an arbitrary Rust expression (e.g. an array or
+    // an iterator), not a filter expression.
+    let fex = fieldexpr(fields, &mcall.receiver);
+    let method = &mcall.method;
+    let vals = mcall.args.first().unwrap();
+    let span = method.span();
+    quote_spanned!(span=> #fex.#method(#vals))
+}
+
+fn handle_null_check(fields: &Scope, mcall: &ExprMethodCall) -> TokenStream2 {
+    let fex = fieldexpr(fields, &mcall.receiver);
+    let method = &mcall.method;
+    let span = method.span();
+    quote_spanned!(span=> #fex.#method())
+}
+
+fn handle_path(fields: &Scope, expr: &ExprPath) -> TokenStream2 {
     if expr.path.is_ident("None") {
         return quote!(None);
     }
     fieldexpr(fields, &expr.path)
 }
 
-fn fieldexpr<F>(fields: &impl ToTokens, field: &F) -> TokenStream2
+pub fn fieldexpr<F>(fields: &impl ToTokens, field: &F) -> TokenStream2
 where
     F: ToTokens + Spanned,
 {
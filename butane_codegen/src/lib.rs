@@ -7,11 +7,14 @@ use butane_core::*;
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use proc_macro2::TokenTree;
-use quote::quote;
+use quote::{quote, quote_spanned};
 use std::path::PathBuf;
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
 use syn::{Expr, Ident};
 
 mod filter;
+mod order;
 
 /// Attribute macro which marks a struct as being a data model and
 /// generates an implementation of [`DataObject`](butane_core::DataObject). This
@@ -19,7 +22,7 @@ mod filter;
 /// generate migrations
 ///
 /// ## Restrictions on model types:
-/// 1. The type of each field must implement [`FieldType`] or be [`Many`].
+/// 1. The type of each field must implement [`FieldType`] or be [`Many`] or [`BackRef`].
 /// 2. There must be a primary key field. This must be either annotated with a `#[pk]` attribute or named `id`.
 ///
 /// ## Helper Attributes
@@ -33,6 +36,11 @@ mod filter;
 ///    (perhaps implemented as the SQL UNIQUE constraint by some backends).
 /// * `[default]` should be used on fields added by later migrations to avoid errors on existing objects.
 ///     Unnecessary if the new field is an `Option<>`
+/// * `#[backref(field)]` is required on a [`BackRef`] field. `field` names the
+///    [`ForeignKey`] field in the referring model which refers to this one.
+/// * `#[numeric(precision, scale)]` on a `Decimal` field sets the precision and
+///   scale of its column. Postgres rounds values to the scale; SQLite, which
+///   has no decimal type, does not enforce either.
 ///
 /// For example
 /// ```ignore
@@ -52,6 +60,8 @@ mod filter;
 ///
 /// [`FieldType`]: crate::FieldType
 /// [`Many`]: butane_core::many::Many
+/// [`BackRef`]: butane_core::backref::BackRef
+/// [`ForeignKey`]: butane_core::fkey::ForeignKey
 #[proc_macro_attribute]
 pub fn model(_args: TokenStream, input: TokenStream) -> TokenStream {
     codegen::model_with_migrations(input.into(), &mut migrations_for_dir()).into()
@@ -73,7 +83,31 @@ pub fn model(_args: TokenStream, input: TokenStream) -> TokenStream {
 ///
 /// Note that the attribute takes a parameter saying which Model this
 /// result is a subset of. Every field named in the DataResult must be
-/// present in the Model.
+/// present in the Model, unless it is an aggregate.
+///
+/// ## Aggregates
+/// Fields may instead be computed by an aggregate function over the
+/// rows of the Model, for use with
+/// [`Query::group_by`](butane_core::query::Query::group_by).
+/// * `#[count]` counts the rows in the group (`#[count(column)]` counts non-null values of `column`).
+/// * `#[sum(column)]`, `#[avg(column)]`, `#[min(column)]` and `#[max(column)]` compute the
+///   corresponding aggregate over `column`.
+///
+/// For example, to count the posts in each blog
+/// ```ignore
+/// #[dataresult(Post)]
+/// pub struct BlogPostCount {
+///   pub blog: ForeignKey<Blog>,
+///   #[count]
+///   pub count: i64,
+///   #[sum(likes)]
+///   pub total_likes: Option<i64>,
+/// }
+/// let counts = BlogPostCount::query()
+///   .group_by(&[colname!(Post, blog)])
+///   .having(BlogPostCount::fields().count().gt(&1))
+///   .load(&conn)?;
+/// ```
 #[proc_macro_attribute]
 pub fn dataresult(args: TokenStream, input: TokenStream) -> TokenStream {
     codegen::dataresult(args.into(), input.into()).into()
@@ -119,6 +153,94 @@ pub fn filter(input: TokenStream) -> TokenStream {
     filter::for_expr(&tyid, &expr).into()
 }
 
+#[proc_macro]
+pub fn select(input: TokenStream) -> TokenStream {
+    let input: TokenStream2 = input.into();
+    let args: Vec<TokenTree> = input.into_iter().collect();
+    let usage = "Expected select!(Type, (column, ...)) or select!(Type, (column, ...), expression)";
+    if args.len() < 3 {
+        return make_compile_error!("{}", usage).into();
+    }
+    let tyid: Ident = match &args[0] {
+        TokenTree::Ident(tyid) => tyid.clone(),
+        _ => {
+            return make_compile_error!("Unexpected tokens in database object type {:?}", &args[0])
+                .into()
+        }
+    };
+    let columns: Vec<Ident> = match (&args[1], &args[2]) {
+        (TokenTree::Punct(_), TokenTree::Group(g))
+            if g.delimiter() == proc_macro2::Delimiter::Parenthesis =>
+        {
+            let parser = Punctuated::<Ident, syn::Token![,]>::parse_terminated;
+            match parser.parse2(g.stream()) {
+                Ok(columns) if !columns.is_empty() => columns.into_iter().collect(),
+                _ => {
+                    return make_compile_error!(g.span()=> "Expected a list of column names").into()
+                }
+            }
+        }
+        _ => return make_compile_error!("{}", usage).into(),
+    };
+
+    let fields = quote!(<#tyid as butane::DataResult>::DBO::fields());
+    let fieldexprs = columns
+        .iter()
+        .map(|col| quote_spanned!(col.span()=> #fields.#col(),));
+    let select = quote!(
+        butane::query::Select::new(
+            <<#tyid as butane::DataResult>::DBO as butane::DataObject>::TABLE,
+            (#(#fieldexprs)*),
+        )
+    );
+
+    if args.len() == 3 {
+        return select.into();
+    }
+    if !matches!(&args[3], TokenTree::Punct(p) if p.as_char() == ',') {
+        return make_compile_error!("{}", usage).into();
+    }
+    let expr: TokenStream2 = args.into_iter().skip(4).collect();
+    let expr: Expr = match syn::parse2(expr) {
+        Ok(expr) => expr,
+        Err(_) => {
+            return make_compile_error!("{} but could not parse expression", usage).into();
+        }
+    };
+    let filter = filter::for_expr(&tyid, &expr);
+    quote!(#select.filter(#filter)).into()
+}
+
+/// Create a list of [`Order`](butane_core::query::Order) terms for a
+/// query. Intended to be passed to
+/// [`Query::order_by`](butane_core::query::Query::order_by). See the
+/// documentation in the `butane` crate.
+#[proc_macro]
+pub fn order_by(input: TokenStream) -> TokenStream {
+    let input: TokenStream2 = input.into();
+    let args: Vec<TokenTree> = input.into_iter().collect();
+    let usage = "Expected order_by!(Type, term, ...)";
+    if args.len() < 3 {
+        return make_compile_error!("{}", usage).into();
+    }
+    let tyid: Ident = match &args[0] {
+        TokenTree::Ident(tyid) => tyid.clone(),
+        _ => {
+            return make_compile_error!("Unexpected tokens in database object type {:?}", &args[0])
+                .into()
+        }
+    };
+    if !matches!(&args[1], TokenTree::Punct(p) if p.as_char() == ',') {
+        return make_compile_error!("{}", usage).into();
+    }
+    let terms: TokenStream2 = args.into_iter().skip(2).collect();
+    let parser = Punctuated::<order::OrderTerm, syn::Token![,]>::parse_terminated;
+    match parser.parse2(terms) {
+        Ok(terms) => order::for_terms(&tyid, terms).into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
 /// Attribute macro which marks a type as being available to butane
 /// for use in models.
 ///
@@ -155,6 +277,42 @@ pub fn butane_type(args: TokenStream, input: TokenStream) -> TokenStream {
         .into()
 }
 
+/// Derive macro implementing [`FieldType`], [`ToSql`] and [`FromSql`]
+/// for an enum whose variants have no fields, and marking it as
+/// available to butane for use in models as with [`butane_type`].
+///
+/// By default the enum is stored as `Text`, using the name of each
+/// variant. A variant may be given a different name with the
+/// `rename` attribute. With `#[sqltype(SmallInt)]`,
+/// `#[sqltype(Int)]` or `#[sqltype(BigInt)]` on the enum, it is
+/// instead stored as the discriminant of each variant.
+///
+/// E.g.
+/// ```ignore
+/// #[derive(FieldType)]
+/// pub enum Currency {
+///   #[rename = "dollars"]
+///   Dollars,
+///   #[rename = "pounds"]
+///   Pounds,
+/// }
+///
+/// #[derive(FieldType)]
+/// #[sqltype(Int)]
+/// pub enum Priority {
+///   Low = 1,
+///   High = 10,
+/// }
+/// ```
+///
+/// [`FieldType`]: crate::FieldType
+/// [`ToSql`]: crate::ToSql
+/// [`FromSql`]: crate::FromSql
+#[proc_macro_derive(FieldType, attributes(sqltype, rename))]
+pub fn derive_field_type(input: TokenStream) -> TokenStream {
+    codegen::derive_field_type_with_migrations(input.into(), &mut migrations_for_dir()).into()
+}
+
 fn migrations_for_dir() -> migrations::FsMigrations {
     migrations::from_root(&migrations_dir())
 }
@@ -0,0 +1,148 @@
+use super::*;
+use crate::filter::{arith_op, fieldexpr};
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned, ToTokens};
+use syn::parse::{Parse, ParseStream};
+use syn::{spanned::Spanned, Expr, ExprCall, ExprField, Ident, Member};
+
+/// A single term of `order_by!`: an expression optionally followed by
+/// `asc` or `desc` and by `nulls first` or `nulls last`.
+pub struct OrderTerm {
+    expr: Expr,
+    descending: bool,
+    nulls: Option<Ident>,
+}
+
+impl Parse for OrderTerm {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let expr: Expr = input.parse()?;
+        let mut descending = false;
+        let mut nulls = None;
+        if input.peek(Ident) {
+            let kw: Ident = input.fork().parse()?;
+            if kw == "asc" || kw == "desc" {
+                input.parse::<Ident>()?;
+                descending = kw == "desc";
+            }
+        }
+        if input.peek(Ident) {
+            let kw: Ident = input.parse()?;
+            if kw != "nulls" {
+                return Err(syn::Error::new(
+                    kw.span(),
+                    "expected `asc`, `desc`, `nulls first` or `nulls last`",
+                ));
+            }
+            let placement: Ident = input.parse()?;
+            if placement != "first" && placement != "last" {
+                return Err(syn::Error::new(
+                    placement.span(),
+                    "expected `nulls first` or `nulls last`",
+                ));
+            }
+            nulls = Some(placement);
+        }
+        Ok(OrderTerm {
+            expr,
+            descending,
+            nulls,
+        })
+    }
+}
+
+pub fn for_terms(dbres: &Ident, terms: impl IntoIterator<Item = OrderTerm>) -> TokenStream2 {
+    let fields = quote!(<#dbres as butane::DataResult>::DBO::fields());
+    let table = quote!(<<#dbres as butane::DataResult>::DBO as butane::DataObject>::TABLE);
+    let orders = terms.into_iter().map(|term| {
+        if is_constant(&term.expr) {
+            return make_compile_error!(term.expr.span()=>
+                "order term must refer to a column; a constant does not order the rows");
+        }
+        let expr = handle_order_expr(&fields, &table, &term.expr);
+        let direction = if term.descending {
+            quote!(Descending)
+        } else {
+            quote!(Ascending)
+        };
+        let nulls = term.nulls.map(|placement| {
+            let method = Ident::new(&format!("nulls_{}", placement), placement.span());
+            quote!(.#method())
+        });
+        quote!(butane::query::Order::new(#expr, butane::query::OrderDirection::#direction)#nulls)
+    });
+    quote!(vec![#(#orders),*])
+}
+
+/// Handles the expression of an order term, producing a
+/// `butane::query::Expr`.
+fn handle_order_expr(fields: &impl ToTokens, table: &TokenStream2, expr: &Expr) -> TokenStream2 {
+    match expr {
+        Expr::Path(path) => {
+            let fex = fieldexpr(fields, &path.path);
+            quote!(#fex.expr())
+        }
+        Expr::Field(field) => handle_related(fields, table, field),
+        Expr::Call(call) => handle_function(fields, table, call),
+        Expr::Binary(binop) => match arith_op(&binop.op) {
+            Some(op) => {
+                let left = handle_order_expr(fields, table, &binop.left);
+                let right = handle_order_expr(fields, table, &binop.right);
+                quote!(butane::query::Expr::Arith(Box::new(#left), #op, Box::new(#right)))
+            }
+            None => make_compile_error!(binop.span()=> "Unsupported operator in order expression"),
+        },
+        Expr::Paren(paren) => handle_order_expr(fields, table, &paren.expr),
+        Expr::Group(group) => handle_order_expr(fields, table, &group.expr),
+        Expr::Lit(lit) => quote!(butane::query::Expr::Val(butane::ToSql::to_sql(&#lit))),
+        _ => make_compile_error!(expr.span()=> "Unsupported order expression"),
+    }
+}
+
+/// Whether an order expression consists only of literals, possibly
+/// passed to functions. Such a term would be passed to the database
+/// as a constant (or, for a bare integer, read as a column position),
+/// so it is rejected. A call without arguments, such as `random()`,
+/// is not constant.
+fn is_constant(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit(_) => true,
+        Expr::Call(call) => !call.args.is_empty() && call.args.iter().all(is_constant),
+        Expr::Paren(paren) => is_constant(&paren.expr),
+        Expr::Group(group) => is_constant(&group.expr),
+        Expr::Unary(unary) => is_constant(&unary.expr),
+        Expr::Binary(binop) => is_constant(&binop.left) && is_constant(&binop.right),
+        _ => false,
+    }
+}
+
+/// `fkey.column`: the column of the object referred to by a foreign key.
+fn handle_related(fields: &impl ToTokens, table: &TokenStream2, field: &ExprField) -> TokenStream2 {
+    let fkey = match field.base.as_ref() {
+        Expr::Path(path) if path.path.get_ident().is_some() => fieldexpr(fields, &path.path),
+        base => {
+            return make_compile_error!(base.span()=> "expected the name of a foreign key field")
+        }
+    };
+    let column = match &field.member {
+        Member::Named(column) => column,
+        member => return make_compile_error!(member.span()=> "expected a field name"),
+    };
+    let span = column.span();
+    quote_spanned!(span=> #fkey.related(#table, #fkey.fields().#column().expr()))
+}
+
+/// `name(args...)`: a call of the SQL function `name`.
+fn handle_function(fields: &impl ToTokens, table: &TokenStream2, call: &ExprCall) -> TokenStream2 {
+    let name = match call.func.as_ref() {
+        Expr::Path(path) => match path.path.get_ident() {
+            Some(name) => name.to_string(),
+            None => return make_compile_error!(path.span()=> "expected a function name"),
+        },
+        func => return make_compile_error!(func.span()=> "expected a function name"),
+    };
+    let args = call
+        .args
+        .iter()
+        .map(|arg| handle_order_expr(fields, table, arg));
+    quote!(butane::query::Expr::Function(#name, vec![#(#args),*]))
+}
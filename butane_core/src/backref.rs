@@ -0,0 +1,174 @@
+use crate::db::{collect_rows, ConnectionMethods};
+use crate::query::{BoolExpr, Expr};
+use crate::{DataObject, Error, Result, SqlVal, SqlValKey};
+use once_cell::unsync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+fn default_oc<T>() -> OnceCell<Vec<T>> {
+    OnceCell::default()
+}
+
+/// Used to access the objects which refer to a model through a
+/// [`ForeignKey`]. This is the reverse of the foreign key
+/// relationship and does not correspond to a column.
+///
+/// The field of `T` containing the foreign key must be given with the
+/// `#[backref(field)]` attribute.
+///
+/// # Examples
+/// ```ignore
+/// #[model]
+/// struct Blog {
+///   ...
+///   #[backref(blog)]
+///   posts: BackRef<Post>,
+/// }
+/// #[model]
+/// struct Post {
+///   blog: ForeignKey<Blog>,
+///   ...
+/// }
+/// ```
+///
+/// [`ForeignKey`]: crate::fkey::ForeignKey
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackRef<T>
+where
+    T: DataObject,
+{
+    #[serde(skip)]
+    column: &'static str,
+    #[serde(skip)]
+    owner: Option<SqlVal>,
+    #[serde(skip)]
+    #[serde(default = "default_oc")]
+    all_values: OnceCell<Vec<T>>,
+}
+impl<T> BackRef<T>
+where
+    T: DataObject,
+{
+    /// Constructs a new BackRef. `ensure_init` must be called before
+    /// it can be loaded (or it will appear to have no values). It
+    /// will automatically be called when a [`DataObject`] with a
+    /// `BackRef` field is loaded or saved.
+    ///
+    /// [`DataObject`]: super::DataObject
+    pub fn new() -> Self {
+        BackRef {
+            column: "not_initialized",
+            owner: None,
+            all_values: OnceCell::new(),
+        }
+    }
+
+    /// Used by macro-generated code. You do not need to call this directly.
+    pub fn ensure_init(&mut self, column: &'static str, owner: SqlVal) {
+        if self.owner.is_some() {
+            return;
+        }
+        self.column = column;
+        self.owner = Some(owner);
+        self.all_values = OnceCell::new();
+    }
+
+    /// Returns a reference to the values. They must have already been
+    /// loaded. If not, returns Error::ValueNotLoaded
+    pub fn get(&self) -> Result<impl Iterator<Item = &T>> {
+        self.all_values
+            .get()
+            .ok_or(Error::ValueNotLoaded)
+            .map(|v| v.iter())
+    }
+
+    /// Loads the objects referring to the owner of this BackRef from
+    /// the database if necessary and returns a reference to them.
+    pub fn load(&self, conn: &impl ConnectionMethods) -> Result<impl Iterator<Item = &T>> {
+        let vals: Result<&Vec<T>> = self.all_values.get_or_try_init(|| {
+            //if we don't have an owner then there are no values
+            let owner: &SqlVal = match &self.owner {
+                Some(o) => o,
+                None => return Ok(Vec::new()),
+            };
+            T::query()
+                .filter(BoolExpr::Eq(self.column, Expr::Val(owner.clone())))
+                .load(conn)
+        });
+        vals.map(|v| v.iter())
+    }
+
+    /// Loads the values of all of `backrefs` which are not already
+    /// loaded, using a single query. Used by
+    /// [Query::prefetch](crate::query::Query::prefetch).
+    pub fn prefetch<'a>(
+        conn: &impl ConnectionMethods,
+        backrefs: impl IntoIterator<Item = &'a Self>,
+    ) -> Result<()>
+    where
+        T: 'a,
+    {
+        let backrefs: Vec<&Self> = backrefs
+            .into_iter()
+            .filter(|backref| backref.owner.is_some() && backref.all_values.get().is_none())
+            .collect();
+        let column = match backrefs.first() {
+            Some(first) => first.column,
+            None => return Ok(()),
+        };
+        let mut owners: Vec<SqlVal> = Vec::new();
+        let mut seen: HashSet<SqlValKey> = HashSet::new();
+        for backref in &backrefs {
+            let owner = backref.owner.as_ref().unwrap();
+            if seen.insert(SqlValKey(owner.clone())) {
+                owners.push(owner.clone());
+            }
+        }
+        let colidx = T::COLUMNS
+            .iter()
+            .position(|col| col.name() == column)
+            .ok_or_else(|| Error::Internal(format!("No column {}", column)))?;
+        let rows = collect_rows(
+            conn.query(
+                T::TABLE,
+                T::COLUMNS,
+                false,
+                Some(BoolExpr::In(column, owners)),
+                None,
+                None,
+                None,
+                None,
+            )?,
+            T::COLUMNS,
+        )?;
+        let mut owned: HashMap<SqlValKey, Vec<Vec<SqlVal>>> = HashMap::new();
+        for row in rows {
+            owned
+                .entry(SqlValKey(row[colidx].clone()))
+                .or_default()
+                .push(row);
+        }
+        for backref in backrefs {
+            let owner = SqlValKey(backref.owner.clone().unwrap());
+            let vals = owned
+                .get(&owner)
+                .into_iter()
+                .flatten()
+                .map(|row| T::from_row(row))
+                .collect::<Result<Vec<T>>>()?;
+            backref.all_values.set(vals).ok();
+        }
+        Ok(())
+    }
+}
+impl<T: DataObject> PartialEq<BackRef<T>> for BackRef<T> {
+    fn eq(&self, other: &BackRef<T>) -> bool {
+        (self.owner == other.owner) && (self.column == other.column)
+    }
+}
+impl<T: DataObject> Eq for BackRef<T> {}
+impl<T: DataObject> Default for BackRef<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -49,6 +49,8 @@ pub fn impl_dbobject(ast_struct: &ItemStruct, config: &Config) -> TokenStream2 {
         )
     }).collect();
 
+    let backref_init = backref_init(ast_struct, quote!(self));
+
     let values: Vec<TokenStream2> = push_values(ast_struct, |_| true);
     let values_no_pk: Vec<TokenStream2> = push_values(ast_struct, |f: &Field| f != &pk_field);
 
@@ -75,7 +77,7 @@ pub fn impl_dbobject(ast_struct: &ItemStruct, config: &Config) -> TokenStream2 {
                     if values.len() > 0 {
                         conn.update(Self::TABLE,
                                     pkcol,
-                                    butane::ToSql::to_sql_ref(self.pk()),
+                                    butane::ToSql::try_to_sql_ref(self.pk())?,
                                     &[#save_cols], &values)?;
                     }
                 } else {
@@ -84,6 +86,7 @@ pub fn impl_dbobject(ast_struct: &ItemStruct, config: &Config) -> TokenStream2 {
                     #(#post_insert)*
                 }
                 #many_save
+                #backref_init
                 Ok(())
             }
             fn delete(&self, conn: &impl butane::db::ConnectionMethods) -> butane::Result<()> {
@@ -156,6 +159,23 @@ pub fn impl_dataresult(ast_struct: &ItemStruct, dbo: &Ident) -> TokenStream2 {
             quote!(obj.#ident.ensure_init(#many_table_lit, butane::ToSql::to_sql(obj.pk()), #pksqltype);)
         }).collect();
 
+    let backref_init = backref_init(ast_struct, quote!(obj));
+
+    // Ensure the columns used by aggregates exist in the model
+    let aggregate_checks: TokenStream2 = fields(ast_struct)
+        .filter_map(|f| match get_aggregate(f) {
+            Ok(Some(FieldAggregate {
+                column: Some(col), ..
+            })) => Some(quote_spanned!(col.span()=> #dbo::fields().#col();)),
+            Ok(_) => None,
+            Err(err) => Some(err),
+        })
+        .collect();
+
+    let prefetch = impl_prefetch(ast_struct);
+    let relations_type = relations_type(tyname);
+    let relations_type_def = relations_type_def(ast_struct);
+
     let dbo_is_self = dbo == tyname;
     let ctor = if dbo_is_self {
         quote!(
@@ -174,8 +194,10 @@ pub fn impl_dataresult(ast_struct: &ItemStruct, dbo: &Ident) -> TokenStream2 {
     };
 
     quote!(
+                #relations_type_def
                 impl butane::DataResult for #tyname {
                         type DBO = #dbo;
+                        type Relations = #relations_type;
                         const COLUMNS: &'static [butane::db::Column] = &[
                                 #cols
                         ];
@@ -186,13 +208,112 @@ pub fn impl_dataresult(ast_struct: &ItemStruct, dbo: &Ident) -> TokenStream2 {
                                 }
                                 #ctor
                                 #many_init
+                                #backref_init
                                 Ok(obj)
                         }
                     fn query() -> butane::query::Query<Self> {
                         use butane::prelude::DataObject;
+                        #aggregate_checks
                         butane::query::Query::new(Self::DBO::TABLE)
                     }
+                    #prefetch
+                }
+    )
+}
+
+/// Initializes the `BackRef` fields of `obj` with its primary key.
+fn backref_init(ast_struct: &ItemStruct, obj: TokenStream2) -> TokenStream2 {
+    fields(ast_struct)
+        .filter(|f| is_backref(f))
+        .map(|f| {
+            let ident = f.ident.clone().expect("Fields must be named for butane");
+            match get_backref(f) {
+                Ok(Some(column)) => {
+                    let column_lit = make_ident_literal_str(&column);
+                    quote!(
+                        #obj.#ident.ensure_init(
+                            #column_lit,
+                            butane::ToSql::to_sql(<Self as butane::DataObject>::pk(&#obj)));
+                    )
+                }
+                Ok(None) => TokenStream2::new(),
+                Err(err) => err,
+            }
+        })
+        .collect()
+}
+
+/// The relationships of `ast_struct` which may be prefetched, as
+/// each field, the name of the relationship and the type which
+/// loads it.
+fn relations(ast_struct: &ItemStruct) -> Vec<(&Field, TokenStream2, TokenStream2)> {
+    fields(ast_struct)
+        .filter_map(|f| {
+            if is_many_to_many(f) {
+                let many_table_lit = many_table_lit(ast_struct, f);
+                Some((f, quote!(#many_table_lit), quote!(butane::Many)))
+            } else if is_backref(f) {
+                Some((f, field_ident_lit(f), quote!(butane::BackRef)))
+            } else if get_foreign_type_argument(&f.ty, "ForeignKey").is_some() {
+                Some((f, field_ident_lit(f), quote!(butane::ForeignKey)))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Implements `DataResult::prefetch` for the relationships in
+/// `ast_struct`, if there are any.
+fn impl_prefetch(ast_struct: &ItemStruct) -> TokenStream2 {
+    let arms: Vec<TokenStream2> = relations(ast_struct)
+        .into_iter()
+        .map(|(f, name, loader)| {
+            let ident = f.ident.clone().expect("Fields must be named for butane");
+            quote!(#name => #loader::prefetch(conn, objs.iter().map(|obj| &obj.#ident)),)
+        })
+        .collect();
+    if arms.is_empty() {
+        return TokenStream2::new();
+    }
+    quote!(
+        fn prefetch(
+            conn: &impl butane::db::ConnectionMethods,
+            objs: &[Self],
+            relation: &str,
+        ) -> butane::Result<()> {
+            match relation {
+                #(#arms)*
+                _ => Err(butane::Error::Internal(format!("No relation named {}", relation))),
+            }
+        }
+    )
+}
+
+/// Defines the type given to the closure passed to
+/// `Query::prefetch`, with a method for each relationship of
+/// `ast_struct`.
+fn relations_type_def(ast_struct: &ItemStruct) -> TokenStream2 {
+    let tyname = &ast_struct.ident;
+    let vis = &ast_struct.vis;
+    let relations_type = relations_type(tyname);
+    let methods: Vec<TokenStream2> = relations(ast_struct)
+        .into_iter()
+        .map(|(f, name, _)| {
+            let ident = f.ident.clone().expect("Fields must be named for butane");
+            quote!(
+                pub fn #ident(&self) -> butane::query::Relation<#tyname> {
+                    butane::query::Relation::new(#name)
                 }
+            )
+        })
+        .collect();
+    quote!(
+        #[derive(Default)]
+        #vis struct #relations_type {}
+        impl #relations_type {
+            #(#methods)*
+        }
     )
 }
 
@@ -210,6 +331,8 @@ pub fn add_fieldexprs(ast_struct: &ItemStruct) -> TokenStream2 {
         .map(|f| {
             if is_many_to_many(f) {
                 fieldexpr_func_many(f, ast_struct)
+            } else if is_backref(f) {
+                fieldexpr_func_backref(f, ast_struct)
             } else {
                 fieldexpr_func_regular(f, ast_struct)
             }
@@ -238,7 +361,7 @@ pub fn add_fieldexprs(ast_struct: &ItemStruct) -> TokenStream2 {
 
 fn fieldexpr_func_regular(f: &Field, ast_struct: &ItemStruct) -> TokenStream2 {
     let fty = &f.ty;
-    let fidlit = field_ident_lit(f);
+    let fidlit = column_lit(f);
     fieldexpr_func(
         f,
         ast_struct,
@@ -259,6 +382,23 @@ fn fieldexpr_func_many(f: &Field, ast_struct: &ItemStruct) -> TokenStream2 {
     )
 }
 
+fn fieldexpr_func_backref(f: &Field, ast_struct: &ItemStruct) -> TokenStream2 {
+    let tyname = &ast_struct.ident;
+    let fty = get_foreign_type_argument(&f.ty, "BackRef").expect("BackRef field misdetected");
+    let fidlit = field_ident_lit(f);
+    let fkey = match get_backref(f) {
+        Ok(Some(column)) => quote_spanned!(column.span()=> <#fty>::fields().#column()),
+        Ok(None) => return TokenStream2::new(),
+        Err(err) => return err,
+    };
+    fieldexpr_func(
+        f,
+        ast_struct,
+        quote!(butane::query::BackRefFieldExpr<#tyname, #fty>),
+        quote!(butane::query::BackRefFieldExpr::<#tyname, #fty>::new(#fidlit, #fkey)),
+    )
+}
+
 fn fieldexpr_func(
     f: &Field,
     ast_struct: &ItemStruct,
@@ -296,10 +436,24 @@ fn field_ident_lit(f: &Field) -> TokenStream2 {
     make_ident_literal_str(fid).into_token_stream()
 }
 
+/// The literal used to select the column for `f`. For aggregate
+/// fields, this is the SQL of the aggregate.
+fn column_lit(f: &Field) -> TokenStream2 {
+    match get_aggregate(f) {
+        Ok(Some(agg)) => make_lit(&aggregate_sql(f, &agg)).into_token_stream(),
+        Ok(None) => field_ident_lit(f),
+        Err(err) => err,
+    }
+}
+
 fn fields_type(tyname: &Ident) -> Ident {
     Ident::new(&format!("{}Fields", tyname), Span::call_site())
 }
 
+fn relations_type(tyname: &Ident) -> Ident {
+    Ident::new(&format!("{}Relations", tyname), Span::call_site())
+}
+
 fn rows_for_from(ast_struct: &ItemStruct) -> Vec<TokenStream2> {
     let mut i: usize = 0;
     fields(ast_struct)
@@ -315,6 +469,8 @@ fn rows_for_from(ast_struct: &ItemStruct) -> Vec<TokenStream2> {
                 ret
             } else if is_many_to_many(f) {
                 quote!(#ident: butane::Many::new())
+            } else if is_backref(f) {
+                quote!(#ident: butane::BackRef::new())
             } else {
                 make_compile_error!(f.span()=> "Unexpected struct field")
             }
@@ -328,9 +484,9 @@ where
 {
     fields(ast_struct)
         .filter(|f| is_row_field(f) && predicate(f))
-        .map(|f| match f.ident.clone() {
-            Some(fname) => {
-                let ident = make_ident_literal_str(&fname);
+        .map(|f| match f.ident {
+            Some(_) => {
+                let ident = column_lit(f);
                 let fty = &f.ty;
                 quote!(butane::db::Column::new(#ident, <#fty as butane::FieldType>::SQLTYPE),)
             }
@@ -358,8 +514,41 @@ fn verify_fields(ast_struct: &ItemStruct) -> Option<TokenStream2> {
     };
     let pk_field = pk_field.unwrap();
     for f in fields(ast_struct) {
+        if is_aggregate(f) {
+            return Some(quote_spanned!(
+                f.span() =>
+                    compile_error!("Aggregates are only supported in a dataresult");
+            ));
+        }
+        if is_backref(f) {
+            match get_backref(f) {
+                Ok(Some(_)) => (),
+                Ok(None) => {
+                    return Some(quote_spanned!(
+                        f.span() =>
+                            compile_error!("BackRef fields require a #[backref(field)] attribute");
+                    ))
+                }
+                Err(err) => return Some(err),
+            }
+        }
+        #[cfg(feature = "decimal")]
+        match get_numeric_precision(f) {
+            Ok(Some(_)) => match get_deferred_sql_type(&f.ty) {
+                DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Numeric(_))) => (),
+                _ => {
+                    return Some(quote_spanned!(
+                        f.span() =>
+                            compile_error!("numeric is only supported for Decimal fields");
+                    ))
+                }
+            },
+            Ok(None) => (),
+            Err(err) => return Some(err.ts),
+        }
         if is_auto(f) {
             match get_primitive_sql_type(&f.ty) {
+                Some(DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::SmallInt))) => (),
                 Some(DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Int))) => (),
                 Some(DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::BigInt))) => (),
                 _ => {
@@ -398,7 +587,7 @@ where
             let ident = f.ident.clone().unwrap();
             if is_row_field(f) {
                 if !is_auto(f) {
-                    quote!(values.push(butane::ToSql::to_sql_ref(&self.#ident));)
+                    quote!(values.push(butane::ToSql::try_to_sql_ref(&self.#ident)?);)
                 } else {
                     quote!()
                 }
@@ -0,0 +1,176 @@
+//! Implementation of `#[derive(FieldType)]` for enums.
+
+use crate::SqlType;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{Attribute, Data, DeriveInput, Fields, Ident, Lit, Meta, MetaNameValue, NestedMeta};
+
+/// The SqlType a derived enum is stored as, from its `sqltype`
+/// attribute. Defaults to `Text`.
+pub fn sqltype(ast: &DeriveInput) -> std::result::Result<SqlType, TokenStream2> {
+    let attr = match ast.attrs.iter().find(|a| a.path.is_ident("sqltype")) {
+        Some(attr) => attr,
+        None => return Ok(SqlType::Text),
+    };
+    let name = match attr.parse_meta() {
+        Ok(Meta::List(list)) if list.nested.len() == 1 => match list.nested.first() {
+            Some(NestedMeta::Meta(Meta::Path(path))) => path.get_ident().cloned(),
+            _ => None,
+        },
+        _ => None,
+    };
+    match name {
+        Some(name) if name == "Text" => Ok(SqlType::Text),
+        Some(name) if name == "SmallInt" => Ok(SqlType::SmallInt),
+        Some(name) if name == "Int" => Ok(SqlType::Int),
+        Some(name) if name == "BigInt" => Ok(SqlType::BigInt),
+        _ => Err(make_compile_error!(attr.span()=>
+            "Expected sqltype(Text), sqltype(SmallInt), sqltype(Int) or sqltype(BigInt)")),
+    }
+}
+
+/// Implements `ToSql`, `FromSql` and `FieldType` for an enum whose
+/// variants have no fields.
+pub fn impl_field_type(ast: &DeriveInput, sqltype: &SqlType) -> TokenStream2 {
+    let data = match &ast.data {
+        Data::Enum(data) => data,
+        _ => return make_compile_error!(ast.span()=> "FieldType can only be derived for enums"),
+    };
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return make_compile_error!(variant.span()=>
+                "FieldType can only be derived for enums whose variants have no fields");
+        }
+    }
+    let variants: Vec<&Ident> = data.variants.iter().map(|v| &v.ident).collect();
+    let ident = &ast.ident;
+    let impls = match sqltype {
+        SqlType::Text => {
+            let mut names = Vec::with_capacity(variants.len());
+            for variant in &data.variants {
+                match variant_name(&variant.attrs) {
+                    Ok(name) => names.push(name.unwrap_or_else(|| variant.ident.to_string())),
+                    Err(err) => return err,
+                }
+            }
+            impl_text(ident, &variants, &names)
+        }
+        _ => {
+            if let Some(attr) = data
+                .variants
+                .iter()
+                .flat_map(|v| &v.attrs)
+                .find(|a| a.path.is_ident("rename"))
+            {
+                return make_compile_error!(attr.span()=>
+                    "rename may only be used with an enum stored as Text");
+            }
+            impl_int(ident, &variants, sqltype)
+        }
+    };
+    let sqltype = sqltype_tokens(sqltype);
+    quote!(
+        #impls
+        impl butane::FieldType for #ident {
+            type RefType = Self;
+            const SQLTYPE: butane::SqlType = #sqltype;
+        }
+    )
+}
+
+/// The name given to a variant by a `#[rename = "name"]` attribute.
+fn variant_name(attrs: &[Attribute]) -> std::result::Result<Option<String>, TokenStream2> {
+    let attr = match attrs.iter().find(|a| a.path.is_ident("rename")) {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+    match attr.parse_meta() {
+        Ok(Meta::NameValue(MetaNameValue {
+            lit: Lit::Str(s), ..
+        })) => Ok(Some(s.value())),
+        _ => Err(make_compile_error!(attr.span()=> "Expected rename = \"name\"")),
+    }
+}
+
+fn impl_text(ident: &Ident, variants: &[&Ident], names: &[String]) -> TokenStream2 {
+    let err = conv_err(&SqlType::Text);
+    quote!(
+        impl butane::ToSql for #ident {
+            fn to_sql(&self) -> butane::SqlVal {
+                self.to_sql_ref().into()
+            }
+            fn to_sql_ref(&self) -> butane::SqlValRef<'_> {
+                butane::SqlValRef::Text(match self {
+                    #(Self::#variants => #names,)*
+                })
+            }
+        }
+        impl butane::FromSql for #ident {
+            fn from_sql_ref(val: butane::SqlValRef) -> butane::Result<Self> {
+                match val {
+                    butane::SqlValRef::Text(s) => match s {
+                        #(#names => Ok(Self::#variants),)*
+                        _ => #err,
+                    },
+                    _ => #err,
+                }
+            }
+        }
+    )
+}
+
+fn impl_int(ident: &Ident, variants: &[&Ident], sqltype: &SqlType) -> TokenStream2 {
+    let err = conv_err(sqltype);
+    let to_sql = match sqltype {
+        SqlType::SmallInt => quote!(butane::SqlValRef::SmallInt(match self {
+            #(Self::#variants => Self::#variants as i16,)*
+        })),
+        SqlType::Int => quote!(butane::SqlValRef::Int(match self {
+            #(Self::#variants => Self::#variants as i32,)*
+        })),
+        _ => quote!(butane::SqlValRef::BigInt(match self {
+            #(Self::#variants => Self::#variants as i64,)*
+        })),
+    };
+    quote!(
+        impl butane::ToSql for #ident {
+            fn to_sql(&self) -> butane::SqlVal {
+                self.to_sql_ref().into()
+            }
+            fn to_sql_ref(&self) -> butane::SqlValRef<'_> {
+                #to_sql
+            }
+        }
+        impl butane::FromSql for #ident {
+            fn from_sql_ref(val: butane::SqlValRef) -> butane::Result<Self> {
+                let discriminant: i64 = match val {
+                    butane::SqlValRef::SmallInt(i) => i.into(),
+                    butane::SqlValRef::Int(i) => i.into(),
+                    butane::SqlValRef::BigInt(i) => i,
+                    _ => return #err,
+                };
+                #(
+                    if discriminant == Self::#variants as i64 {
+                        return Ok(Self::#variants);
+                    }
+                )*
+                #err
+            }
+        }
+    )
+}
+
+fn conv_err(sqltype: &SqlType) -> TokenStream2 {
+    let sqltype = sqltype_tokens(sqltype);
+    quote!(Err(butane::Error::CannotConvertSqlVal(#sqltype, val.into())))
+}
+
+fn sqltype_tokens(sqltype: &SqlType) -> TokenStream2 {
+    match sqltype {
+        SqlType::SmallInt => quote!(butane::SqlType::SmallInt),
+        SqlType::Int => quote!(butane::SqlType::Int),
+        SqlType::BigInt => quote!(butane::SqlType::BigInt),
+        _ => quote!(butane::SqlType::Text),
+    }
+}
@@ -45,7 +45,7 @@ fn create_atables(ast_struct: &ItemStruct, config: &dbobj::Config) -> Vec<ATable
         if is_row_field(f) {
             let col = AColumn::new(
                 name,
-                get_deferred_sql_type(&f.ty),
+                column_sql_type(f),
                 is_nullable(f),
                 f == &pk,
                 is_auto(f),
@@ -61,6 +61,20 @@ fn create_atables(ast_struct: &ItemStruct, config: &dbobj::Config) -> Vec<ATable
     result
 }
 
+/// The type of the column for `field`. A malformed or misplaced
+/// `numeric` attribute is ignored here, as it is reported as a
+/// compiler error when the model is verified.
+fn column_sql_type(field: &Field) -> DeferredSqlType {
+    let ty = get_deferred_sql_type(&field.ty);
+    #[cfg(feature = "decimal")]
+    if let Ok(Some(precision)) = get_numeric_precision(field) {
+        if let DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Numeric(_))) = ty {
+            return DeferredSqlType::KnownId(TypeIdentifier::Ty(SqlType::Numeric(Some(precision))));
+        }
+    }
+    ty
+}
+
 fn many_table(main_table_name: &str, many_field: &Field, pk_field: &Field) -> ATable {
     let field_name = many_field
         .ident
@@ -1,11 +1,13 @@
 use crate::migrations::adb::{DeferredSqlType, TypeIdentifier, TypeKey};
 use crate::migrations::{MigrationMut, MigrationsMut};
+use crate::query::AggregateFunction;
 use crate::{SqlType, SqlVal};
 use proc_macro2::TokenStream as TokenStream2;
 use proc_macro2::{Ident, Span, TokenTree};
-use quote::{quote, ToTokens};
+use quote::{quote, quote_spanned, ToTokens};
 use regex::Regex;
 use syn::parse_quote;
+use syn::spanned::Spanned;
 use syn::{
     punctuated::Punctuated, Attribute, Field, ItemEnum, ItemStruct, ItemType, Lit, LitStr, Meta,
     MetaNameValue, NestedMeta,
@@ -24,6 +26,7 @@ macro_rules! make_compile_error {
 }
 
 mod dbobj;
+mod field_type;
 mod migration;
 
 pub fn model_with_migrations<M>(
@@ -95,6 +98,7 @@ pub fn dataresult(args: TokenStream2, input: TokenStream2) -> TokenStream2 {
     let vis = &ast_struct.vis;
 
     let impltraits = dbobj::impl_dataresult(&ast_struct, &dbo);
+    let fieldexprs = dbobj::add_fieldexprs(&ast_struct);
 
     let fields = match remove_helper_field_attributes(&mut ast_struct.fields) {
         Ok(fields) => &fields.named,
@@ -110,6 +114,7 @@ pub fn dataresult(args: TokenStream2, input: TokenStream2) -> TokenStream2 {
             #fields
         }
         #impltraits
+        #fieldexprs
     )
 }
 
@@ -196,6 +201,35 @@ where
     }
 }
 
+pub fn derive_field_type_with_migrations<M>(
+    input: TokenStream2,
+    ms: &mut impl MigrationsMut<M = M>,
+) -> TokenStream2
+where
+    M: MigrationMut,
+{
+    let ast: syn::DeriveInput = match syn::parse2(input) {
+        Ok(ast) => ast,
+        Err(err) => return err.to_compile_error(),
+    };
+    let sqltype = match field_type::sqltype(&ast) {
+        Ok(sqltype) => sqltype,
+        Err(err) => return err,
+    };
+    let impls = field_type::impl_field_type(&ast, &sqltype);
+    match add_custom_type(
+        ms,
+        ast.ident.to_string(),
+        TypeIdentifier::from(sqltype).into(),
+    ) {
+        Ok(()) => impls,
+        Err(e) => {
+            eprintln!("unable to save type {}", e);
+            quote!(compile_error!("unable to save type");)
+        }
+    }
+}
+
 fn make_ident_literal_str(ident: &Ident) -> LitStr {
     let as_str = format!("{}", ident);
     LitStr::new(&as_str, Span::call_site())
@@ -243,6 +277,9 @@ fn remove_helper_field_attributes(
                         && !a.path.is_ident("sqltype")
                         && !a.path.is_ident("default")
                         && !a.path.is_ident("unique")
+                        && !a.path.is_ident("numeric")
+                        && !a.path.is_ident("backref")
+                        && aggregate_function(&a.path).is_none()
                 });
             }
             Ok(fields)
@@ -290,10 +327,133 @@ fn is_auto(field: &Field) -> bool {
     field.attrs.iter().any(|attr| attr.path.is_ident("auto"))
 }
 
+/// The precision and scale of a decimal field, from an attribute like
+/// `#[numeric(12, 2)]`.
+#[cfg(feature = "decimal")]
+fn get_numeric_precision(
+    field: &Field,
+) -> std::result::Result<Option<(u16, u16)>, CompilerErrorMsg> {
+    let attr = match field.attrs.iter().find(|a| a.path.is_ident("numeric")) {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+    let args: Vec<u16> = match attr.parse_meta() {
+        Ok(Meta::List(list)) => list
+            .nested
+            .iter()
+            .map(|arg| match arg {
+                NestedMeta::Lit(Lit::Int(i)) => i.base10_parse().ok(),
+                _ => None,
+            })
+            .collect::<Option<Vec<u16>>>()
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+    match args.as_slice() {
+        [precision, scale] if scale <= precision => Ok(Some((*precision, *scale))),
+        _ => Err(make_compile_error!(attr.span()=> "expected numeric(precision, scale)").into()),
+    }
+}
+
 fn is_unique(field: &Field) -> bool {
     field.attrs.iter().any(|attr| attr.path.is_ident("unique"))
 }
 
+/// An aggregate function applied to a dataresult field with an
+/// attribute such as `#[count]` or `#[sum(likes)]`.
+struct FieldAggregate {
+    function: AggregateFunction,
+    column: Option<Ident>,
+}
+
+fn aggregate_function(path: &syn::Path) -> Option<AggregateFunction> {
+    if path.is_ident("count") {
+        Some(AggregateFunction::Count)
+    } else if path.is_ident("sum") {
+        Some(AggregateFunction::Sum)
+    } else if path.is_ident("avg") {
+        Some(AggregateFunction::Avg)
+    } else if path.is_ident("min") {
+        Some(AggregateFunction::Min)
+    } else if path.is_ident("max") {
+        Some(AggregateFunction::Max)
+    } else {
+        None
+    }
+}
+
+/// Aggregates are declared on a dataresult field like
+/// `#[count]`, `#[count(column)]` or `#[sum(column)]`
+fn get_aggregate(field: &Field) -> std::result::Result<Option<FieldAggregate>, TokenStream2> {
+    for attr in &field.attrs {
+        let function = match aggregate_function(&attr.path) {
+            Some(function) => function,
+            None => continue,
+        };
+        let column = match attr.parse_meta() {
+            Ok(Meta::Path(_)) if function == AggregateFunction::Count => None,
+            Ok(Meta::List(ml)) if ml.nested.len() == 1 => match ml.nested.first() {
+                Some(NestedMeta::Meta(Meta::Path(path))) if path.get_ident().is_some() => {
+                    path.get_ident().cloned()
+                }
+                _ => return Err(make_compile_error!(ml.nested.span()=> "expected a column name")),
+            },
+            _ => {
+                return Err(make_compile_error!(
+                    attr.span()=> "malformed aggregate, expected #[{}(column)]",
+                    attr.path.to_token_stream()
+                ))
+            }
+        };
+        return Ok(Some(FieldAggregate { function, column }));
+    }
+    Ok(None)
+}
+
+fn is_aggregate(field: &Field) -> bool {
+    field
+        .attrs
+        .iter()
+        .any(|attr| aggregate_function(&attr.path).is_some())
+}
+
+/// The SQL selecting an aggregate field, e.g. `COUNT(*)`.
+fn aggregate_sql(field: &Field, agg: &FieldAggregate) -> String {
+    let ty = match get_deferred_sql_type(&field.ty) {
+        DeferredSqlType::KnownId(TypeIdentifier::Ty(ty)) => ty,
+        _ => SqlType::BigInt,
+    };
+    let column = agg.column.as_ref().map(|c| c.to_string());
+    let mut sql = String::new();
+    crate::db::helper::sql_aggregate_expr(agg.function, column.as_deref(), &ty, &mut sql);
+    sql
+}
+
+/// Backreferences are declared on a `BackRef` field like
+/// `#[backref(field)]`, where `field` is the `ForeignKey` field of the
+/// referring model. Returns `Ok(None)` if the attribute is absent.
+fn get_backref(field: &Field) -> std::result::Result<Option<Ident>, TokenStream2> {
+    let attr = match field.attrs.iter().find(|a| a.path.is_ident("backref")) {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+    match attr.parse_meta() {
+        Ok(Meta::List(ml)) if ml.nested.len() == 1 => match ml.nested.first() {
+            Some(NestedMeta::Meta(Meta::Path(path))) if path.get_ident().is_some() => {
+                Ok(path.get_ident().cloned())
+            }
+            _ => Err(make_compile_error!(ml.nested.span()=> "expected a field name")),
+        },
+        _ => Err(make_compile_error!(
+            attr.span()=> "malformed backref, expected #[backref(field)]"
+        )),
+    }
+}
+
+fn is_backref(field: &Field) -> bool {
+    get_foreign_type_argument(&field.ty, "BackRef").is_some()
+}
+
 fn fields(ast_struct: &ItemStruct) -> impl Iterator<Item = &Field> {
     ast_struct
         .fields
@@ -328,7 +488,7 @@ fn is_option(field: &Field) -> bool {
 /// Check for special fields which won't correspond to rows and don't
 /// implement FieldType
 fn is_row_field(f: &Field) -> bool {
-    !is_many_to_many(f)
+    !is_many_to_many(f) && !is_backref(f)
 }
 
 fn get_foreign_type_argument<'a>(ty: &'a syn::Type, tyname: &'static str) -> Option<&'a syn::Path> {
@@ -412,17 +572,13 @@ fn some_known(ty: SqlType) -> Option<DeferredSqlType> {
 fn get_primitive_sql_type(ty: &syn::Type) -> Option<DeferredSqlType> {
     if *ty == parse_quote!(bool) {
         return some_known(SqlType::Bool);
-    } else if *ty == parse_quote!(u8)
-        || *ty == parse_quote!(i8)
-        || *ty == parse_quote!(u16)
-        || *ty == parse_quote!(i16)
-        || *ty == parse_quote!(u16)
-        || *ty == parse_quote!(i32)
-    {
+    } else if *ty == parse_quote!(u8) || *ty == parse_quote!(i8) || *ty == parse_quote!(i16) {
+        return some_known(SqlType::SmallInt);
+    } else if *ty == parse_quote!(u16) || *ty == parse_quote!(i32) {
         return some_known(SqlType::Int);
-    } else if *ty == parse_quote!(u32) || *ty == parse_quote!(i64) {
-        // Future improvement: better support unsigned integers
-        // here. Sqlite has no u64, though Postgres does
+    } else if *ty == parse_quote!(u32) || *ty == parse_quote!(i64) || *ty == parse_quote!(u64) {
+        // Neither database has an unsigned 64 bit type, so u64
+        // values above i64::MAX cannot be stored.
         return some_known(SqlType::BigInt);
     } else if *ty == parse_quote!(f32) || *ty == parse_quote!(f64) {
         return some_known(SqlType::Real);
@@ -430,12 +586,29 @@ fn get_primitive_sql_type(ty: &syn::Type) -> Option<DeferredSqlType> {
         return some_known(SqlType::Text);
     } else if *ty == parse_quote!(Vec<u8>) {
         return some_known(SqlType::Blob);
+    } else if is_json_type(ty) {
+        return some_known(SqlType::Json);
     }
 
     #[cfg(feature = "datetime")]
     {
         if *ty == parse_quote!(NaiveDateTime) {
             return some_known(SqlType::Timestamp);
+        } else if *ty == parse_quote!(NaiveDate) || *ty == parse_quote!(chrono::NaiveDate) {
+            return some_known(SqlType::Date);
+        } else if *ty == parse_quote!(NaiveTime) || *ty == parse_quote!(chrono::NaiveTime) {
+            return some_known(SqlType::Time);
+        } else if *ty == parse_quote!(DateTime<Utc>)
+            || *ty == parse_quote!(chrono::DateTime<chrono::Utc>)
+        {
+            return some_known(SqlType::TimestampTz);
+        }
+    }
+
+    #[cfg(feature = "decimal")]
+    {
+        if *ty == parse_quote!(Decimal) || *ty == parse_quote!(rust_decimal::Decimal) {
+            return some_known(SqlType::Numeric(None));
         }
     }
 
@@ -449,6 +622,20 @@ fn get_primitive_sql_type(ty: &syn::Type) -> Option<DeferredSqlType> {
     None
 }
 
+/// Whether the type is `Json<T>` (or `butane::Json<T>`).
+fn is_json_type(ty: &syn::Type) -> bool {
+    let path = match ty {
+        syn::Type::Path(path) => &path.path,
+        _ => return false,
+    };
+    match path.segments.last() {
+        Some(seg) => {
+            seg.ident == "Json" && matches!(seg.arguments, syn::PathArguments::AngleBracketed(_))
+        }
+        None => false,
+    }
+}
+
 fn has_derive_serialize(attrs: &[Attribute]) -> bool {
     for attr in attrs {
         if let Ok(Meta::List(ml)) = attr.parse_meta() {
@@ -502,13 +689,23 @@ fn sqltype_from_name(name: &Ident) -> Option<TypeIdentifier> {
     let name = name.to_string();
     match name.as_ref() {
         "Bool" => return some_id(SqlType::Bool),
+        "SmallInt" => return some_id(SqlType::SmallInt),
         "Int" => return some_id(SqlType::Int),
         "BigInt" => return some_id(SqlType::BigInt),
         "Real" => return some_id(SqlType::Real),
         "Text" => return some_id(SqlType::Text),
         #[cfg(feature = "datetime")]
         "Timestamp" => return some_id(SqlType::Timestamp),
+        #[cfg(feature = "datetime")]
+        "Date" => return some_id(SqlType::Date),
+        #[cfg(feature = "datetime")]
+        "Time" => return some_id(SqlType::Time),
+        #[cfg(feature = "datetime")]
+        "TimestampTz" => return some_id(SqlType::TimestampTz),
+        #[cfg(feature = "decimal")]
+        "Numeric" => return some_id(SqlType::Numeric(None)),
         "Blob" => return some_id(SqlType::Blob),
+        "Json" => return some_id(SqlType::Json),
         _ => (),
     }
     if let Some(custom_name) = Regex::new(r"^Custom\((.*)\)$").unwrap().captures(&name) {
@@ -1,7 +1,9 @@
 //! Not expected to be called directly by most users. Used by code
 //! generated by `#[model]`, `query!`, and other macros.
 
-use crate::query::{BoolExpr, Expr, Order};
+use crate::query::{
+    Aggregate, Assignment, BoolExpr, Expr, GroupBy, Order, SelectJoin, SelectQuery,
+};
 use crate::{Result, SqlType, SqlVal, SqlValRef};
 use std::ops::{Deref, DerefMut};
 use std::vec::Vec;
@@ -12,15 +14,84 @@ use std::vec::Vec;
 /// implemented by both database connections and transactions.
 pub trait ConnectionMethods {
     fn execute(&self, sql: &str) -> Result<()>;
+    /// Selects `columns` from the rows of `table` for which `expr` is
+    /// true. If `distinct` is true, duplicate rows are omitted.
+    #[allow(clippy::too_many_arguments)]
     fn query<'a, 'b, 'c: 'a>(
         &'c self,
         table: &str,
         columns: &'b [Column],
+        distinct: bool,
         expr: Option<BoolExpr>,
+        group: Option<GroupBy>,
         limit: Option<i32>,
         offset: Option<i32>,
         sort: Option<&[Order]>,
     ) -> Result<RawQueryResult<'a>>;
+    /// Like `query`, but described by `query`, whose source may be a
+    /// compound query.
+    fn query_compound<'a, 'b, 'c: 'a>(
+        &'c self,
+        columns: &'b [Column],
+        query: SelectQuery,
+    ) -> Result<RawQueryResult<'a>>;
+    /// Like `query_compound`, but reads the rows from the database in
+    /// batches as they are requested, so that the full result need
+    /// not be held in memory. The connection may not be used for
+    /// other queries until the result is dropped. The default
+    /// implementation calls `query_compound`, for backends whose
+    /// results are always read lazily.
+    fn query_stream<'a, 'b, 'c: 'a>(
+        &'c self,
+        columns: &'b [Column],
+        query: SelectQuery,
+    ) -> Result<RawQueryResult<'a>> {
+        self.query_compound(columns, query)
+    }
+    /// Returns the database's plan for the query described by
+    /// `query` (EXPLAIN in SQL), as text, without executing it.
+    fn explain(&self, columns: &[Column], query: SelectQuery) -> Result<String>;
+    /// Executes the SELECT query `sql` with `params` as the values of
+    /// its placeholders. Placeholders are written as `?` for all
+    /// backends.
+    fn query_sql<'c>(&'c self, sql: &str, params: &[SqlVal]) -> Result<RawQueryResult<'c>>;
+    /// Like `query`, but rather than executing the query immediately,
+    /// prepares it so that it may be executed repeatedly. Each
+    /// [Expr::Placeholder] in `expr` or `group` must be given a value
+    /// when the query is executed.
+    #[allow(clippy::too_many_arguments)]
+    fn prepare_query<'c>(
+        &'c self,
+        table: &str,
+        columns: &[Column],
+        distinct: bool,
+        expr: Option<BoolExpr>,
+        group: Option<GroupBy>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<&[Order]>,
+    ) -> Result<Box<dyn PreparedStatement + 'c>>;
+    /// Like `query`, but joins `table` with another table as described
+    /// by `join`. Each row contains `columns` followed by
+    /// `join.columns`. The columns in `expr` and `sort` refer to
+    /// `table`.
+    #[allow(clippy::too_many_arguments)]
+    fn query_join<'a, 'b, 'c: 'a>(
+        &'c self,
+        table: &str,
+        columns: &'b [Column],
+        join: SelectJoin,
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        sort: Option<&[Order]>,
+    ) -> Result<RawQueryResult<'a>>;
+    /// Computes the aggregate `agg` over the rows of `table` for
+    /// which `expr` is true (or all rows if `expr` is `None`).
+    fn aggregate(&self, table: &str, agg: Aggregate, expr: Option<BoolExpr>) -> Result<SqlVal>;
+    /// Tests whether `table` has any rows for which `expr` is true
+    /// (or any rows at all if `expr` is `None`), without loading them.
+    fn exists(&self, table: &str, expr: Option<BoolExpr>) -> Result<bool>;
     fn insert_returning_pk(
         &self,
         table: &str,
@@ -51,10 +122,27 @@ pub trait ConnectionMethods {
         Ok(())
     }
     fn delete_where(&self, table: &str, expr: BoolExpr) -> Result<usize>;
+    /// Applies `assignments` to every row of `table` for which `expr`
+    /// is true. Returns the number of rows updated.
+    fn update_where(
+        &self,
+        table: &str,
+        assignments: Vec<Assignment>,
+        expr: BoolExpr,
+    ) -> Result<usize>;
     /// Tests if a table exists in the database.
     fn has_table(&self, table: &str) -> Result<bool>;
 }
 
+/// A query prepared by [ConnectionMethods::prepare_query]. Most users
+/// do not need to use this directly and should use
+/// [PreparedQuery](crate::query::PreparedQuery) instead.
+pub trait PreparedStatement {
+    /// Executes the query. `args` are the values for the query's
+    /// placeholders, in order.
+    fn query(&mut self, args: Vec<SqlVal>) -> Result<RawQueryResult<'_>>;
+}
+
 /// Represents a database column. Most users do not need to use this
 /// directly.
 pub struct Column {
@@ -78,12 +166,82 @@ impl Column {
 pub trait BackendRow {
     fn get(&self, idx: usize, ty: SqlType) -> Result<SqlValRef>;
     fn len(&self) -> usize;
+    /// The declared type of the column at `idx`, if the backend knows
+    /// it and it cannot hold values of `ty`. Used to check the
+    /// columns of hand-written queries, including those whose values
+    /// are NULL.
+    fn incompatible_column_type(&self, _idx: usize, _ty: &SqlType) -> Option<String> {
+        None
+    }
     // clippy wants this method to exist
     fn is_empty(&self) -> bool {
         self.len() == 0
     }
 }
 
+/// A row which has already been read into memory, for example so
+/// that it can be converted with `from_row` more than once.
+impl BackendRow for Vec<SqlVal> {
+    fn get(&self, idx: usize, _ty: SqlType) -> Result<SqlValRef<'_>> {
+        self.as_slice()
+            .get(idx)
+            .map(SqlVal::as_ref)
+            .ok_or_else(|| crate::Error::BoundsError(format!("No column at index {}", idx)))
+    }
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+}
+
+/// A view of the columns of a row starting at `offset`, so that
+/// several results may be loaded from one row of a join.
+pub(crate) struct OffsetRow<'r> {
+    row: &'r dyn BackendRow,
+    offset: usize,
+    len: usize,
+}
+impl<'r> OffsetRow<'r> {
+    pub fn new(row: &'r dyn BackendRow, offset: usize, len: usize) -> Self {
+        OffsetRow { row, offset, len }
+    }
+}
+impl BackendRow for OffsetRow<'_> {
+    fn get(&self, idx: usize, ty: SqlType) -> Result<SqlValRef<'_>> {
+        if idx >= self.len {
+            return Err(crate::Error::BoundsError(format!(
+                "No column at index {}",
+                idx
+            )));
+        }
+        self.row.get(idx + self.offset, ty)
+    }
+    fn len(&self) -> usize {
+        self.len
+    }
+    fn incompatible_column_type(&self, idx: usize, ty: &SqlType) -> Option<String> {
+        self.row.incompatible_column_type(idx + self.offset, ty)
+    }
+}
+
+/// Reads all of `rows` into memory. `columns` must be the columns
+/// the rows were queried with.
+pub(crate) fn collect_rows(
+    mut rows: RawQueryResult,
+    columns: &[Column],
+) -> Result<Vec<Vec<SqlVal>>> {
+    let mut ret = Vec::new();
+    while let Some(row) = rows.next()? {
+        ret.push(
+            columns
+                .iter()
+                .enumerate()
+                .map(|(i, col)| Ok(SqlVal::from(row.get(i, col.ty().clone())?)))
+                .collect::<Result<Vec<SqlVal>>>()?,
+        );
+    }
+    Ok(ret)
+}
+
 /// Abstraction of rows returned from a query. Most users do not need
 /// to deal with this directly and should use the `query!` macro or
 /// [Query](crate::query::Query) type.
@@ -134,31 +292,6 @@ pub trait ConnectionMethodWrapper {
     fn wrapped_connection_methods(&self) -> Result<&Self::Wrapped>;
 }
 
-pub(crate) struct VecRows<T> {
-    rows: Vec<T>,
-    idx: usize,
-}
-impl<T> VecRows<T> {
-    #[allow(unused)] // Not used with all feature combinations
-    pub fn new(rows: Vec<T>) -> Self {
-        VecRows { rows, idx: 0 }
-    }
-}
-impl<T> BackendRows for VecRows<T>
-where
-    T: BackendRow,
-{
-    fn next(&mut self) -> Result<Option<&(dyn BackendRow)>> {
-        let ret = self.rows.get(self.idx);
-        self.idx += 1;
-        Ok(ret.map(|row| row as &dyn BackendRow))
-    }
-
-    fn current(&self) -> Option<&(dyn BackendRow)> {
-        self.rows.get(self.idx).map(|row| row as &dyn BackendRow)
-    }
-}
-
 impl<'a> BackendRows for Box<dyn BackendRows + 'a> {
     fn next(&mut self) -> Result<Option<&(dyn BackendRow)>> {
         BackendRows::next(self.deref_mut())
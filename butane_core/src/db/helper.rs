@@ -6,17 +6,208 @@
 use super::Column;
 use crate::migrations::adb::{AColumn, TypeIdentifier};
 use crate::query::Expr::{Condition, Placeholder, Val};
-use crate::query::{BoolExpr::*, Expr, Join, Order, OrderDirection};
+use crate::query::{
+    Aggregate, AggregateFunction, ArithOp, Assignment, BoolExpr::*, CompareOp, Expr, Join,
+    NullsOrder, Order, OrderDirection, SelectJoin, SetOperation,
+};
 use crate::Error;
 use crate::{query, Result, SqlType, SqlVal};
 use std::borrow::Cow;
 use std::fmt::Write;
 
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
+
 #[cfg(feature = "datetime")]
-use chrono::naive::NaiveDateTime;
+use chrono::{naive::NaiveDateTime, NaiveDate, NaiveTime, SecondsFormat, TimeZone, Utc};
+
+/// Alias for the table of an [Expr::Related] subquery.
+const RELATED_ALIAS: &str = "butane_related";
+/// Alias for the table of a [query::BoolExpr::Exists] subquery.
+const EXISTS_ALIAS: &str = "butane_exists";
+/// Alias for the joined table of a [SelectJoin], so that it is
+/// distinct from the queried table even if they are the same.
+const JOIN_ALIAS: &str = "butane_join";
 
 pub trait PlaceholderSource {
     fn next_placeholder(&mut self) -> Cow<str>;
+    /// The placeholder for an [Expr::Placeholder], whose value is
+    /// bound when a prepared query is executed rather than being
+    /// added to the values when the SQL is written.
+    fn next_bind_placeholder(&mut self) -> Cow<'_, str> {
+        self.next_placeholder()
+    }
+}
+
+/// Writes `sql` to `w`, replacing each `?` placeholder (as found by
+/// [query::raw_sql_placeholders]) with a placeholder from `pls`.
+/// Returns the number of placeholders.
+pub fn sql_with_placeholders<P, W>(sql: &str, pls: &mut P, w: &mut W) -> usize
+where
+    P: PlaceholderSource,
+    W: Write,
+{
+    let positions = query::raw_sql_placeholders(sql);
+    let mut start = 0;
+    for &pos in &positions {
+        w.write_str(&sql[start..pos]).unwrap();
+        w.write_str(&pls.next_placeholder()).unwrap();
+        start = pos + 1;
+    }
+    w.write_str(&sql[start..]).unwrap();
+    positions.len()
+}
+
+/// Combines the values collected while writing the SQL for a query
+/// with `args`, the values for its [Expr::Placeholder]s. `binds` are
+/// the (zero-based) positions of the latter among all placeholders,
+/// in ascending order.
+pub fn merge_bind_args(
+    values: &[SqlVal],
+    binds: &[usize],
+    args: Vec<SqlVal>,
+) -> Result<Vec<SqlVal>> {
+    if args.len() != binds.len() {
+        return Err(Error::BoundsError(format!(
+            "Expected {} values for placeholders, found {}",
+            binds.len(),
+            args.len()
+        )));
+    }
+    let mut args = args.into_iter();
+    let mut values = values.iter();
+    let mut binds = binds.iter().peekable();
+    Ok((0..binds.len() + values.len())
+        .map(|i| {
+            if binds.next_if_eq(&&i).is_some() {
+                args.next().unwrap()
+            } else {
+                values.next().unwrap().clone()
+            }
+        })
+        .collect())
+}
+
+/// Writes the SQL for `query`, selecting `columns`, as used by the
+/// backends' `query_compound`. Arguments are as for [sql_for_query].
+pub fn sql_for_select_query<F, P>(
+    columns: &[Column],
+    query: query::SelectQuery,
+    emulate_nulls: bool,
+    offset_requires_limit: bool,
+    f: F,
+    values: &mut Vec<SqlVal>,
+    pls: &mut P,
+) -> String
+where
+    F: Fn(Expr, &mut Vec<SqlVal>, &mut P, &mut String) + Copy,
+    P: PlaceholderSource,
+{
+    let table = match query.source {
+        query::QuerySource::Table(table) => table,
+        query::QuerySource::Compound(alias, left, op, right) => {
+            let mut sql = |query| {
+                sql_for_select_query(
+                    columns,
+                    query,
+                    emulate_nulls,
+                    offset_requires_limit,
+                    f,
+                    values,
+                    pls,
+                )
+            };
+            let left = sql(*left);
+            let right = sql(*right);
+            let mut table = String::new();
+            sql_compound(&left, op, &right, &alias, &mut table);
+            Cow::Owned(table)
+        }
+    };
+    let sort = if query.sort.is_empty() {
+        None
+    } else {
+        Some(query.sort.as_slice())
+    };
+    sql_for_query(
+        &table,
+        columns,
+        query.distinct,
+        query.expr,
+        query.group,
+        query.limit,
+        query.offset,
+        sort,
+        emulate_nulls,
+        offset_requires_limit,
+        f,
+        values,
+        pls,
+    )
+}
+
+/// Writes the SQL for a SELECT query, as used by the backends'
+/// `query` and `prepare_query`. Expressions are written with `f`, the
+/// backend's counterpart of [sql_for_expr]. `emulate_nulls` is as for
+/// [sql_order], and `offset_requires_limit` is whether the backend
+/// supports OFFSET only following LIMIT.
+#[allow(clippy::too_many_arguments)]
+pub fn sql_for_query<F, P>(
+    table: &str,
+    columns: &[Column],
+    distinct: bool,
+    expr: Option<query::BoolExpr>,
+    group: Option<query::GroupBy>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+    order: Option<&[Order]>,
+    emulate_nulls: bool,
+    offset_requires_limit: bool,
+    f: F,
+    values: &mut Vec<SqlVal>,
+    pls: &mut P,
+) -> String
+where
+    F: Fn(Expr, &mut Vec<SqlVal>, &mut P, &mut String),
+    P: PlaceholderSource,
+{
+    let mut sqlquery = String::new();
+    if distinct {
+        sql_select_distinct(columns, table, &mut sqlquery);
+    } else {
+        sql_select(columns, table, &mut sqlquery);
+    }
+    if let Some(expr) = expr {
+        sqlquery.write_str(" WHERE ").unwrap();
+        f(Condition(Box::new(expr)), values, pls, &mut sqlquery);
+    }
+
+    if let Some(group) = group {
+        if !group.columns.is_empty() {
+            sql_group_by(&group.columns, &mut sqlquery);
+        }
+        if let Some(having) = group.having {
+            sqlquery.write_str(" HAVING ").unwrap();
+            f(Condition(Box::new(having)), values, pls, &mut sqlquery);
+        }
+    }
+
+    if let Some(order) = order {
+        sql_order(order, emulate_nulls, &f, values, pls, &mut sqlquery)
+    }
+
+    if let Some(limit) = limit {
+        sql_limit(limit, &mut sqlquery)
+    }
+
+    if let Some(offset) = offset {
+        if limit.is_none() && offset_requires_limit {
+            // Add a max limit if we don't have one already.
+            sql_limit(i32::MAX, &mut sqlquery)
+        }
+        sql_offset(offset, &mut sqlquery)
+    }
+    sqlquery
 }
 
 /// Writes to `w` the SQL to express the expression given in `expr`. Values contained in `expr` are rendered
@@ -29,10 +220,12 @@ where
 {
     match expr {
         Expr::Column(name) => w.write_str(name),
+        Expr::QualifiedColumn(table, name) => write!(w, "{}.{}", table, name),
         Val(v) => match v {
             // No risk of SQL injection with integers and the
             // different sizes are tricky with the PG backend's binary
             // protocol
+            SqlVal::SmallInt(i) => write!(w, "{}", i),
             SqlVal::Int(i) => write!(w, "{}", i),
             SqlVal::BigInt(i) => write!(w, "{}", i),
             _ => {
@@ -40,7 +233,68 @@ where
                 w.write_str(&pls.next_placeholder())
             }
         },
-        Placeholder => w.write_str(&pls.next_placeholder()),
+        Placeholder => w.write_str(&pls.next_bind_placeholder()),
+        Expr::OutOfRange => w.write_str("NULL"),
+        Expr::Arith(a, op, b) => {
+            let op = match op {
+                ArithOp::Add => "+",
+                ArithOp::Sub => "-",
+                ArithOp::Mul => "*",
+                ArithOp::Div => "/",
+                ArithOp::Rem => "%",
+            };
+            w.write_str("(").unwrap();
+            f(*a, values, pls, w);
+            write!(w, " {} ", op).unwrap();
+            f(*b, values, pls, w);
+            w.write_str(")")
+        }
+        Expr::Function(name, args) => {
+            write!(w, "{}(", name).unwrap();
+            args.into_iter().fold("", |sep, arg| {
+                w.write_str(sep).unwrap();
+                f(arg, values, pls, w);
+                ", "
+            });
+            w.write_str(")")
+        }
+        Expr::JsonGet(json, path) if path.iter().any(|key| key.contains('"')) => {
+            // A JSON path cannot quote a key containing a double
+            // quote, so look up each key with json_each instead:
+            // (SELECT value FROM json_each(<json>) WHERE key = ?)
+            for _ in 0..path.len() {
+                w.write_str("(SELECT value FROM json_each(").unwrap();
+            }
+            f(*json, values, pls, w);
+            for key in path {
+                values.push(SqlVal::Text(key));
+                write!(w, ") WHERE key = {})", pls.next_placeholder()).unwrap();
+            }
+            Ok(())
+        }
+        Expr::JsonGet(json, path) => {
+            w.write_str("json_extract(").unwrap();
+            f(*json, values, pls, w);
+            values.push(SqlVal::Text(json_path(&path)));
+            write!(w, ", {})", pls.next_placeholder())
+        }
+        Expr::Related {
+            table,
+            col,
+            tbl2,
+            tbl2_col,
+            expr,
+        } => {
+            // Alias tbl2 so that table.col refers to the outer table
+            // even if it is the same as tbl2.
+            w.write_str("(SELECT ").unwrap();
+            f(*expr, values, pls, w);
+            write!(
+                w,
+                " FROM {} AS {} WHERE {}.{} = {}.{})",
+                tbl2, RELATED_ALIAS, RELATED_ALIAS, tbl2_col, table, col
+            )
+        }
         Condition(c) => match *c {
             True => write!(w, "TRUE"),
             Eq(col, ex) => match ex {
@@ -56,6 +310,14 @@ where
             Le(col, ex) => write!(w, "{} <= ", col).and_then(|_| Ok(f(ex, values, pls, w))),
             Ge(col, ex) => write!(w, "{} >= ", col).and_then(|_| Ok(f(ex, values, pls, w))),
             Like(col, ex) => write!(w, "{} like ", col).and_then(|_| Ok(f(ex, values, pls, w))),
+            // LIKE is case-insensitive (for ASCII) by default in
+            // sqlite. Backends for which it is not must render ILike
+            // themselves.
+            LikeEscape(col, ex) | ILike(col, ex) => {
+                write!(w, "{} LIKE ", col).unwrap();
+                f(ex, values, pls, w);
+                write!(w, " ESCAPE '\\'")
+            }
             AllOf(conds) => {
                 let mut remaining = conds.len();
                 for cond in conds {
@@ -75,12 +337,17 @@ where
                 Ok(())
             }
             Or(a, b) => {
+                write!(w, "(").unwrap();
                 f(Condition(a), values, pls, w);
                 write!(w, " OR ").unwrap();
                 f(Condition(b), values, pls, w);
-                Ok(())
+                write!(w, ")")
+            }
+            Not(a) => {
+                write!(w, "NOT (").unwrap();
+                f(Condition(a), values, pls, w);
+                write!(w, ")")
             }
-            Not(a) => write!(w, "NOT ").and_then(|_| Ok(f(Condition(a), values, pls, w))),
             Subquery {
                 col,
                 tbl2,
@@ -92,6 +359,19 @@ where
                 write!(w, ")").unwrap();
                 Ok(())
             }
+            Exists { tbl2, expr } => {
+                // Alias tbl2 as for Expr::Related, so that qualified
+                // columns refer to the enclosing table.
+                write!(
+                    w,
+                    "EXISTS (SELECT 1 FROM {} AS {} WHERE ",
+                    tbl2, EXISTS_ALIAS
+                )
+                .unwrap();
+                f(Expr::Condition(expr), values, pls, w);
+                write!(w, ")").unwrap();
+                Ok(())
+            }
             SubqueryJoin {
                 col,
                 tbl2,
@@ -109,6 +389,9 @@ where
                 write!(w, ")").unwrap();
                 Ok(())
             }
+            // An empty IN list is not valid SQL on all backends, but
+            // it can never match
+            In(_, vals) if vals.is_empty() => write!(w, "FALSE"),
             In(col, vals) => {
                 write!(w, "{} IN (", col).unwrap();
                 let mut remaining = vals.len();
@@ -121,6 +404,26 @@ where
                 }
                 write!(w, ")")
             }
+            Raw(sql, params) => {
+                w.write_str("(").unwrap();
+                sql_with_placeholders(&sql, pls, w);
+                values.extend(params);
+                w.write_str(")")
+            }
+            Compare(a, CompareOp::Eq, Expr::Val(SqlVal::Null)) => {
+                f(a, values, pls, w);
+                write!(w, " IS NULL")
+            }
+            Compare(a, CompareOp::Ne, Expr::Val(SqlVal::Null)) => {
+                f(a, values, pls, w);
+                write!(w, " IS NOT NULL")
+            }
+            Compare(a, op, b) => {
+                f(a, values, pls, w);
+                write!(w, " {} ", sql_compare_op(op)).unwrap();
+                f(b, values, pls, w);
+                Ok(())
+            }
         },
     }
     .unwrap()
@@ -132,6 +435,122 @@ pub fn sql_select(columns: &[Column], table: &str, w: &mut impl Write) {
     write!(w, " FROM {}", table).unwrap();
 }
 
+/// Like [sql_select], but omits duplicate rows (SELECT DISTINCT).
+pub fn sql_select_distinct(columns: &[Column], table: &str, w: &mut impl Write) {
+    write!(w, "SELECT DISTINCT ").unwrap();
+    list_columns(columns, w);
+    write!(w, " FROM {}", table).unwrap();
+}
+
+/// Writes a subquery combining the results of the queries `left` and
+/// `right` with `op`, for use in place of a table name. Each query is
+/// wrapped in a subquery of its own so that it may have its own ORDER
+/// BY and LIMIT. The combined rows are aliased as `alias`.
+pub fn sql_compound(left: &str, op: SetOperation, right: &str, alias: &str, w: &mut impl Write) {
+    let op = match op {
+        SetOperation::Union => "UNION",
+        SetOperation::Intersect => "INTERSECT",
+        SetOperation::Except => "EXCEPT",
+    };
+    write!(
+        w,
+        "(SELECT * FROM ({}) AS butane_left {} SELECT * FROM ({}) AS butane_right) AS {}",
+        left, op, right, alias
+    )
+    .unwrap();
+}
+
+/// Writes the start of a query testing whether any rows of `table`
+/// exist, up to and including the table name. The caller must add
+/// any WHERE clause followed by a closing parenthesis.
+pub fn sql_select_exists(table: &str, w: &mut impl Write) {
+    write!(w, "SELECT EXISTS(SELECT 1 FROM {}", table).unwrap();
+}
+
+/// Writes the start of a SELECT for `columns` of `table` together
+/// with the columns of `join`, up to and including the FROM
+/// keyword. Each column is qualified with its table and aliased as
+/// `<table>_<column>`, so that columns with the same name in both
+/// tables remain distinct. The joined table is referred to by an
+/// alias, which [sql_select_join_on] defines.
+pub fn sql_select_join(columns: &[Column], table: &str, join: &SelectJoin, w: &mut impl Write) {
+    write!(w, "SELECT ").unwrap();
+    list_columns_qualified(columns, table, w);
+    w.write_str(",").unwrap();
+    list_columns_qualified(join.columns, JOIN_ALIAS, w);
+    write!(w, " FROM ").unwrap();
+}
+
+/// Writes the JOIN clause of a [SelectJoin] started with
+/// [sql_select_join]. The joined table is aliased, so that a model
+/// may be joined with itself through a self-referential
+/// [ForeignKey](crate::ForeignKey).
+pub fn sql_select_join_on(join: Join, w: &mut impl Write) {
+    let (kind, join_table, col1, col2) = match join {
+        Join::Inner {
+            join_table,
+            col1,
+            col2,
+        } => ("INNER", join_table, col1, col2),
+        Join::Left {
+            join_table,
+            col1,
+            col2,
+        } => ("LEFT", join_table, col1, col2),
+    };
+    write!(w, "{} JOIN {} AS {} ON ", kind, join_table, JOIN_ALIAS).unwrap();
+    sql_column(col1, w);
+    write!(w, " = {}.{}", JOIN_ALIAS, col2.name()).unwrap();
+}
+
+pub fn sql_select_aggregate(agg: &Aggregate, table: &str, w: &mut impl Write) {
+    write!(w, "SELECT ").unwrap();
+    sql_aggregate(agg, w);
+    write!(w, " FROM {}", table).unwrap();
+}
+
+pub fn sql_aggregate(agg: &Aggregate, w: &mut impl Write) {
+    sql_aggregate_expr(agg.function, agg.column, &agg.ty, w)
+}
+
+/// Like `sql_aggregate` but without requiring an
+/// [Aggregate]. Used by code generation, where columns are not known
+/// statically.
+pub fn sql_aggregate_expr(
+    function: AggregateFunction,
+    column: Option<&str>,
+    ty: &SqlType,
+    w: &mut impl Write,
+) {
+    let func = match function {
+        AggregateFunction::Count => "COUNT",
+        AggregateFunction::Sum => "SUM",
+        AggregateFunction::Avg => "AVG",
+        AggregateFunction::Min => "MIN",
+        AggregateFunction::Max => "MAX",
+    };
+    let col = column.unwrap_or("*");
+    match function {
+        // The result type of SUM and AVG varies between backends
+        // (e.g. Postgres uses NUMERIC for sums of BIGINT), so cast to
+        // the type we were asked for. These type names are understood
+        // by both sqlite and postgres.
+        AggregateFunction::Sum | AggregateFunction::Avg => {
+            let ty = match ty {
+                SqlType::Real => "DOUBLE PRECISION",
+                SqlType::SmallInt => "SMALLINT",
+                SqlType::Int => "INTEGER",
+                #[cfg(feature = "decimal")]
+                SqlType::Numeric(_) => "NUMERIC",
+                _ => "BIGINT",
+            };
+            write!(w, "CAST({}({}) AS {})", func, col, ty)
+        }
+        _ => write!(w, "{}({})", func, col),
+    }
+    .unwrap()
+}
+
 pub fn sql_insert_with_placeholders(
     table: &str,
     columns: &[Column],
@@ -168,6 +587,34 @@ pub fn sql_update_with_placeholders(
     write!(w, " WHERE {} = {}", pkcol.name(), pls.next_placeholder()).unwrap();
 }
 
+pub fn sql_group_by(columns: &[&'static str], w: &mut impl Write) {
+    write!(w, " GROUP BY {}", columns.join(",")).unwrap();
+}
+
+/// Writes to `w` the SQL for an UPDATE of `table` applying
+/// `assignments`, not including the WHERE clause. Values are handled
+/// as in [sql_for_expr].
+pub fn sql_update_assignments<F, P, W>(
+    table: &str,
+    assignments: Vec<Assignment>,
+    f: F,
+    values: &mut Vec<SqlVal>,
+    pls: &mut P,
+    w: &mut W,
+) where
+    F: Fn(Expr, &mut Vec<SqlVal>, &mut P, &mut W),
+    P: PlaceholderSource,
+    W: Write,
+{
+    write!(w, "UPDATE {} SET ", table).unwrap();
+    let mut sep = "";
+    for assignment in assignments {
+        write!(w, "{}{} = ", sep, assignment.column).unwrap();
+        f(assignment.value, values, pls, w);
+        sep = ", ";
+    }
+}
+
 pub fn sql_limit(limit: i32, w: &mut impl Write) {
     write!(w, " LIMIT {}", limit).unwrap();
 }
@@ -176,18 +623,120 @@ pub fn sql_offset(offset: i32, w: &mut impl Write) {
     write!(w, " OFFSET {}", offset).unwrap();
 }
 
-pub fn sql_order(order: &[Order], w: &mut impl Write) {
+/// Writes the ORDER BY clause for `order`, writing expressions with
+/// `f` as in [sql_for_expr]. If `emulate_nulls` is true, the
+/// placement of nulls is emulated by first sorting on whether the
+/// expression is null, for backends which do not support `NULLS
+/// FIRST` and `NULLS LAST`.
+pub fn sql_order<F, P, W>(
+    order: &[Order],
+    emulate_nulls: bool,
+    f: F,
+    values: &mut Vec<SqlVal>,
+    pls: &mut P,
+    w: &mut W,
+) where
+    F: Fn(Expr, &mut Vec<SqlVal>, &mut P, &mut W),
+    P: PlaceholderSource,
+    W: Write,
+{
+    sql_order_impl(None, order, emulate_nulls, f, values, pls, w)
+}
+
+/// Like [sql_order], but qualifies each order term which is a plain
+/// column with `table`.
+pub fn sql_order_qualified<F, P, W>(
+    table: &str,
+    order: &[Order],
+    emulate_nulls: bool,
+    f: F,
+    values: &mut Vec<SqlVal>,
+    pls: &mut P,
+    w: &mut W,
+) where
+    F: Fn(Expr, &mut Vec<SqlVal>, &mut P, &mut W),
+    P: PlaceholderSource,
+    W: Write,
+{
+    sql_order_impl(Some(table), order, emulate_nulls, f, values, pls, w)
+}
+
+fn sql_order_impl<F, P, W>(
+    table: Option<&str>,
+    order: &[Order],
+    emulate_nulls: bool,
+    f: F,
+    values: &mut Vec<SqlVal>,
+    pls: &mut P,
+    w: &mut W,
+) where
+    F: Fn(Expr, &mut Vec<SqlVal>, &mut P, &mut W),
+    P: PlaceholderSource,
+    W: Write,
+{
+    let write_expr =
+        |o: &Order, values: &mut Vec<SqlVal>, pls: &mut P, w: &mut W| match (table, o.column()) {
+            (Some(table), Some(column)) => write!(w, "{}.{}", table, column).unwrap(),
+            _ => f(o.expr.clone(), values, pls, w),
+        };
     write!(w, " ORDER BY ").unwrap();
     order.iter().fold("", |sep, o| {
+        w.write_str(sep).unwrap();
         let sql_dir = match o.direction {
             OrderDirection::Ascending => "ASC",
             OrderDirection::Descending => "DESC",
         };
-        write!(w, "{}{} {}", sep, o.column, sql_dir).unwrap();
+        match o.nulls {
+            Some(nulls) if emulate_nulls => {
+                // Sort by whether the expression is null first
+                let (null, not_null) = match nulls {
+                    NullsOrder::First => (0, 1),
+                    NullsOrder::Last => (1, 0),
+                };
+                w.write_str("CASE WHEN ").unwrap();
+                write_expr(o, values, pls, w);
+                write!(w, " IS NULL THEN {} ELSE {} END, ", null, not_null).unwrap();
+                write_expr(o, values, pls, w);
+                write!(w, " {}", sql_dir).unwrap();
+            }
+            Some(nulls) => {
+                write_expr(o, values, pls, w);
+                let sql_nulls = match nulls {
+                    NullsOrder::First => "NULLS FIRST",
+                    NullsOrder::Last => "NULLS LAST",
+                };
+                write!(w, " {} {}", sql_dir, sql_nulls).unwrap();
+            }
+            None => {
+                write_expr(o, values, pls, w);
+                write!(w, " {}", sql_dir).unwrap();
+            }
+        }
         ", "
     });
 }
 
+/// The SQL operator for a comparison.
+pub fn sql_compare_op(op: CompareOp) -> &'static str {
+    match op {
+        CompareOp::Eq => "=",
+        CompareOp::Ne => "<>",
+        CompareOp::Lt => "<",
+        CompareOp::Gt => ">",
+        CompareOp::Le => "<=",
+        CompareOp::Ge => ">=",
+    }
+}
+
+/// A JSON path for `json_extract` from a sequence of object keys,
+/// e.g. `$."a"."b"`. The keys must not contain double quotes.
+fn json_path(keys: &[String]) -> String {
+    keys.iter().fold(String::from("$"), |mut path, key| {
+        write!(path, ".\"{}\"", key).unwrap();
+        path
+    })
+}
+
 pub fn column_default(col: &AColumn) -> Result<SqlVal> {
     if let Some(val) = col.default() {
         return Ok(val.clone());
@@ -198,13 +747,23 @@ pub fn column_default(col: &AColumn) -> Result<SqlVal> {
     Ok(match col.typeid()? {
         TypeIdentifier::Ty(ty) => match ty {
             SqlType::Bool => SqlVal::Bool(false),
+            SqlType::SmallInt => SqlVal::SmallInt(0),
             SqlType::Int => SqlVal::Int(0),
             SqlType::BigInt => SqlVal::Int(0),
             SqlType::Real => SqlVal::Real(0.0),
             SqlType::Text => SqlVal::Text("".to_string()),
             SqlType::Blob => SqlVal::Blob(Vec::new()),
+            SqlType::Json => SqlVal::Json(serde_json::Value::Null),
             #[cfg(feature = "datetime")]
             SqlType::Timestamp => SqlVal::Timestamp(NaiveDateTime::from_timestamp(0, 0)),
+            #[cfg(feature = "datetime")]
+            SqlType::Date => SqlVal::Date(NaiveDate::from_ymd(1970, 1, 1)),
+            #[cfg(feature = "datetime")]
+            SqlType::Time => SqlVal::Time(NaiveTime::from_hms(0, 0, 0)),
+            #[cfg(feature = "datetime")]
+            SqlType::TimestampTz => SqlVal::TimestampTz(Utc.timestamp(0, 0)),
+            #[cfg(feature = "decimal")]
+            SqlType::Numeric(_) => SqlVal::Numeric(Decimal::ZERO),
             SqlType::Custom(_) => return Err(Error::NoCustomDefault),
         },
         TypeIdentifier::Name(_) => return Err(Error::NoCustomDefault),
@@ -217,24 +776,39 @@ pub fn list_columns(columns: &[Column], w: &mut impl Write) {
     write!(w, "{}", colnames.as_slice().join(",")).unwrap();
 }
 
+fn list_columns_qualified(columns: &[Column], table: &str, w: &mut impl Write) {
+    columns.iter().fold("", |sep, c| {
+        write!(w, "{}{}.{} AS {}_{}", sep, table, c.name(), table, c.name()).unwrap();
+        ","
+    });
+}
+
 fn sql_joins(joins: Vec<Join>, w: &mut impl Write) {
     for join in joins {
-        match join {
-            Join::Inner {
-                join_table,
-                col1,
-                col2,
-            } => {
-                // INNER JOIN <join_table> ON <col1> = <col2>
-                write!(w, "INNER JOIN {} ON ", join_table).unwrap();
-                sql_column(col1, w);
-                w.write_str(" = ").unwrap();
-                sql_column(col2, w);
-            }
-        }
+        sql_join(join, w);
     }
 }
 
+/// Writes the SQL for a single join, e.g. `INNER JOIN <join_table> ON <col1> = <col2>`.
+pub fn sql_join(join: Join, w: &mut impl Write) {
+    let (kind, join_table, col1, col2) = match join {
+        Join::Inner {
+            join_table,
+            col1,
+            col2,
+        } => ("INNER", join_table, col1, col2),
+        Join::Left {
+            join_table,
+            col1,
+            col2,
+        } => ("LEFT", join_table, col1, col2),
+    };
+    write!(w, "{} JOIN {} ON ", kind, join_table).unwrap();
+    sql_column(col1, w);
+    w.write_str(" = ").unwrap();
+    sql_column(col2, w);
+}
+
 fn sql_column(col: query::Column, w: &mut impl Write) {
     match col.table() {
         Some(table) => write!(w, "{}.{}", table, col.name()),
@@ -248,13 +822,26 @@ pub fn sql_literal_value(val: SqlVal) -> Result<String> {
     match val {
         SqlVal::Null => Ok("NULL".to_string()),
         SqlVal::Bool(val) => Ok(val.to_string()),
+        SmallInt(val) => Ok(val.to_string()),
         Int(val) => Ok(val.to_string()),
         BigInt(val) => Ok(val.to_string()),
         Real(val) => Ok(val.to_string()),
         Text(val) => Ok(format!("'{}'", val)),
         Blob(val) => Ok(format!("x'{}'", hex::encode_upper(val))),
+        Json(val) => Ok(format!("'{}'", val.to_string().replace('\'', "''"))),
         #[cfg(feature = "datetime")]
         Timestamp(ndt) => Ok(ndt.format("'%Y-%m-%dT%H:%M:%S%.f'").to_string()),
+        #[cfg(feature = "datetime")]
+        Date(d) => Ok(d.format("'%Y-%m-%d'").to_string()),
+        #[cfg(feature = "datetime")]
+        Time(t) => Ok(t.format("'%H:%M:%S%.9f'").to_string()),
+        #[cfg(feature = "datetime")]
+        TimestampTz(dt) => Ok(format!(
+            "'{}'",
+            dt.to_rfc3339_opts(SecondsFormat::Nanos, true)
+        )),
+        #[cfg(feature = "decimal")]
+        Numeric(d) => Ok(d.normalize().to_string()),
         Custom(val) => Err(Error::LiteralForCustomUnsupported((*val).clone())),
     }
 }
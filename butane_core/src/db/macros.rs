@@ -9,13 +9,80 @@ macro_rules! connection_method_wrapper {
                 &'c self,
                 table: &str,
                 columns: &'b [Column],
+                distinct: bool,
                 expr: Option<BoolExpr>,
+                group: Option<$crate::query::GroupBy>,
                 limit: Option<i32>,
                 offset: Option<i32>,
                 sort: Option<&[$crate::query::Order]>,
             ) -> Result<RawQueryResult<'a>> {
                 self.wrapped_connection_methods()?
-                    .query(table, columns, expr, limit, offset, sort)
+                    .query(table, columns, distinct, expr, group, limit, offset, sort)
+            }
+            fn query_compound<'a, 'b, 'c: 'a>(
+                &'c self,
+                columns: &'b [Column],
+                query: $crate::query::SelectQuery,
+            ) -> Result<RawQueryResult<'a>> {
+                self.wrapped_connection_methods()?
+                    .query_compound(columns, query)
+            }
+            fn query_stream<'a, 'b, 'c: 'a>(
+                &'c self,
+                columns: &'b [Column],
+                query: $crate::query::SelectQuery,
+            ) -> Result<RawQueryResult<'a>> {
+                self.wrapped_connection_methods()?
+                    .query_stream(columns, query)
+            }
+            fn explain(
+                &self,
+                columns: &[Column],
+                query: $crate::query::SelectQuery,
+            ) -> Result<String> {
+                self.wrapped_connection_methods()?.explain(columns, query)
+            }
+            fn query_sql<'c>(&'c self, sql: &str, params: &[SqlVal]) -> Result<RawQueryResult<'c>> {
+                self.wrapped_connection_methods()?.query_sql(sql, params)
+            }
+            fn prepare_query<'c>(
+                &'c self,
+                table: &str,
+                columns: &[Column],
+                distinct: bool,
+                expr: Option<BoolExpr>,
+                group: Option<$crate::query::GroupBy>,
+                limit: Option<i32>,
+                offset: Option<i32>,
+                sort: Option<&[$crate::query::Order]>,
+            ) -> Result<Box<dyn $crate::db::PreparedStatement + 'c>> {
+                self.wrapped_connection_methods()?
+                    .prepare_query(table, columns, distinct, expr, group, limit, offset, sort)
+            }
+            fn query_join<'a, 'b, 'c: 'a>(
+                &'c self,
+                table: &str,
+                columns: &'b [Column],
+                join: $crate::query::SelectJoin,
+                expr: Option<BoolExpr>,
+                limit: Option<i32>,
+                offset: Option<i32>,
+                sort: Option<&[$crate::query::Order]>,
+            ) -> Result<RawQueryResult<'a>> {
+                self.wrapped_connection_methods()?
+                    .query_join(table, columns, join, expr, limit, offset, sort)
+            }
+            fn aggregate(
+                &self,
+                table: &str,
+                agg: $crate::query::Aggregate,
+                expr: Option<BoolExpr>,
+            ) -> Result<SqlVal> {
+                self.wrapped_connection_methods()?
+                    .aggregate(table, agg, expr)
+            }
+            fn exists(&self, table: &str, expr: Option<BoolExpr>) -> Result<bool> {
+                self.wrapped_connection_methods()?.exists(table, expr)
             }
             fn insert_returning_pk(
                 &self,
@@ -60,6 +127,15 @@ macro_rules! connection_method_wrapper {
             fn delete_where(&self, table: &str, expr: BoolExpr) -> Result<usize> {
                 self.wrapped_connection_methods()?.delete_where(table, expr)
             }
+            fn update_where(
+                &self,
+                table: &str,
+                assignments: Vec<$crate::query::Assignment>,
+                expr: BoolExpr,
+            ) -> Result<usize> {
+                self.wrapped_connection_methods()?
+                    .update_where(table, assignments, expr)
+            }
             fn has_table(&self, table: &str) -> Result<bool> {
                 self.wrapped_connection_methods()?.has_table(table)
             }
@@ -12,7 +12,7 @@
 //!    what a `BackendConnection` can do, but allows using a single concrete type that is not tied to a particular
 //!    database backend. It is returned by the `connect` method.
 
-use crate::query::BoolExpr;
+use crate::query::{BoolExpr, SelectQuery};
 use crate::{migrations::adb, Error, Result, SqlVal, SqlValRef};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
@@ -22,7 +22,7 @@ use std::ops::{Deref, DerefMut};
 use std::path::Path;
 
 mod connmethods;
-mod helper;
+pub(crate) mod helper;
 mod macros;
 #[cfg(feature = "pg")]
 pub mod pg;
@@ -37,8 +37,10 @@ pub use r2::ConnectionManager;
 // Macros are always exported at the root of the crate
 use crate::connection_method_wrapper;
 
+pub(crate) use connmethods::{collect_rows, OffsetRow};
 pub use connmethods::{
-    BackendRow, BackendRows, Column, ConnectionMethods, QueryResult, RawQueryResult,
+    BackendRow, BackendRows, Column, ConnectionMethods, PreparedStatement, QueryResult,
+    RawQueryResult,
 };
 
 /// Database connection.
@@ -133,6 +135,10 @@ pub trait Backend {
     fn name(&self) -> &'static str;
     fn create_migration_sql(&self, current: &adb::ADB, ops: Vec<adb::Operation>) -> Result<String>;
     fn connect(&self, conn_str: &str) -> Result<Connection>;
+    /// Renders the SELECT query for `columns` described by `query`
+    /// without executing it, returning the SQL and the values for its
+    /// placeholders. Intended for debugging and tests.
+    fn sql_for_select(&self, columns: &[Column], query: SelectQuery) -> (String, Vec<SqlVal>);
 }
 
 impl Backend for Box<dyn Backend> {
@@ -145,6 +151,9 @@ impl Backend for Box<dyn Backend> {
     fn connect(&self, conn_str: &str) -> Result<Connection> {
         self.deref().connect(conn_str)
     }
+    fn sql_for_select(&self, columns: &[Column], query: SelectQuery) -> (String, Vec<SqlVal>) {
+        self.deref().sql_for_select(columns, query)
+    }
 }
 
 /// Find a backend by name.
@@ -1,6 +1,5 @@
 //! Postgresql database backend
-use super::connmethods::VecRows;
-use super::helper;
+use super::helper::{self, PlaceholderSource};
 use super::*;
 use crate::custom::{SqlTypeCustom, SqlValRefCustom};
 use crate::migrations::adb::{AColumn, ATable, Operation, TypeIdentifier, ADB};
@@ -8,10 +7,13 @@ use crate::{debug, query};
 use crate::{Result, SqlType, SqlVal, SqlValRef};
 use bytes::BufMut;
 #[cfg(feature = "datetime")]
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use postgres::fallible_iterator::FallibleIterator;
 use postgres::GenericClient;
-use std::cell::RefCell;
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
+use std::borrow::Cow;
+use std::cell::{RefCell, RefMut};
 use std::fmt::Write;
 
 /// The name of the postgres backend.
@@ -41,6 +43,9 @@ impl Backend for PgBackend {
             .iter()
             .map(|o| sql_for_op(&mut current, o))
             .collect::<Result<Vec<String>>>()?
+            .into_iter()
+            .filter(|sql| !sql.is_empty())
+            .collect::<Vec<String>>()
             .join("\n"))
     }
 
@@ -49,6 +54,17 @@ impl Backend for PgBackend {
             conn: Box::new(self.connect(path)?),
         })
     }
+
+    fn sql_for_select(
+        &self,
+        columns: &[Column],
+        query: query::SelectQuery,
+    ) -> (String, Vec<SqlVal>) {
+        let mut values: Vec<SqlVal> = Vec::new();
+        let sql =
+            sql_for_select_query(columns, query, &mut values, &mut PgPlaceholderSource::new());
+        (sql, values)
+    }
 }
 
 /// Pg database connection.
@@ -129,26 +145,221 @@ where
         &'c self,
         table: &str,
         columns: &'b [Column],
+        distinct: bool,
         expr: Option<BoolExpr>,
+        group: Option<query::GroupBy>,
         limit: Option<i32>,
         offset: Option<i32>,
         order: Option<&[query::Order]>,
     ) -> Result<RawQueryResult<'a>> {
+        let mut values: Vec<SqlVal> = Vec::new();
+        let mut pls = PgPlaceholderSource::new();
+        let sqlquery = helper::sql_for_query(
+            table,
+            columns,
+            distinct,
+            expr,
+            group,
+            limit,
+            offset,
+            order,
+            false,
+            false,
+            sql_for_expr,
+            &mut values,
+            &mut pls,
+        );
+
+        if cfg!(feature = "log") {
+            debug!("query sql {}", sqlquery);
+        }
+        eprintln!("query sql {}", sqlquery);
+
+        let types: Vec<postgres::types::Type> = values.iter().map(pgtype_for_val).collect();
+        let mut client = self.cell()?.try_borrow_mut()?;
+        let stmt = client.prepare_typed(&sqlquery, types.as_ref())?;
+        let adapter = PgQueryAdapter::new(client, columns.len(), |client| {
+            client.query_raw(&stmt, values.iter().map(sqlval_for_pg_query))
+        })?;
+        Ok(Box::new(adapter))
+    }
+    fn query_compound<'a, 'b, 'c: 'a>(
+        &'c self,
+        columns: &'b [Column],
+        query: query::SelectQuery,
+    ) -> Result<RawQueryResult<'a>> {
+        let mut values: Vec<SqlVal> = Vec::new();
+        let sqlquery =
+            sql_for_select_query(columns, query, &mut values, &mut PgPlaceholderSource::new());
+
+        if cfg!(feature = "log") {
+            debug!("query sql {}", sqlquery);
+        }
+
+        let types: Vec<postgres::types::Type> = values.iter().map(pgtype_for_val).collect();
+        let mut client = self.cell()?.try_borrow_mut()?;
+        let stmt = client.prepare_typed(&sqlquery, types.as_ref())?;
+        let adapter = PgQueryAdapter::new(client, columns.len(), |client| {
+            client.query_raw(&stmt, values.iter().map(sqlval_for_pg_query))
+        })?;
+        Ok(Box::new(adapter))
+    }
+    fn query_stream<'a, 'b, 'c: 'a>(
+        &'c self,
+        columns: &'b [Column],
+        query: query::SelectQuery,
+    ) -> Result<RawQueryResult<'a>> {
+        let mut values: Vec<SqlVal> = Vec::new();
+        let sqlquery =
+            sql_for_select_query(columns, query, &mut values, &mut PgPlaceholderSource::new());
+
+        if cfg!(feature = "log") {
+            debug!("query sql {}", sqlquery);
+        }
+
+        let types: Vec<postgres::types::Type> = values.iter().map(pgtype_for_val).collect();
+        let client = self.cell()?.try_borrow_mut()?;
+        let adapter = PgPortalAdapter::new(client, columns.len(), |transaction| {
+            let stmt = transaction.prepare_typed(&sqlquery, types.as_ref())?;
+            let params: Vec<&DynToSqlPg> = values.iter().map(|v| v as &DynToSqlPg).collect();
+            transaction.bind(&stmt, &params)
+        })?;
+        Ok(Box::new(adapter))
+    }
+    fn explain(&self, columns: &[Column], query: query::SelectQuery) -> Result<String> {
+        let mut values: Vec<SqlVal> = Vec::new();
+        let sqlquery = format!(
+            "EXPLAIN {}",
+            sql_for_select_query(columns, query, &mut values, &mut PgPlaceholderSource::new())
+        );
+        if cfg!(feature = "log") {
+            debug!("explain sql {}", sqlquery);
+        }
+
+        let types: Vec<postgres::types::Type> = values.iter().map(pgtype_for_val).collect();
+        let mut client = self.cell()?.try_borrow_mut()?;
+        let stmt = client.prepare_typed(&sqlquery, types.as_ref())?;
+        // Each row is one line of the plan
+        let lines = client
+            .query_raw(&stmt, values.iter().map(sqlval_for_pg_query))?
+            .map(|row| row.try_get::<_, String>(0))
+            .collect::<Vec<String>>()?;
+        Ok(lines.join("\n"))
+    }
+    fn query_sql<'c>(&'c self, sql: &str, params: &[SqlVal]) -> Result<RawQueryResult<'c>> {
         let mut sqlquery = String::new();
-        helper::sql_select(columns, table, &mut sqlquery);
+        helper::sql_with_placeholders(sql, &mut PgPlaceholderSource::new(), &mut sqlquery);
+        if cfg!(feature = "log") {
+            debug!("query sql {}", sqlquery);
+        }
+
+        let types: Vec<postgres::types::Type> = params.iter().map(pgtype_for_val).collect();
+        let mut client = self.cell()?.try_borrow_mut()?;
+        let stmt = client.prepare_typed(&sqlquery, types.as_ref())?;
+        let ncols = stmt.columns().len();
+        let adapter = PgQueryAdapter::new(client, ncols, |client| {
+            client.query_raw(&stmt, params.iter().map(sqlval_for_pg_query))
+        })?;
+        Ok(Box::new(adapter))
+    }
+    fn prepare_query<'c>(
+        &'c self,
+        table: &str,
+        columns: &[Column],
+        distinct: bool,
+        expr: Option<BoolExpr>,
+        group: Option<query::GroupBy>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        order: Option<&[query::Order]>,
+    ) -> Result<Box<dyn PreparedStatement + 'c>> {
         let mut values: Vec<SqlVal> = Vec::new();
+        let mut pls = PgPlaceholderSource::new();
+        let sqlquery = helper::sql_for_query(
+            table,
+            columns,
+            distinct,
+            expr,
+            group,
+            limit,
+            offset,
+            order,
+            false,
+            false,
+            sql_for_expr,
+            &mut values,
+            &mut pls,
+        );
+        if cfg!(feature = "log") {
+            debug!("prepare query sql {}", sqlquery);
+        }
+
+        // The types of the bound values are inferred by the server
+        let mut values_iter = values.iter();
+        let types: Vec<postgres::types::Type> = (0..values.len() + pls.binds.len())
+            .map(|i| {
+                if pls.binds.contains(&i) {
+                    postgres::types::Type::UNKNOWN
+                } else {
+                    pgtype_for_val(values_iter.next().unwrap())
+                }
+            })
+            .collect();
+        let stmt = self
+            .cell()?
+            .try_borrow_mut()?
+            .prepare_typed(&sqlquery, types.as_ref())?;
+        Ok(Box::new(PgPreparedStatement {
+            conn: self,
+            stmt,
+            values,
+            binds: pls.binds,
+            ncols: columns.len(),
+        }))
+    }
+    #[allow(clippy::too_many_arguments)]
+    fn query_join<'a, 'b, 'c: 'a>(
+        &'c self,
+        table: &str,
+        columns: &'b [Column],
+        join: query::SelectJoin,
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        order: Option<&[query::Order]>,
+    ) -> Result<RawQueryResult<'a>> {
+        let mut sqlquery = String::new();
+        helper::sql_select_join(columns, table, &join, &mut sqlquery);
+        let mut values: Vec<SqlVal> = Vec::new();
+        let mut pls = PgPlaceholderSource::new();
         if let Some(expr) = expr {
-            sqlquery.write_str(" WHERE ").unwrap();
+            // The columns in expr are not qualified with the table
+            // name, so filter in a subquery to avoid ambiguity with
+            // the columns of the joined table.
+            write!(&mut sqlquery, "(SELECT * FROM {} WHERE ", table).unwrap();
             sql_for_expr(
                 query::Expr::Condition(Box::new(expr)),
                 &mut values,
-                &mut PgPlaceholderSource::new(),
+                &mut pls,
                 &mut sqlquery,
             );
+            write!(&mut sqlquery, ") AS {}", table).unwrap();
+        } else {
+            sqlquery.write_str(table).unwrap();
         }
+        sqlquery.write_str(" ").unwrap();
+        helper::sql_select_join_on(join.join, &mut sqlquery);
 
         if let Some(order) = order {
-            helper::sql_order(order, &mut sqlquery)
+            helper::sql_order_qualified(
+                table,
+                order,
+                false,
+                sql_for_expr,
+                &mut values,
+                &mut pls,
+                &mut sqlquery,
+            )
         }
 
         if let Some(limit) = limit {
@@ -162,25 +373,76 @@ where
         if cfg!(feature = "log") {
             debug!("query sql {}", sqlquery);
         }
-        eprintln!("query sql {}", sqlquery);
 
         let types: Vec<postgres::types::Type> = values.iter().map(pgtype_for_val).collect();
-        let stmt = self
+        let mut client = self.cell()?.try_borrow_mut()?;
+        let stmt = client.prepare_typed(&sqlquery, types.as_ref())?;
+        let ncols = columns.len() + join.columns.len();
+        let adapter = PgQueryAdapter::new(client, ncols, |client| {
+            client.query_raw(&stmt, values.iter().map(sqlval_for_pg_query))
+        })?;
+        Ok(Box::new(adapter))
+    }
+    fn aggregate(
+        &self,
+        table: &str,
+        agg: query::Aggregate,
+        expr: Option<BoolExpr>,
+    ) -> Result<SqlVal> {
+        let mut sqlquery = String::new();
+        helper::sql_select_aggregate(&agg, table, &mut sqlquery);
+        let mut values: Vec<SqlVal> = Vec::new();
+        if let Some(expr) = expr {
+            sqlquery.write_str(" WHERE ").unwrap();
+            sql_for_expr(
+                query::Expr::Condition(Box::new(expr)),
+                &mut values,
+                &mut PgPlaceholderSource::new(),
+                &mut sqlquery,
+            );
+        }
+
+        if cfg!(feature = "log") {
+            debug!("aggregate sql {}", sqlquery);
+        }
+
+        let col = Column::new(agg.column.unwrap_or("*"), agg.ty);
+        let val: Option<SqlVal> = self
             .cell()?
             .try_borrow_mut()?
-            .prepare_typed(&sqlquery, types.as_ref())?;
-        // todo avoid intermediate vec?
-        let rowvec: Vec<postgres::Row> = self
+            .query_raw(sqlquery.as_str(), values.iter().map(sqlval_for_pg_query))?
+            .map_err(Error::Postgres)
+            .map(|r| sql_val_from_postgres(&r, 0, &col))
+            .nth(0)?;
+        val.ok_or_else(|| Error::Internal("aggregate query returned no rows".to_string()))
+    }
+    fn exists(&self, table: &str, expr: Option<BoolExpr>) -> Result<bool> {
+        let mut sqlquery = String::new();
+        helper::sql_select_exists(table, &mut sqlquery);
+        let mut values: Vec<SqlVal> = Vec::new();
+        if let Some(expr) = expr {
+            sqlquery.write_str(" WHERE ").unwrap();
+            sql_for_expr(
+                query::Expr::Condition(Box::new(expr)),
+                &mut values,
+                &mut PgPlaceholderSource::new(),
+                &mut sqlquery,
+            );
+        }
+        sqlquery.write_str(")").unwrap();
+
+        if cfg!(feature = "log") {
+            debug!("exists sql {}", sqlquery);
+        }
+
+        let row = self
             .cell()?
             .try_borrow_mut()?
-            .query_raw(&stmt, values.iter().map(sqlval_for_pg_query))?
+            .query_raw(sqlquery.as_str(), values.iter().map(sqlval_for_pg_query))?
             .map_err(Error::Postgres)
-            .map(|r| {
-                check_columns(&r, columns)?;
-                Ok(r)
-            })
-            .collect()?;
-        Ok(Box::new(VecRows::new(rowvec)))
+            .nth(0)?
+            .ok_or_else(|| Error::Internal("exists query returned no rows".to_string()))?;
+        Ok(row.try_get(0)?)
     }
     fn insert_returning_pk(
         &self,
@@ -286,6 +548,37 @@ where
             .execute(sql.as_str(), params.as_slice())?;
         Ok(cnt as usize)
     }
+    fn update_where(
+        &self,
+        table: &str,
+        assignments: Vec<query::Assignment>,
+        expr: BoolExpr,
+    ) -> Result<usize> {
+        let mut sql = String::new();
+        let mut values: Vec<SqlVal> = Vec::new();
+        let mut pls = PgPlaceholderSource::new();
+        helper::sql_update_assignments(
+            table,
+            assignments,
+            sql_for_expr,
+            &mut values,
+            &mut pls,
+            &mut sql,
+        );
+        sql.push_str(" WHERE ");
+        sql_for_expr(
+            query::Expr::Condition(Box::new(expr)),
+            &mut values,
+            &mut pls,
+            &mut sql,
+        );
+        let params: Vec<&DynToSqlPg> = values.iter().map(|v| v as &DynToSqlPg).collect();
+        let cnt = self
+            .cell()?
+            .try_borrow_mut()?
+            .execute(sql.as_str(), params.as_slice())?;
+        Ok(cnt as usize)
+    }
     fn has_table(&self, table: &str) -> Result<bool> {
         // future improvement, should be schema-aware
         let stmt = self
@@ -374,6 +667,7 @@ impl<'a> postgres::types::ToSql for SqlValRef<'a> {
         use SqlValRef::*;
         match self {
             Bool(b) => b.to_sql_checked(requested_ty, out),
+            SmallInt(i) => i.to_sql_checked(requested_ty, out),
             Int(i) => i.to_sql_checked(requested_ty, out),
             BigInt(i) => i.to_sql_checked(requested_ty, out),
             Real(r) => r.to_sql_checked(requested_ty, out),
@@ -381,6 +675,15 @@ impl<'a> postgres::types::ToSql for SqlValRef<'a> {
             Blob(b) => b.to_sql_checked(requested_ty, out),
             #[cfg(feature = "datetime")]
             Timestamp(dt) => dt.to_sql_checked(requested_ty, out),
+            #[cfg(feature = "datetime")]
+            Date(d) => d.to_sql_checked(requested_ty, out),
+            #[cfg(feature = "datetime")]
+            Time(t) => t.to_sql_checked(requested_ty, out),
+            #[cfg(feature = "datetime")]
+            TimestampTz(dt) => dt.to_sql_checked(requested_ty, out),
+            #[cfg(feature = "decimal")]
+            Numeric(d) => d.to_sql_checked(requested_ty, out),
+            Json(v) => v.to_sql_checked(requested_ty, out),
             Null => Ok(postgres::types::IsNull::Yes),
             Custom(SqlValRefCustom::PgToSql { ty, tosql }) => {
                 check_type_match(ty, requested_ty)?;
@@ -424,6 +727,7 @@ impl<'a> postgres::types::FromSql<'a> for SqlValRef<'a> {
         use postgres::types::Type;
         match *ty {
             Type::BOOL => Ok(SqlValRef::Bool(bool::from_sql(ty, raw)?)),
+            Type::INT2 => Ok(SqlValRef::SmallInt(i16::from_sql(ty, raw)?)),
             Type::INT4 => Ok(SqlValRef::Int(i32::from_sql(ty, raw)?)),
             Type::INT8 => Ok(SqlValRef::BigInt(i64::from_sql(ty, raw)?)),
             Type::FLOAT8 => Ok(SqlValRef::Real(f64::from_sql(ty, raw)?)),
@@ -435,6 +739,15 @@ impl<'a> postgres::types::FromSql<'a> for SqlValRef<'a> {
             )?)),
             #[cfg(feature = "datetime")]
             Type::TIMESTAMP => Ok(SqlValRef::Timestamp(NaiveDateTime::from_sql(ty, raw)?)),
+            #[cfg(feature = "datetime")]
+            Type::DATE => Ok(SqlValRef::Date(NaiveDate::from_sql(ty, raw)?)),
+            #[cfg(feature = "datetime")]
+            Type::TIME => Ok(SqlValRef::Time(NaiveTime::from_sql(ty, raw)?)),
+            #[cfg(feature = "datetime")]
+            Type::TIMESTAMPTZ => Ok(SqlValRef::TimestampTz(DateTime::<Utc>::from_sql(ty, raw)?)),
+            #[cfg(feature = "decimal")]
+            Type::NUMERIC => Ok(SqlValRef::Numeric(Decimal::from_sql(ty, raw)?)),
+            Type::JSON | Type::JSONB => Ok(SqlValRef::Json(serde_json::Value::from_sql(ty, raw)?)),
             _ => Ok(SqlValRef::Custom(SqlValRefCustom::PgBytes {
                 ty: ty.clone(),
                 data: raw,
@@ -458,18 +771,174 @@ impl<'a> postgres::types::FromSql<'a> for SqlValRef<'a> {
     }
 }
 
-fn check_columns(row: &postgres::Row, cols: &[Column]) -> Result<()> {
-    if cols.len() != row.len() {
+fn check_columns(row: &postgres::Row, ncols: usize) -> Result<()> {
+    if ncols != row.len() {
         Err(Error::Internal(format!(
             "postgres returns columns {} doesn't match requested columns {}",
             row.len(),
-            cols.len()
+            ncols
         )))
     } else {
         Ok(())
     }
 }
 
+/// Streams the rows of a query from the server as they are
+/// requested rather than collecting them up front. The connection
+/// remains borrowed until the adapter is dropped.
+struct PgQueryAdapter<'c, C> {
+    // rows borrows the client, so it must be declared (and therefore
+    // dropped) before it
+    rows: postgres::RowIter<'c>,
+    _client: RefMut<'c, C>,
+    ncols: usize,
+    current: Option<postgres::Row>,
+}
+impl<'c, C> PgQueryAdapter<'c, C> {
+    fn new<F>(mut client: RefMut<'c, C>, ncols: usize, query: F) -> Result<Self>
+    where
+        F: FnOnce(&'c mut C) -> std::result::Result<postgres::RowIter<'c>, postgres::Error>,
+    {
+        let rows = unsafe {
+            //Soundness: the client lives in the RefCell rather than
+            //  in the RefMut, so it does not move along with the
+            //  RefMut. We hold the RefMut (without using it) for as
+            //  long as the rows borrowing the client exist, and the
+            //  rows are dropped first.
+            let client_ref: *mut C = &mut *client;
+            query(&mut *client_ref)?
+        };
+        Ok(PgQueryAdapter {
+            rows,
+            _client: client,
+            ncols,
+            current: None,
+        })
+    }
+}
+impl<'c, C> BackendRows for PgQueryAdapter<'c, C> {
+    fn next<'b>(&'b mut self) -> Result<Option<&'b (dyn BackendRow + 'b)>> {
+        self.current = self.rows.next()?;
+        if let Some(row) = &self.current {
+            check_columns(row, self.ncols)?;
+        }
+        Ok(self.current.as_ref().map(|row| row as &dyn BackendRow))
+    }
+    fn current<'b>(&'b self) -> Option<&'b (dyn BackendRow + 'b)> {
+        self.current.as_ref().map(|row| row as &dyn BackendRow)
+    }
+}
+
+/// The number of rows [PgPortalAdapter] fetches from the server at a
+/// time.
+const PORTAL_FETCH_ROWS: i32 = 256;
+
+/// Reads the rows of a query through a portal (a server-side cursor)
+/// within a transaction, fetching `PORTAL_FETCH_ROWS` rows at a time
+/// so that only one batch is held in memory. As the transaction only
+/// reads, it is rolled back when the adapter is dropped. The
+/// connection remains borrowed until then.
+struct PgPortalAdapter<'c, C> {
+    portal: postgres::Portal,
+    // transaction borrows the client, so it must be declared (and
+    // therefore dropped) before it
+    transaction: postgres::Transaction<'c>,
+    _client: RefMut<'c, C>,
+    batch: std::collections::VecDeque<postgres::Row>,
+    exhausted: bool,
+    ncols: usize,
+    current: Option<postgres::Row>,
+}
+impl<'c, C: GenericClient> PgPortalAdapter<'c, C> {
+    fn new<F>(mut client: RefMut<'c, C>, ncols: usize, bind: F) -> Result<Self>
+    where
+        F: FnOnce(
+            &mut postgres::Transaction<'c>,
+        ) -> std::result::Result<postgres::Portal, postgres::Error>,
+    {
+        let mut transaction = unsafe {
+            //Soundness: as for PgQueryAdapter, the client does not
+            //  move along with the RefMut, which we hold for as long
+            //  as the transaction borrowing the client exists.
+            let client_ref: *mut C = &mut *client;
+            (*client_ref).transaction()?
+        };
+        let portal = bind(&mut transaction)?;
+        Ok(PgPortalAdapter {
+            portal,
+            transaction,
+            _client: client,
+            batch: std::collections::VecDeque::new(),
+            exhausted: false,
+            ncols,
+            current: None,
+        })
+    }
+}
+impl<'c, C> BackendRows for PgPortalAdapter<'c, C> {
+    fn next<'b>(&'b mut self) -> Result<Option<&'b (dyn BackendRow + 'b)>> {
+        if self.batch.is_empty() && !self.exhausted {
+            let rows = self
+                .transaction
+                .query_portal(&self.portal, PORTAL_FETCH_ROWS)?;
+            self.exhausted = rows.len() < PORTAL_FETCH_ROWS as usize;
+            self.batch.extend(rows);
+        }
+        self.current = self.batch.pop_front();
+        if let Some(row) = &self.current {
+            check_columns(row, self.ncols)?;
+        }
+        Ok(self.current.as_ref().map(|row| row as &dyn BackendRow))
+    }
+    fn current<'b>(&'b self) -> Option<&'b (dyn BackendRow + 'b)> {
+        self.current.as_ref().map(|row| row as &dyn BackendRow)
+    }
+}
+
+struct PgPreparedStatement<'c, T> {
+    conn: &'c T,
+    stmt: postgres::Statement,
+    values: Vec<SqlVal>,
+    binds: Vec<usize>,
+    ncols: usize,
+}
+impl<T: PgConnectionLike> PreparedStatement for PgPreparedStatement<'_, T> {
+    fn query(&mut self, args: Vec<SqlVal>) -> Result<RawQueryResult<'_>> {
+        let params: Vec<SqlVal> = helper::merge_bind_args(&self.values, &self.binds, args)?
+            .into_iter()
+            .zip(self.stmt.params())
+            .map(|(val, ty)| coerce_int_for_pg(val, ty))
+            .collect();
+        let client = self.conn.cell()?.try_borrow_mut()?;
+        let stmt = &self.stmt;
+        let adapter = PgQueryAdapter::new(client, self.ncols, |client| {
+            client.query_raw(stmt, params.iter().map(sqlval_for_pg_query))
+        })?;
+        Ok(Box::new(adapter))
+    }
+}
+
+/// Converts integers to the width of the parameter type `ty`
+/// (inferred by the server), if possible, as the binary protocol
+/// requires an exact match.
+fn coerce_int_for_pg(val: SqlVal, ty: &postgres::types::Type) -> SqlVal {
+    use postgres::types::Type;
+    use std::convert::TryFrom;
+    let i: i64 = match val {
+        SqlVal::SmallInt(i) => i.into(),
+        SqlVal::Int(i) => i.into(),
+        SqlVal::BigInt(i) => i,
+        val => return val,
+    };
+    let coerced = match *ty {
+        Type::INT2 => i16::try_from(i).ok().map(SqlVal::SmallInt),
+        Type::INT4 => i32::try_from(i).ok().map(SqlVal::Int),
+        Type::INT8 => Some(SqlVal::BigInt(i)),
+        _ => None,
+    };
+    coerced.unwrap_or(val)
+}
+
 impl BackendRow for postgres::Row {
     fn get(&self, idx: usize, _ty: SqlType) -> Result<SqlValRef> {
         Ok(self.try_get(idx)?)
@@ -477,6 +946,30 @@ impl BackendRow for postgres::Row {
     fn len(&self) -> usize {
         postgres::Row::len(self)
     }
+    fn incompatible_column_type(&self, idx: usize, ty: &SqlType) -> Option<String> {
+        use postgres::types::Type;
+        let declared = self.columns().get(idx)?.type_();
+        let compatible = match ty {
+            SqlType::Json => *declared == Type::JSON || *declared == Type::JSONB,
+            ty => *declared == pgtype(ty),
+        };
+        if compatible {
+            None
+        } else {
+            Some(declared.to_string())
+        }
+    }
+}
+
+/// Writes the SQL for the SELECT query described by `query`, as used
+/// by `query_compound`.
+fn sql_for_select_query(
+    columns: &[Column],
+    query: query::SelectQuery,
+    values: &mut Vec<SqlVal>,
+    pls: &mut PgPlaceholderSource,
+) -> String {
+    helper::sql_for_select_query(columns, query, false, false, sql_for_expr, values, pls)
 }
 
 fn sql_for_expr<W>(
@@ -487,7 +980,64 @@ fn sql_for_expr<W>(
 ) where
     W: Write,
 {
-    helper::sql_for_expr(expr, sql_for_expr, values, pls, w)
+    match expr {
+        query::Expr::Condition(cond) => match *cond {
+            query::BoolExpr::ILike(col, ex) => {
+                write!(w, "{} ILIKE ", col).unwrap();
+                sql_for_expr(ex, values, pls, w);
+                write!(w, " ESCAPE '\\'").unwrap();
+            }
+            // ->> yields text, which does not compare with other
+            // types, so compare the JSON value with the other operand
+            // converted to JSON instead. NULL and placeholders (whose
+            // type is not known here) still use the text value.
+            query::BoolExpr::Compare(query::Expr::JsonGet(json, path), op, other)
+                if !matches!(
+                    other,
+                    query::Expr::Val(SqlVal::Null)
+                        | query::Expr::Placeholder
+                        | query::Expr::OutOfRange
+                ) =>
+            {
+                sql_json_get(*json, path, false, values, pls, w);
+                write!(w, " {} to_jsonb(", helper::sql_compare_op(op)).unwrap();
+                sql_for_expr(other, values, pls, w);
+                w.write_str(")").unwrap();
+            }
+            cond => helper::sql_for_expr(
+                query::Expr::Condition(Box::new(cond)),
+                sql_for_expr,
+                values,
+                pls,
+                w,
+            ),
+        },
+        query::Expr::JsonGet(json, path) => sql_json_get(*json, path, true, values, pls, w),
+        expr => helper::sql_for_expr(expr, sql_for_expr, values, pls, w),
+    }
+}
+
+/// Writes the SQL for the value at `path` within `json`. If `text`,
+/// the value is converted to text (`->>`), otherwise it is JSON (`->`).
+fn sql_json_get<W>(
+    json: query::Expr,
+    path: Vec<String>,
+    text: bool,
+    values: &mut Vec<SqlVal>,
+    pls: &mut PgPlaceholderSource,
+    w: &mut W,
+) where
+    W: Write,
+{
+    let last = path.len() - 1;
+    w.write_str("(").unwrap();
+    sql_for_expr(json, values, pls, w);
+    for (i, key) in path.into_iter().enumerate() {
+        let op = if text && i == last { "->>" } else { "->" };
+        values.push(SqlVal::Text(key));
+        write!(w, " {} {}", op, pls.next_placeholder()).unwrap();
+    }
+    w.write_str(")").unwrap();
 }
 
 fn sql_val_from_postgres<I>(row: &postgres::Row, idx: I, col: &Column) -> Result<SqlVal>
@@ -560,6 +1110,7 @@ fn col_sqltype(col: &AColumn) -> Result<Cow<str>> {
         TypeIdentifier::Ty(ty) => {
             if col.is_auto() {
                 match ty {
+                    SqlType::SmallInt => Ok(Cow::Borrowed("SMALLSERIAL")),
                     SqlType::Int => Ok(Cow::Borrowed("SERIAL")),
                     SqlType::BigInt => Ok(Cow::Borrowed("BIGSERIAL")),
                     _ => Err(Error::InvalidAuto(col.name().to_string())),
@@ -567,13 +1118,27 @@ fn col_sqltype(col: &AColumn) -> Result<Cow<str>> {
             } else {
                 Ok(match ty {
                     SqlType::Bool => Cow::Borrowed("BOOLEAN"),
+                    SqlType::SmallInt => Cow::Borrowed("SMALLINT"),
                     SqlType::Int => Cow::Borrowed("INTEGER"),
                     SqlType::BigInt => Cow::Borrowed("BIGINT"),
                     SqlType::Real => Cow::Borrowed("DOUBLE PRECISION"),
                     SqlType::Text => Cow::Borrowed("TEXT"),
                     #[cfg(feature = "datetime")]
                     SqlType::Timestamp => Cow::Borrowed("TIMESTAMP"),
+                    #[cfg(feature = "datetime")]
+                    SqlType::Date => Cow::Borrowed("DATE"),
+                    #[cfg(feature = "datetime")]
+                    SqlType::Time => Cow::Borrowed("TIME"),
+                    #[cfg(feature = "datetime")]
+                    SqlType::TimestampTz => Cow::Borrowed("TIMESTAMPTZ"),
+                    #[cfg(feature = "decimal")]
+                    SqlType::Numeric(None) => Cow::Borrowed("NUMERIC"),
+                    #[cfg(feature = "decimal")]
+                    SqlType::Numeric(Some((precision, scale))) => {
+                        Cow::Owned(format!("NUMERIC({},{})", precision, scale))
+                    }
                     SqlType::Blob => Cow::Borrowed("BYTEA"),
+                    SqlType::Json => Cow::Borrowed("JSONB"),
                     SqlType::Custom(c) => match c {
                         SqlTypeCustom::Pg(ref ty) => Cow::Owned(ty.name().to_string()),
                     },
@@ -634,6 +1199,16 @@ fn change_column(
         return Ok(String::new());
     }
     let old_table = table.unwrap();
+    // A change which the backend does not distinguish (such as
+    // between signed and unsigned integers) needs no rebuild.
+    if let Some(col) = new {
+        if define_column(col)? == define_column(old)? {
+            let mut new_table = old_table.clone();
+            new_table.replace_column(col.clone());
+            current.replace_table(new_table);
+            return Ok(String::new());
+        }
+    }
     let mut new_table = old_table.clone();
     new_table.name = tmp_table_name(&new_table.name);
     match new {
@@ -679,30 +1254,50 @@ pub fn sql_insert_or_replace_with_placeholders(
 }
 
 fn pgtype_for_val(val: &SqlVal) -> postgres::types::Type {
-    use postgres::types::Type;
     match val.sqltype() {
-        None => Type::UNKNOWN,
-        Some(SqlType::Bool) => postgres::types::Type::BOOL,
-        Some(SqlType::Int) => postgres::types::Type::INT4,
-        Some(SqlType::BigInt) => postgres::types::Type::INT8,
-        Some(SqlType::Real) => postgres::types::Type::FLOAT8,
-        Some(SqlType::Text) => postgres::types::Type::TEXT,
-        Some(SqlType::Blob) => postgres::types::Type::BYTEA,
+        None => postgres::types::Type::UNKNOWN,
+        Some(ty) => pgtype(&ty),
+    }
+}
+
+fn pgtype(ty: &SqlType) -> postgres::types::Type {
+    match ty {
+        SqlType::Bool => postgres::types::Type::BOOL,
+        SqlType::SmallInt => postgres::types::Type::INT2,
+        SqlType::Int => postgres::types::Type::INT4,
+        SqlType::BigInt => postgres::types::Type::INT8,
+        SqlType::Real => postgres::types::Type::FLOAT8,
+        SqlType::Text => postgres::types::Type::TEXT,
+        SqlType::Blob => postgres::types::Type::BYTEA,
+        SqlType::Json => postgres::types::Type::JSONB,
+        #[cfg(feature = "datetime")]
+        SqlType::Timestamp => postgres::types::Type::TIMESTAMP,
+        #[cfg(feature = "datetime")]
+        SqlType::Date => postgres::types::Type::DATE,
         #[cfg(feature = "datetime")]
-        Some(SqlType::Timestamp) => postgres::types::Type::TIMESTAMP,
-        Some(SqlType::Custom(inner)) => match inner {
+        SqlType::Time => postgres::types::Type::TIME,
+        #[cfg(feature = "datetime")]
+        SqlType::TimestampTz => postgres::types::Type::TIMESTAMPTZ,
+        #[cfg(feature = "decimal")]
+        SqlType::Numeric(_) => postgres::types::Type::NUMERIC,
+        SqlType::Custom(inner) => match inner {
             #[cfg(feature = "pg")]
-            SqlTypeCustom::Pg(ty, ..) => ty,
+            SqlTypeCustom::Pg(ty, ..) => ty.clone(),
         },
     }
 }
 
 struct PgPlaceholderSource {
-    n: i8,
+    n: usize,
+    // zero-based positions of the placeholders for values bound later
+    binds: Vec<usize>,
 }
 impl PgPlaceholderSource {
     fn new() -> Self {
-        PgPlaceholderSource { n: 1 }
+        PgPlaceholderSource {
+            n: 1,
+            binds: Vec::new(),
+        }
     }
 }
 impl helper::PlaceholderSource for PgPlaceholderSource {
@@ -711,4 +1306,8 @@ impl helper::PlaceholderSource for PgPlaceholderSource {
         self.n += 1;
         ret
     }
+    fn next_bind_placeholder(&mut self) -> Cow<'_, str> {
+        self.binds.push(self.n - 1);
+        self.next_placeholder()
+    }
 }
@@ -8,15 +8,28 @@ use crate::query;
 use crate::query::Order;
 use crate::{Result, SqlType, SqlVal, SqlValRef};
 #[cfg(feature = "datetime")]
-use chrono::naive::NaiveDateTime;
+use chrono::{naive::NaiveDateTime, DateTime, NaiveDate, NaiveTime, SecondsFormat, Utc};
 use fallible_streaming_iterator::FallibleStreamingIterator;
 use pin_project::pin_project;
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
 use std::borrow::Cow;
 use std::fmt::Write;
 use std::pin::Pin;
 
 #[cfg(feature = "datetime")]
 const SQLITE_DT_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+#[cfg(feature = "datetime")]
+const SQLITE_DATE_FORMAT: &str = "%Y-%m-%d";
+// Fixed precision so that times sort correctly as text
+#[cfg(feature = "datetime")]
+const SQLITE_TIME_FORMAT: &str = "%H:%M:%S%.9f";
+// Named as in SQLite's decimal extension, which provides the same
+// collation and aggregate to other clients of the database.
+#[cfg(feature = "decimal")]
+const DECIMAL_COLLATION: &str = "decimal";
+#[cfg(feature = "decimal")]
+const DECIMAL_SUM: &str = "decimal_sum";
 
 /// The name of the sqlite backend.
 pub const BACKEND_NAME: &str = "sqlite";
@@ -49,6 +62,9 @@ impl Backend for SQLiteBackend {
                 sql
             })
             .collect::<Result<Vec<String>>>()?
+            .into_iter()
+            .filter(|sql| !sql.is_empty())
+            .collect::<Vec<String>>()
             .join("\n"))
     }
 
@@ -57,6 +73,21 @@ impl Backend for SQLiteBackend {
             conn: Box::new(self.connect(path)?),
         })
     }
+
+    fn sql_for_select(
+        &self,
+        columns: &[Column],
+        query: query::SelectQuery,
+    ) -> (String, Vec<SqlVal>) {
+        let mut values: Vec<SqlVal> = Vec::new();
+        let sql = sql_for_select_query(
+            columns,
+            query,
+            &mut values,
+            &mut SQLitePlaceholderSource::new(),
+        );
+        (sql, values)
+    }
 }
 
 /// SQLite database connection.
@@ -65,9 +96,10 @@ pub struct SQLiteConnection {
 }
 impl SQLiteConnection {
     fn open(path: impl AsRef<Path>) -> Result<Self> {
-        rusqlite::Connection::open(path)
-            .map(|conn| SQLiteConnection { conn })
-            .map_err(|e| e.into())
+        let conn = rusqlite::Connection::open(path)?;
+        #[cfg(feature = "decimal")]
+        register_decimal_functions(&conn)?;
+        Ok(SQLiteConnection { conn })
     }
 
     // For use with connection_method_wrapper macro
@@ -108,26 +140,164 @@ impl ConnectionMethods for rusqlite::Connection {
         &'c self,
         table: &str,
         columns: &'b [Column],
+        distinct: bool,
         expr: Option<BoolExpr>,
+        group: Option<query::GroupBy>,
         limit: Option<i32>,
         offset: Option<i32>,
         order: Option<&[Order]>,
     ) -> Result<RawQueryResult<'a>> {
+        let mut values: Vec<SqlVal> = Vec::new();
+        let mut pls = SQLitePlaceholderSource::new();
+        let sqlquery = helper::sql_for_query(
+            table,
+            columns,
+            distinct,
+            expr,
+            group,
+            limit,
+            offset,
+            order,
+            true,
+            true,
+            sql_for_expr,
+            &mut values,
+            &mut pls,
+        );
+        debug!("query sql {}", sqlquery);
+
+        let stmt = self.prepare(&sqlquery)?;
+        let adapter = QueryAdapter::new(stmt, rusqlite::params_from_iter(values))?;
+        Ok(Box::new(adapter))
+    }
+    fn query_compound<'a, 'b, 'c: 'a>(
+        &'c self,
+        columns: &'b [Column],
+        query: query::SelectQuery,
+    ) -> Result<RawQueryResult<'a>> {
+        let mut values: Vec<SqlVal> = Vec::new();
+        let sqlquery = sql_for_select_query(
+            columns,
+            query,
+            &mut values,
+            &mut SQLitePlaceholderSource::new(),
+        );
+        debug!("query sql {}", sqlquery);
+
+        let stmt = self.prepare(&sqlquery)?;
+        let adapter = QueryAdapter::new(stmt, rusqlite::params_from_iter(values))?;
+        Ok(Box::new(adapter))
+    }
+    fn explain(&self, columns: &[Column], query: query::SelectQuery) -> Result<String> {
+        let mut values: Vec<SqlVal> = Vec::new();
+        let sqlquery = format!(
+            "EXPLAIN QUERY PLAN {}",
+            sql_for_select_query(
+                columns,
+                query,
+                &mut values,
+                &mut SQLitePlaceholderSource::new()
+            )
+        );
+        debug!("explain sql {}", sqlquery);
+
+        let mut stmt = self.prepare(&sqlquery)?;
+        // The plan's description is in the fourth column
+        let lines = stmt
+            .query_map(rusqlite::params_from_iter(values), |row| {
+                row.get::<_, String>(3)
+            })?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(lines.join("\n"))
+    }
+    fn query_sql<'c>(&'c self, sql: &str, params: &[SqlVal]) -> Result<RawQueryResult<'c>> {
         let mut sqlquery = String::new();
-        helper::sql_select(columns, table, &mut sqlquery);
+        helper::sql_with_placeholders(sql, &mut SQLitePlaceholderSource::new(), &mut sqlquery);
+        debug!("query sql {}", sqlquery);
+
+        let stmt = self.prepare(&sqlquery)?;
+        let adapter = QueryAdapter::new(stmt, rusqlite::params_from_iter(params))?;
+        Ok(Box::new(adapter))
+    }
+    fn prepare_query<'c>(
+        &'c self,
+        table: &str,
+        columns: &[Column],
+        distinct: bool,
+        expr: Option<BoolExpr>,
+        group: Option<query::GroupBy>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        order: Option<&[Order]>,
+    ) -> Result<Box<dyn PreparedStatement + 'c>> {
         let mut values: Vec<SqlVal> = Vec::new();
+        let mut pls = SQLitePlaceholderSource::new();
+        let sqlquery = helper::sql_for_query(
+            table,
+            columns,
+            distinct,
+            expr,
+            group,
+            limit,
+            offset,
+            order,
+            true,
+            true,
+            sql_for_expr,
+            &mut values,
+            &mut pls,
+        );
+        debug!("prepare query sql {}", sqlquery);
+
+        Ok(Box::new(SqlitePreparedStatement {
+            stmt: self.prepare_cached(&sqlquery)?,
+            values,
+            binds: pls.binds,
+        }))
+    }
+    #[allow(clippy::too_many_arguments)]
+    fn query_join<'a, 'b, 'c: 'a>(
+        &'c self,
+        table: &str,
+        columns: &'b [Column],
+        join: query::SelectJoin,
+        expr: Option<BoolExpr>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        order: Option<&[query::Order]>,
+    ) -> Result<RawQueryResult<'a>> {
+        let mut sqlquery = String::new();
+        helper::sql_select_join(columns, table, &join, &mut sqlquery);
+        let mut values: Vec<SqlVal> = Vec::new();
+        let mut pls = SQLitePlaceholderSource::new();
         if let Some(expr) = expr {
-            sqlquery.write_str(" WHERE ").unwrap();
+            // The columns in expr are not qualified with the table
+            // name, so filter in a subquery to avoid ambiguity with
+            // the columns of the joined table.
+            write!(&mut sqlquery, "(SELECT * FROM {} WHERE ", table).unwrap();
             sql_for_expr(
                 query::Expr::Condition(Box::new(expr)),
                 &mut values,
-                &mut SQLitePlaceholderSource::new(),
+                &mut pls,
                 &mut sqlquery,
             );
+            write!(&mut sqlquery, ") AS {}", table).unwrap();
+        } else {
+            sqlquery.write_str(table).unwrap();
         }
+        sqlquery.write_str(" ").unwrap();
+        helper::sql_select_join_on(join.join, &mut sqlquery);
 
         if let Some(order) = order {
-            helper::sql_order(order, &mut sqlquery)
+            helper::sql_order_qualified(
+                table,
+                order,
+                true,
+                sql_for_expr,
+                &mut values,
+                &mut pls,
+                &mut sqlquery,
+            )
         }
 
         if let Some(limit) = limit {
@@ -150,6 +320,54 @@ impl ConnectionMethods for rusqlite::Connection {
         let adapter = QueryAdapter::new(stmt, rusqlite::params_from_iter(values))?;
         Ok(Box::new(adapter))
     }
+    fn aggregate(
+        &self,
+        table: &str,
+        agg: query::Aggregate,
+        expr: Option<BoolExpr>,
+    ) -> Result<SqlVal> {
+        let mut sqlquery = String::new();
+        sql_select_aggregate(&agg, table, &mut sqlquery);
+        let mut values: Vec<SqlVal> = Vec::new();
+        if let Some(expr) = expr {
+            sqlquery.write_str(" WHERE ").unwrap();
+            sql_for_expr(
+                query::Expr::Condition(Box::new(expr)),
+                &mut values,
+                &mut SQLitePlaceholderSource::new(),
+                &mut sqlquery,
+            );
+        }
+
+        debug!("aggregate sql {}", sqlquery);
+
+        self.query_row_and_then(&sqlquery, rusqlite::params_from_iter(values), |row| {
+            sql_valref_from_rusqlite(row.get_ref_unwrap(0), &agg.ty).map(|v| v.into())
+        })
+    }
+    fn exists(&self, table: &str, expr: Option<BoolExpr>) -> Result<bool> {
+        let mut sqlquery = String::new();
+        helper::sql_select_exists(table, &mut sqlquery);
+        let mut values: Vec<SqlVal> = Vec::new();
+        if let Some(expr) = expr {
+            sqlquery.write_str(" WHERE ").unwrap();
+            sql_for_expr(
+                query::Expr::Condition(Box::new(expr)),
+                &mut values,
+                &mut SQLitePlaceholderSource::new(),
+                &mut sqlquery,
+            );
+        }
+        sqlquery.write_str(")").unwrap();
+
+        debug!("exists sql {}", sqlquery);
+
+        Ok(
+            self.query_row(&sqlquery, rusqlite::params_from_iter(values), |row| {
+                row.get(0)
+            })?,
+        )
+    }
     fn insert_returning_pk(
         &self,
         table: &str,
@@ -241,6 +459,36 @@ impl ConnectionMethods for rusqlite::Connection {
         let cnt = self.execute(&sql, rusqlite::params_from_iter(values))?;
         Ok(cnt)
     }
+    fn update_where(
+        &self,
+        table: &str,
+        assignments: Vec<query::Assignment>,
+        expr: BoolExpr,
+    ) -> Result<usize> {
+        let mut sql = String::new();
+        let mut values: Vec<SqlVal> = Vec::new();
+        let mut pls = SQLitePlaceholderSource::new();
+        helper::sql_update_assignments(
+            table,
+            assignments,
+            sql_for_expr,
+            &mut values,
+            &mut pls,
+            &mut sql,
+        );
+        sql.push_str(" WHERE ");
+        sql_for_expr(
+            query::Expr::Condition(Box::new(expr)),
+            &mut values,
+            &mut pls,
+            &mut sql,
+        );
+        if cfg!(feature = "log") {
+            debug!("update sql {}", sql);
+        }
+        let cnt = self.execute(&sql, rusqlite::params_from_iter(values))?;
+        Ok(cnt)
+    }
     fn has_table(&self, table: &str) -> Result<bool> {
         let mut stmt =
             self.prepare("SELECT name FROM sqlite_master WHERE type='table' AND name=?;")?;
@@ -309,6 +557,7 @@ fn sqlvalref_to_sqlite<'a, 'b>(valref: &'b SqlValRef<'a>) -> rusqlite::types::To
     use SqlValRef::*;
     match valref {
         Bool(b) => Owned(Value::Integer(*b as i64)),
+        SmallInt(i) => Owned(Value::Integer(*i as i64)),
         Int(i) => Owned(Value::Integer(*i as i64)),
         BigInt(i) => Owned(Value::Integer(*i)),
         Real(r) => Owned(Value::Real(*r)),
@@ -319,11 +568,27 @@ fn sqlvalref_to_sqlite<'a, 'b>(valref: &'b SqlValRef<'a>) -> rusqlite::types::To
             let f = dt.format(SQLITE_DT_FORMAT);
             Owned(Value::Text(f.to_string()))
         }
+        #[cfg(feature = "datetime")]
+        Date(d) => Owned(Value::Text(d.format(SQLITE_DATE_FORMAT).to_string())),
+        #[cfg(feature = "datetime")]
+        Time(t) => Owned(Value::Text(t.format(SQLITE_TIME_FORMAT).to_string())),
+        #[cfg(feature = "datetime")]
+        TimestampTz(dt) => Owned(Value::Text(sqlite_timestamptz(dt))),
+        #[cfg(feature = "decimal")]
+        Numeric(d) => Owned(Value::Text(d.normalize().to_string())),
+        Json(v) => Owned(Value::Text(v.to_string())),
         Null => Owned(Value::Null),
         Custom(_) => panic!("Custom types not supported in sqlite"),
     }
 }
 
+/// RFC 3339 in UTC with fixed precision, so that timestamps sort
+/// correctly as text.
+#[cfg(feature = "datetime")]
+fn sqlite_timestamptz(dt: &DateTime<Utc>) -> String {
+    dt.to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
 #[pin_project]
 struct QueryAdapterInner<'a> {
     stmt: rusqlite::Statement<'a>,
@@ -386,6 +651,29 @@ impl<'a> BackendRows for QueryAdapter<'a> {
     }
 }
 
+struct SqlitePreparedStatement<'c> {
+    stmt: rusqlite::CachedStatement<'c>,
+    values: Vec<SqlVal>,
+    binds: Vec<usize>,
+}
+impl PreparedStatement for SqlitePreparedStatement<'_> {
+    fn query(&mut self, args: Vec<SqlVal>) -> Result<RawQueryResult<'_>> {
+        let params = helper::merge_bind_args(&self.values, &self.binds, args)?;
+        Ok(Box::new(
+            self.stmt.query(rusqlite::params_from_iter(params))?,
+        ))
+    }
+}
+
+impl BackendRows for rusqlite::Rows<'_> {
+    fn next<'b>(&'b mut self) -> Result<Option<&'b (dyn BackendRow + 'b)>> {
+        Ok(rusqlite::Rows::next(self)?.map(|row| row as &dyn BackendRow))
+    }
+    fn current<'b>(&'b self) -> Option<&'b (dyn BackendRow + 'b)> {
+        self.get().map(|row| row as &dyn BackendRow)
+    }
+}
+
 impl BackendRow for rusqlite::Row<'_> {
     fn get(&self, idx: usize, ty: SqlType) -> Result<SqlValRef> {
         sql_valref_from_rusqlite(self.get_ref(idx)?, &ty)
@@ -393,6 +681,30 @@ impl BackendRow for rusqlite::Row<'_> {
     fn len(&self) -> usize {
         self.as_ref().column_count()
     }
+    fn incompatible_column_type(&self, idx: usize, ty: &SqlType) -> Option<String> {
+        if let SqlType::Custom(_) = ty {
+            return None;
+        }
+        // Only columns read directly from a table have a declared type
+        let columns = self.as_ref().columns();
+        let declared = columns.get(idx)?.decl_type()?;
+        if declared.eq_ignore_ascii_case(sqltype(ty)) {
+            None
+        } else {
+            Some(declared.to_string())
+        }
+    }
+}
+
+/// Writes the SQL for the SELECT query described by `query`, as used
+/// by `query_compound`.
+fn sql_for_select_query(
+    columns: &[Column],
+    query: query::SelectQuery,
+    values: &mut Vec<SqlVal>,
+    pls: &mut SQLitePlaceholderSource,
+) -> String {
+    helper::sql_for_select_query(columns, query, true, true, sql_for_expr, values, pls)
 }
 
 fn sql_for_expr<W>(
@@ -403,7 +715,94 @@ fn sql_for_expr<W>(
 ) where
     W: Write,
 {
-    helper::sql_for_expr(expr, sql_for_expr, values, pls, w)
+    match expr {
+        query::Expr::Condition(cond) => match *cond {
+            // SQLite's LIKE ignores case, so use GLOB, which does not.
+            query::BoolExpr::LikeEscape(col, query::Expr::Val(SqlVal::Text(pattern))) => {
+                write!(w, "{} GLOB ", col).unwrap();
+                sql_for_expr(
+                    query::Expr::Val(SqlVal::Text(glob_for_like(&pattern))),
+                    values,
+                    pls,
+                    w,
+                );
+            }
+            // A pattern which is not known until the query is run
+            // (such as a placeholder) is converted to GLOB in SQL.
+            query::BoolExpr::LikeEscape(col, ex) => {
+                write!(w, "{} GLOB ", col).unwrap();
+                for _ in GLOB_FOR_LIKE_REPLACEMENTS {
+                    w.write_str("replace(").unwrap();
+                }
+                sql_for_expr(ex, values, pls, w);
+                for (from, to) in GLOB_FOR_LIKE_REPLACEMENTS {
+                    write!(w, ", {}, {})", from, to).unwrap();
+                }
+            }
+            cond => helper::sql_for_expr(
+                query::Expr::Condition(Box::new(cond)),
+                sql_for_expr,
+                values,
+                pls,
+                w,
+            ),
+        },
+        expr => helper::sql_for_expr(expr, sql_for_expr, values, pls, w),
+    }
+}
+
+/// The replacements, applied in order, which convert a LIKE pattern
+/// to GLOB in SQL, as [glob_for_like] does in Rust. The GLOB
+/// metacharacters are first bracketed, escaped LIKE characters are
+/// then set aside as control characters while the wildcards are
+/// converted, and finally restored as literals.
+const GLOB_FOR_LIKE_REPLACEMENTS: [(&str, &str); 11] = [
+    ("'['", "'[[]'"),
+    ("'*'", "'[*]'"),
+    ("'?'", "'[?]'"),
+    ("'\\\\'", "char(1)"),
+    ("'\\%'", "char(2)"),
+    ("'\\_'", "char(3)"),
+    ("'%'", "'*'"),
+    ("'_'", "'?'"),
+    ("char(1)", "'\\'"),
+    ("char(2)", "'%'"),
+    ("char(3)", "'_'"),
+];
+
+/// Converts a LIKE pattern with `\` as its escape character to the
+/// equivalent GLOB pattern. GLOB's wildcards `*` and `?` correspond
+/// to `%` and `_`, and a character is matched literally by enclosing
+/// it in brackets.
+fn glob_for_like(pattern: &str) -> String {
+    let mut glob = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        let literal = match c {
+            '%' => {
+                glob.push('*');
+                continue;
+            }
+            '_' => {
+                glob.push('?');
+                continue;
+            }
+            '\\' => match chars.next() {
+                Some(escaped) => escaped,
+                None => c,
+            },
+            c => c,
+        };
+        match literal {
+            '*' | '?' | '[' => {
+                glob.push('[');
+                glob.push(literal);
+                glob.push(']');
+            }
+            _ => glob.push(literal),
+        }
+    }
+    glob
 }
 
 fn sql_val_from_rusqlite(val: rusqlite::types::ValueRef, col: &Column) -> Result<SqlVal> {
@@ -419,7 +818,8 @@ fn sql_valref_from_rusqlite<'a>(
     }
     Ok(match ty {
         SqlType::Bool => SqlValRef::Bool(val.as_i64()? != 0),
-        SqlType::Int => SqlValRef::Int(val.as_i64()? as i32),
+        SqlType::SmallInt => SqlValRef::SmallInt(int_from_rusqlite(val)?),
+        SqlType::Int => SqlValRef::Int(int_from_rusqlite(val)?),
         SqlType::BigInt => SqlValRef::BigInt(val.as_i64()?),
         SqlType::Real => SqlValRef::Real(val.as_f64()?),
         SqlType::Text => SqlValRef::Text(val.as_str()?),
@@ -428,13 +828,134 @@ fn sql_valref_from_rusqlite<'a>(
             val.as_str()?,
             SQLITE_DT_FORMAT,
         )?),
+        #[cfg(feature = "datetime")]
+        SqlType::Date => SqlValRef::Date(NaiveDate::parse_from_str(
+            val.as_str()?,
+            SQLITE_DATE_FORMAT,
+        )?),
+        #[cfg(feature = "datetime")]
+        SqlType::Time => SqlValRef::Time(NaiveTime::parse_from_str(
+            val.as_str()?,
+            SQLITE_TIME_FORMAT,
+        )?),
+        #[cfg(feature = "datetime")]
+        SqlType::TimestampTz => {
+            SqlValRef::TimestampTz(DateTime::parse_from_rfc3339(val.as_str()?)?.with_timezone(&Utc))
+        }
+        #[cfg(feature = "decimal")]
+        SqlType::Numeric(_) => SqlValRef::Numeric(decimal_from_rusqlite(val)?),
         SqlType::Blob => SqlValRef::Blob(val.as_blob()?),
+        SqlType::Json => SqlValRef::Json(serde_json::from_str(val.as_str()?)?),
         SqlType::Custom(v) => {
             return Err(Error::IncompatibleCustomT(v.deref().clone(), BACKEND_NAME))
         }
     })
 }
 
+/// SQLite stores every integer as 64 bits, so narrower integer
+/// columns may hold values which do not fit their SqlType.
+fn int_from_rusqlite<T: std::convert::TryFrom<i64>>(val: rusqlite::types::ValueRef) -> Result<T> {
+    T::try_from(val.as_i64()?).map_err(|_| Error::OutOfRange)
+}
+
+/// Decimals are stored as text, but an integer may also be read
+/// exactly.
+#[cfg(feature = "decimal")]
+fn decimal_from_rusqlite(val: rusqlite::types::ValueRef) -> Result<Decimal> {
+    use rusqlite::types::ValueRef;
+    use std::str::FromStr;
+    let decimal = match val {
+        ValueRef::Text(_) => Decimal::from_str(val.as_str()?).ok(),
+        ValueRef::Integer(i) => Some(Decimal::from(i)),
+        _ => None,
+    };
+    decimal.ok_or_else(|| {
+        Error::CannotConvertSqlVal(
+            SqlType::Numeric(None),
+            sql_valref_from_rusqlite(val, &SqlType::Text)
+                .map(SqlVal::from)
+                .unwrap_or(SqlVal::Null),
+        )
+    })
+}
+
+/// Decimals are stored as canonical text, which SQLite would compare
+/// as text and sum as reals. Register a collation for decimal columns
+/// and an aggregate which compare and sum them exactly instead.
+#[cfg(feature = "decimal")]
+fn register_decimal_functions(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    use rusqlite::functions::FunctionFlags;
+    conn.create_collation(DECIMAL_COLLATION, decimal_collation)?;
+    conn.create_aggregate_function(
+        DECIMAL_SUM,
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        DecimalSum,
+    )
+}
+
+#[cfg(feature = "decimal")]
+fn decimal_collation(a: &str, b: &str) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    use std::str::FromStr;
+    match (Decimal::from_str(a), Decimal::from_str(b)) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        // Text which is not a decimal sorts after any which is
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+/// The `decimal_sum` aggregate. Like SUM, it ignores nulls and is
+/// null if there are no other values.
+#[cfg(feature = "decimal")]
+struct DecimalSum;
+#[cfg(feature = "decimal")]
+impl rusqlite::functions::Aggregate<Option<Decimal>, Option<String>> for DecimalSum {
+    fn init(&self, _: &mut rusqlite::functions::Context<'_>) -> rusqlite::Result<Option<Decimal>> {
+        Ok(None)
+    }
+    fn step(
+        &self,
+        ctx: &mut rusqlite::functions::Context<'_>,
+        sum: &mut Option<Decimal>,
+    ) -> rusqlite::Result<()> {
+        let val = ctx.get_raw(0);
+        if val == rusqlite::types::ValueRef::Null {
+            return Ok(());
+        }
+        let val = decimal_from_rusqlite(val)
+            .map_err(|e| rusqlite::Error::UserFunctionError(Box::new(e)))?;
+        let total = sum
+            .unwrap_or_default()
+            .checked_add(val)
+            .ok_or_else(|| rusqlite::Error::UserFunctionError(Box::new(Error::OutOfRange)))?;
+        *sum = Some(total);
+        Ok(())
+    }
+    fn finalize(
+        &self,
+        _: &mut rusqlite::functions::Context<'_>,
+        sum: Option<Option<Decimal>>,
+    ) -> rusqlite::Result<Option<String>> {
+        Ok(sum.flatten().map(|d| d.normalize().to_string()))
+    }
+}
+
+/// Like [helper::sql_select_aggregate], but summing decimals with
+/// `decimal_sum`.
+fn sql_select_aggregate(agg: &query::Aggregate, table: &str, w: &mut impl Write) {
+    #[cfg(feature = "decimal")]
+    if let (query::AggregateFunction::Sum, SqlType::Numeric(_), Some(column)) =
+        (agg.function, &agg.ty, agg.column)
+    {
+        write!(w, "SELECT {}({}) FROM {}", DECIMAL_SUM, column, table).unwrap();
+        return;
+    }
+    helper::sql_select_aggregate(agg, table, w)
+}
+
 fn sql_for_op(current: &mut ADB, op: &Operation) -> Result<String> {
     match op {
         Operation::AddTable(table) => Ok(create_table(table, false)),
@@ -473,6 +994,10 @@ fn define_column(col: &AColumn) -> String {
     if col.unique() {
         constraints.push("UNIQUE".to_string());
     }
+    #[cfg(feature = "decimal")]
+    if let Ok(TypeIdentifier::Ty(SqlType::Numeric(_))) = col.typeid() {
+        constraints.push(format!("COLLATE {}", DECIMAL_COLLATION));
+    }
     format!(
         "{} {} {}",
         &col.name(),
@@ -494,13 +1019,23 @@ fn col_sqltype(col: &AColumn) -> Cow<str> {
 fn sqltype(ty: &SqlType) -> &'static str {
     match ty {
         SqlType::Bool => "INTEGER",
+        SqlType::SmallInt => "INTEGER",
         SqlType::Int => "INTEGER",
         SqlType::BigInt => "INTEGER",
         SqlType::Real => "REAL",
         SqlType::Text => "TEXT",
         #[cfg(feature = "datetime")]
         SqlType::Timestamp => "TEXT",
+        #[cfg(feature = "datetime")]
+        SqlType::Date => "TEXT",
+        #[cfg(feature = "datetime")]
+        SqlType::Time => "TEXT",
+        #[cfg(feature = "datetime")]
+        SqlType::TimestampTz => "TEXT",
+        #[cfg(feature = "decimal")]
+        SqlType::Numeric(_) => "TEXT",
         SqlType::Blob => "BLOB",
+        SqlType::Json => "TEXT",
         SqlType::Custom(_) => panic!("Custom types not supported by sqlite backend"),
     }
 }
@@ -570,6 +1105,16 @@ fn change_column(
         return "".to_string();
     }
     let old_table = table.unwrap();
+    // A change which the backend does not distinguish (such as the
+    // width of an integer) needs no rebuild.
+    if let Some(col) = new {
+        if define_column(col) == define_column(old) {
+            let mut new_table = old_table.clone();
+            new_table.replace_column(col.clone());
+            current.replace_table(new_table);
+            return String::new();
+        }
+    }
     let mut new_table = old_table.clone();
     new_table.name = tmp_table_name(&new_table.name);
     match new {
@@ -600,15 +1145,27 @@ pub fn sql_insert_or_update(table: &str, columns: &[Column], w: &mut impl Write)
     write!(w, ")").unwrap();
 }
 
-struct SQLitePlaceholderSource {}
+struct SQLitePlaceholderSource {
+    n: usize,
+    // positions of the placeholders for values bound later
+    binds: Vec<usize>,
+}
 impl SQLitePlaceholderSource {
     fn new() -> Self {
-        SQLitePlaceholderSource {}
+        SQLitePlaceholderSource {
+            n: 0,
+            binds: Vec::new(),
+        }
     }
 }
 impl helper::PlaceholderSource for SQLitePlaceholderSource {
     fn next_placeholder(&mut self) -> Cow<str> {
+        self.n += 1;
         // sqlite placeholder is always a question mark.
         Cow::Borrowed("?")
     }
+    fn next_bind_placeholder(&mut self) -> Cow<'_, str> {
+        self.binds.push(self.n);
+        self.next_placeholder()
+    }
 }
@@ -1,8 +1,9 @@
-use crate::db::ConnectionMethods;
+use crate::db::{collect_rows, ConnectionMethods};
 use crate::*;
 use once_cell::unsync::OnceCell;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Formatter};
 
 /// Used to implement a relationship between models.
@@ -66,6 +67,63 @@ impl<T: DataObject> ForeignKey<T> {
             .map(|v| v.as_ref())
     }
 
+    /// Loads the values referred to by all of `fkeys` which are not
+    /// already loaded, using a single query. Used by
+    /// [Query::prefetch](crate::query::Query::prefetch).
+    pub fn prefetch<'a>(
+        conn: &impl ConnectionMethods,
+        fkeys: impl IntoIterator<Item = &'a Self>,
+    ) -> Result<()>
+    where
+        T: 'a,
+    {
+        let fkeys: Vec<&Self> = fkeys
+            .into_iter()
+            .filter(|fkey| fkey.val.get().is_none())
+            .collect();
+        let mut pks: Vec<SqlVal> = Vec::new();
+        let mut seen: HashSet<SqlValKey> = HashSet::new();
+        for fkey in &fkeys {
+            let pk = fkey.ensure_valpk();
+            if seen.insert(SqlValKey(pk.clone())) {
+                pks.push(pk.clone());
+            }
+        }
+        if pks.is_empty() {
+            return Ok(());
+        }
+        let pkidx = T::COLUMNS
+            .iter()
+            .position(|col| col.name() == T::PKCOL)
+            .ok_or_else(|| Error::Internal(format!("No column for pk {}", T::PKCOL)))?;
+
+        // Each value may be referred to by several foreign keys, so
+        // read the rows into memory and construct a value per key.
+        let rows: HashMap<SqlValKey, Vec<SqlVal>> = collect_rows(
+            conn.query(
+                T::TABLE,
+                T::COLUMNS,
+                false,
+                Some(query::BoolExpr::In(T::PKCOL, pks)),
+                None,
+                None,
+                None,
+                None,
+            )?,
+            T::COLUMNS,
+        )?
+        .into_iter()
+        .map(|row| (SqlValKey(row[pkidx].clone()), row))
+        .collect();
+        for fkey in fkeys {
+            let pk = fkey.ensure_valpk();
+            if let Some(row) = rows.get(&SqlValKey(pk.clone())) {
+                fkey.val.set(Box::new(T::from_row(row)?)).ok();
+            }
+        }
+        Ok(())
+    }
+
     fn new_raw() -> Self {
         ForeignKey {
             val: OnceCell::new(),
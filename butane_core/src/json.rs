@@ -0,0 +1,86 @@
+//! Storage of serializable values as JSON.
+
+use crate::{
+    Error::CannotConvertSqlVal, FieldType, FromSql, Result, SqlType, SqlVal, SqlValRef, ToSql,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::ops::{Deref, DerefMut};
+
+/// Wrapper for a value stored in the database as JSON. This is a
+/// JSONB column on Postgres and a TEXT column on SQLite.
+///
+/// Within [`filter`](crate::query::BoolExpr), a value within the JSON
+/// may be compared with `field.get("key")`, e.g.
+/// `filter!(Foo, meta.get("color") == "red")`. Calls of `get` may be
+/// chained to reach nested objects. Numbers compare as numbers and
+/// strings as strings.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Json<T>(pub T);
+
+impl<T> Json<T> {
+    pub fn new(val: T) -> Self {
+        Json(val)
+    }
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Json<T> {
+    fn from(val: T) -> Self {
+        Json(val)
+    }
+}
+
+impl<T> Deref for Json<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Json<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> ToSql for Json<T>
+where
+    T: Serialize,
+{
+    fn to_sql(&self) -> SqlVal {
+        self.to_sql_ref().into()
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        self.try_to_sql_ref()
+            .expect("value cannot be serialized as JSON")
+    }
+    // Serialization only fails for values which cannot be represented
+    // as JSON at all, such as maps with non-string keys.
+    fn try_to_sql_ref(&self) -> Result<SqlValRef<'_>> {
+        Ok(SqlValRef::Json(serde_json::to_value(&self.0)?))
+    }
+}
+
+impl<T> FromSql for Json<T>
+where
+    T: DeserializeOwned,
+{
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        match valref {
+            SqlValRef::Json(val) => Ok(Json(serde_json::from_value(val)?)),
+            SqlValRef::Text(text) => Ok(Json(serde_json::from_str(text)?)),
+            _ => Err(CannotConvertSqlVal(SqlType::Json, valref.into())),
+        }
+    }
+}
+
+impl<T> FieldType for Json<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    const SQLTYPE: SqlType = SqlType::Json;
+    type RefType = Self;
+}
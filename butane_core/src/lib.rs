@@ -6,10 +6,12 @@ use std::cmp::{Eq, PartialEq};
 use std::default::Default;
 use thiserror::Error as ThisError;
 
+pub mod backref;
 pub mod codegen;
 pub mod custom;
 pub mod db;
 pub mod fkey;
+pub mod json;
 pub mod many;
 pub mod migrations;
 pub mod query;
@@ -18,7 +20,8 @@ pub mod sqlval;
 #[cfg(feature = "uuid")]
 pub mod uuid;
 
-use db::{BackendRow, Column, ConnectionMethods};
+use db::{BackendRow, BackendRows, Column, ConnectionMethods};
+use fallible_iterator::FallibleIterator;
 
 use custom::SqlTypeCustom;
 pub use query::Query;
@@ -56,12 +59,73 @@ impl Eq for ObjectState {}
 pub trait DataResult: Sized {
     /// Corresponding object type.
     type DBO: DataObject;
+    /// The relationships of this type which may be prefetched. See
+    /// [Query::prefetch].
+    type Relations: Default;
     const COLUMNS: &'static [Column];
     fn from_row<'a>(row: &(dyn BackendRow + 'a)) -> Result<Self>
     where
         Self: Sized;
     /// Create a blank query (matching all rows) for this type.
     fn query() -> Query<Self>;
+    /// Load the relationship named `relation` for each of `objs`
+    /// using as few queries as possible. Used by
+    /// [Query::prefetch]. Implemented by `#[model]` and `#[dataresult]`
+    /// for any relationships the type has.
+    fn prefetch(_conn: &impl ConnectionMethods, _objs: &[Self], relation: &str) -> Result<()> {
+        Err(Error::Internal(format!("No relation named {}", relation)))
+    }
+    /// Runs the hand-written SELECT query `sql` and loads each row as
+    /// `Self`. Use `?` as the placeholder for each of `params`. The
+    /// query must return the columns of `Self::COLUMNS`, in order and
+    /// with matching types, which is checked for each row.
+    fn from_sql_query(
+        conn: &impl ConnectionMethods,
+        sql: &str,
+        params: &[SqlVal],
+    ) -> Result<db::QueryResult<Self>> {
+        conn.query_sql(sql, params)?
+            .mapped(|row| {
+                check_row_columns(row, Self::COLUMNS)?;
+                Self::from_row(row)
+            })
+            .collect()
+    }
+}
+
+/// Checks that `row` has the number and types of `columns`.
+fn check_row_columns(row: &dyn BackendRow, columns: &[Column]) -> Result<()> {
+    if row.len() != columns.len() {
+        return Err(Error::BoundsError(format!(
+            "Expected {} columns in row, found {}",
+            columns.len(),
+            row.len()
+        )));
+    }
+    for (i, col) in columns.iter().enumerate() {
+        if let Some(declared) = row.incompatible_column_type(i, col.ty()) {
+            return Err(Error::SqlResultTypeMismatch {
+                col: col.name().to_string(),
+                detail: format!(
+                    "column of type {} is not compatible with expected column type {}",
+                    declared,
+                    col.ty()
+                ),
+            });
+        }
+        let val = row.get(i, col.ty().clone())?;
+        if matches!(val.sqltype(), Some(ty) if ty != *col.ty()) {
+            return Err(Error::SqlResultTypeMismatch {
+                col: col.name().to_string(),
+                detail: format!(
+                    "{:?} is not compatible with expected column type {}",
+                    val,
+                    col.ty()
+                ),
+            });
+        }
+    }
+    Ok(())
 }
 
 /// An object in the database.
@@ -89,7 +153,7 @@ pub trait DataObject: DataResult<DBO = Self> {
         <Self as DataResult>::query()
             .filter(query::BoolExpr::Eq(
                 Self::PKCOL,
-                query::Expr::Val(id.borrow().to_sql()),
+                query::Expr::value(id.borrow()),
             ))
             .limit(1)
             .load(conn)?
@@ -150,6 +214,14 @@ pub enum Error {
     IncompatibleCustomT(custom::SqlTypeCustom, &'static str),
     #[error("Literal values for custom types are currently unsupported.")]
     LiteralForCustomUnsupported(custom::SqlValCustom),
+    #[error("Invalid pagination cursor: {0}")]
+    InvalidCursor(String),
+    #[error("{0} is not supported for compound (union, intersect or except) queries")]
+    UnsupportedForCompound(&'static str),
+    #[error("{0} is not supported for join queries")]
+    UnsupportedForJoin(&'static str),
+    #[error("Prefetching is not supported by {0}")]
+    UnsupportedForPrefetch(&'static str),
     #[error("(De)serialization error {0}")]
     SerdeJson(#[from] serde_json::Error),
     #[error("IO error {0}")]
@@ -197,6 +269,8 @@ impl From<rusqlite::types::FromSqlError> for Error {
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum SqlType {
     Bool,
+    /// 2 bytes
+    SmallInt,
     /// 4 bytes
     Int,
     /// 8 bytes
@@ -206,7 +280,27 @@ pub enum SqlType {
     Text,
     #[cfg(feature = "datetime")]
     Timestamp,
+    /// A date without a time zone.
+    #[cfg(feature = "datetime")]
+    Date,
+    /// A time of day without a time zone.
+    #[cfg(feature = "datetime")]
+    Time,
+    /// A timestamp in UTC.
+    #[cfg(feature = "datetime")]
+    TimestampTz,
+    /// An exact decimal number, optionally with the given precision
+    /// (total number of digits) and scale (digits after the decimal
+    /// point). SQLite has no decimal type, so there it is stored as
+    /// canonical text, in a column whose collation compares it as a
+    /// number. SQLite can only sum it in a
+    /// [Query::sum](crate::query::Query::sum), not in an aggregate
+    /// field of a `dataresult`.
+    #[cfg(feature = "decimal")]
+    Numeric(Option<(u16, u16)>),
     Blob,
+    /// JSON, stored as JSONB on Postgres and as text on SQLite.
+    Json,
     Custom(SqlTypeCustom),
 }
 impl std::fmt::Display for SqlType {
@@ -214,13 +308,23 @@ impl std::fmt::Display for SqlType {
         use SqlType::*;
         match &self {
             Bool => "bool",
+            SmallInt => "small int",
             Int => "int",
             BigInt => "big int",
             Real => "float",
             Text => "string",
             #[cfg(feature = "datetime")]
             Timestamp => "timestamp",
+            #[cfg(feature = "datetime")]
+            Date => "date",
+            #[cfg(feature = "datetime")]
+            Time => "time",
+            #[cfg(feature = "datetime")]
+            TimestampTz => "timestamp with time zone",
+            #[cfg(feature = "decimal")]
+            Numeric(_) => "numeric",
             Blob => "blob",
+            Json => "json",
             Custom(_) => "custom",
         }
         .fmt(f)
@@ -1,9 +1,10 @@
-use crate::db::{Column, ConnectionMethods};
+use crate::db::{collect_rows, Column, ConnectionMethods};
 use crate::query::{BoolExpr, Expr};
-use crate::{DataObject, Error, FieldType, Result, SqlType, SqlVal, ToSql};
+use crate::{DataObject, Error, FieldType, Result, SqlType, SqlVal, SqlValKey, ToSql};
 use once_cell::unsync::OnceCell;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 
 fn default_oc<T>() -> OnceCell<Vec<T>> {
     OnceCell::default()
@@ -71,6 +72,7 @@ where
         // Check for uninitialized pk
         if T::AUTO_PK {
             let ipk: i64 = match new_val.pk().to_sql() {
+                SqlVal::SmallInt(i) => i as i64,
                 SqlVal::Int(i) => i as i64,
                 SqlVal::BigInt(i) => i,
                 _ => 1,
@@ -153,6 +155,111 @@ where
         });
         vals.map(|v| v.iter())
     }
+    /// Loads the values of all of `manys` which are not already
+    /// loaded, querying the many table once for all owners rather
+    /// than once per owner. Used by
+    /// [Query::prefetch](crate::query::Query::prefetch).
+    pub fn prefetch<'a>(
+        conn: &impl ConnectionMethods,
+        manys: impl IntoIterator<Item = &'a Self>,
+    ) -> Result<()>
+    where
+        T: 'a,
+    {
+        // Values not yet saved to the db are left for `load` to handle
+        let manys: Vec<&Self> = manys
+            .into_iter()
+            .filter(|many| {
+                many.owner.is_some()
+                    && many.new_values.is_empty()
+                    && many.all_values.get().is_none()
+            })
+            .collect();
+        let first = match manys.first() {
+            Some(first) => first,
+            None => return Ok(()),
+        };
+        let mut owners: Vec<SqlVal> = Vec::new();
+        let mut seen: HashSet<SqlValKey> = HashSet::new();
+        for many in &manys {
+            let owner = many.owner.as_ref().unwrap();
+            if seen.insert(SqlValKey(owner.clone())) {
+                owners.push(owner.clone());
+            }
+        }
+
+        // Rows of (owner, has)
+        let columns = first.columns();
+        let links = collect_rows(
+            conn.query(
+                &first.item_table,
+                &columns,
+                false,
+                Some(BoolExpr::In("owner", owners)),
+                None,
+                None,
+                None,
+                None,
+            )?,
+            &columns,
+        )?;
+        let mut pks: Vec<SqlVal> = Vec::new();
+        let mut seen: HashSet<SqlValKey> = HashSet::new();
+        // The pks each owner has, in the order they were found
+        let mut owned: HashMap<SqlValKey, Vec<SqlValKey>> = HashMap::new();
+        for link in links {
+            let mut link = link.into_iter();
+            let (owner, has) = match (link.next(), link.next()) {
+                (Some(owner), Some(has)) => (owner, has),
+                _ => return Err(Error::Internal("Malformed many table row".to_string())),
+            };
+            if seen.insert(SqlValKey(has.clone())) {
+                pks.push(has.clone());
+            }
+            owned
+                .entry(SqlValKey(owner))
+                .or_default()
+                .push(SqlValKey(has));
+        }
+        let rows = if pks.is_empty() {
+            Vec::new()
+        } else {
+            collect_rows(
+                conn.query(
+                    T::TABLE,
+                    T::COLUMNS,
+                    false,
+                    Some(BoolExpr::In(T::PKCOL, pks)),
+                    None,
+                    None,
+                    None,
+                    None,
+                )?,
+                T::COLUMNS,
+            )?
+        };
+        let pkidx = T::COLUMNS
+            .iter()
+            .position(|col| col.name() == T::PKCOL)
+            .ok_or_else(|| Error::Internal(format!("No column for pk {}", T::PKCOL)))?;
+        let rows: HashMap<SqlValKey, Vec<SqlVal>> = rows
+            .into_iter()
+            .map(|row| (SqlValKey(row[pkidx].clone()), row))
+            .collect();
+
+        for many in manys {
+            let owner = SqlValKey(many.owner.clone().unwrap());
+            let mut vals = Vec::new();
+            for has in owned.get(&owner).into_iter().flatten() {
+                if let Some(row) = rows.get(has) {
+                    vals.push(T::from_row(row)?);
+                }
+            }
+            many.all_values.set(vals).ok();
+        }
+        Ok(())
+    }
+
     pub fn columns(&self) -> [Column; 2] {
         [
             Column::new("owner", self.owner_type.clone()),
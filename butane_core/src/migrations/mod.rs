@@ -83,6 +83,8 @@ pub trait Migrations {
             .query(
                 ButaneMigration::TABLE,
                 ButaneMigration::COLUMNS,
+                false,
+                None,
                 None,
                 None,
                 None,
@@ -235,6 +237,7 @@ struct ButaneMigration {
 }
 impl DataResult for ButaneMigration {
     type DBO = Self;
+    type Relations = ();
     const COLUMNS: &'static [Column] = &[Column::new("name", SqlType::Text)];
     fn from_row(row: &dyn db::BackendRow) -> Result<Self> {
         if row.len() != 1usize {
@@ -0,0 +1,100 @@
+//! Cursors for keyset pagination. See [Query::load_page](super::Query::load_page).
+
+use super::{BoolExpr, Expr, Order, OrderDirection};
+use crate::{Error, Result, SqlVal};
+use serde::{Deserialize, Serialize};
+
+/// Position in the ordered results of a query, used to load the
+/// results after or before it with [Query::after](super::Query::after)
+/// or [Query::before](super::Query::before).
+///
+/// Holds the values of the query's order terms for a single row. A
+/// cursor may be converted to an opaque string token with
+/// [to_token](Cursor::to_token), e.g. to pass it to an HTTP client.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cursor {
+    values: Vec<SqlVal>,
+}
+impl Cursor {
+    /// Creates a cursor from the values of the order terms of a
+    /// query (including the primary key), in order.
+    pub fn new(values: Vec<SqlVal>) -> Self {
+        Cursor { values }
+    }
+
+    pub fn values(&self) -> &[SqlVal] {
+        &self.values
+    }
+
+    /// Encodes the cursor as an opaque string token.
+    pub fn to_token(&self) -> String {
+        // Serializing a Vec<SqlVal> cannot fail
+        hex::encode(serde_json::to_vec(&self.values).unwrap())
+    }
+
+    /// Decodes a cursor from a token created by
+    /// [to_token](Cursor::to_token).
+    pub fn from_token(token: &str) -> Result<Self> {
+        let bytes = hex::decode(token).map_err(|e| Error::InvalidCursor(e.to_string()))?;
+        let values =
+            serde_json::from_slice(&bytes).map_err(|e| Error::InvalidCursor(e.to_string()))?;
+        Ok(Cursor { values })
+    }
+
+    /// Builds the expression matching rows strictly after (or, if
+    /// `before` is true, strictly before) the cursor when ordered by
+    /// `sort`. For order terms `a, b` this is `a > va OR (a = va AND b > vb)`.
+    pub(super) fn seek_expr(&self, sort: &[Order], before: bool) -> Result<BoolExpr> {
+        if self.values.len() != sort.len() || sort.is_empty() {
+            return Err(Error::InvalidCursor(
+                "cursor does not match the order of the query".to_string(),
+            ));
+        }
+        let columns = sort
+            .iter()
+            .map(|order| {
+                order.column().ok_or_else(|| {
+                    Error::InvalidCursor("cannot paginate by an expression".to_string())
+                })
+            })
+            .collect::<Result<Vec<&'static str>>>()?;
+        let mut terms = sort.iter().enumerate().map(|(i, order)| {
+            let mut conds: Vec<BoolExpr> = columns[..i]
+                .iter()
+                .zip(&self.values)
+                .map(|(&col, val)| BoolExpr::Eq(col, Expr::Val(val.clone())))
+                .collect();
+            let val = Expr::Val(self.values[i].clone());
+            let ascending = matches!(order.direction, OrderDirection::Ascending);
+            conds.push(if ascending != before {
+                BoolExpr::Gt(columns[i], val)
+            } else {
+                BoolExpr::Lt(columns[i], val)
+            });
+            BoolExpr::AllOf(conds)
+        });
+        let first = terms.next().unwrap();
+        Ok(terms.fold(first, |a, b| BoolExpr::Or(Box::new(a), Box::new(b))))
+    }
+}
+
+/// A page of query results loaded by
+/// [Query::load_page](super::Query::load_page).
+#[derive(Debug)]
+pub struct Page<T> {
+    /// The objects in the page, in the order of the query.
+    pub objects: Vec<T>,
+    /// Cursor for the first object. Pass to
+    /// [Query::before](super::Query::before) to load the previous page.
+    pub first: Option<Cursor>,
+    /// Cursor for the last object. Pass to
+    /// [Query::after](super::Query::after) to load the next page.
+    pub last: Option<Cursor>,
+}
+
+/// Where a paginated query starts, relative to a cursor.
+#[derive(Clone)]
+pub(super) enum Seek {
+    After(Cursor),
+    Before(Cursor),
+}
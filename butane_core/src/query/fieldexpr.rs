@@ -1,7 +1,8 @@
 //! Not expected to be used directly.
 
 use crate::fkey::ForeignKey;
-use crate::query::{BoolExpr, Column, Expr, Join};
+use crate::json::Json;
+use crate::query::{escape_like, ArithOp, Assignment, BoolExpr, Column, CompareOp, Expr, Join};
 use crate::sqlval::{FieldType, SqlVal, ToSql};
 use crate::DataObject;
 use std::borrow::{Borrow, Cow};
@@ -15,7 +16,19 @@ macro_rules! binary_op {
             T: $bound,
             U: ToSql,
         {
-            BoolExpr::$cond(self.name, Expr::Val(val.to_sql()))
+            BoolExpr::$cond(self.name, Expr::value(val))
+        }
+    };
+}
+
+macro_rules! qualified_op {
+    ($func_name:ident, $bound:path, $op:ident) => {
+        pub fn $func_name<U>(&self, table: &'static str, val: &U) -> BoolExpr
+        where
+            T: $bound,
+            U: ToSql,
+        {
+            BoolExpr::Compare(self.qualified_expr(table), CompareOp::$op, Expr::value(val))
         }
     };
 }
@@ -36,6 +49,148 @@ pub trait DataOrd<Rhs> {}
 impl<T> DataOrd<T> for Option<T> where T: PartialOrd<T> + FieldType {}
 impl<T> DataOrd<T> for T where T: PartialOrd<T> + FieldType {}
 
+/// Marker trait for types which support arithmetic with values of
+/// type `Rhs` in a filter. The result has the type of the left
+/// operand.
+pub trait DataArith<Rhs> {}
+
+macro_rules! data_arith {
+    ($($t:ty),+) => {
+        $(
+            impl DataArith<$t> for $t {}
+            impl DataArith<$t> for Option<$t> {}
+        )+
+    };
+}
+data_arith!(i8, u8, i16, u16, i32, u32, i64, f32, f64);
+
+/// Marker trait for types which may be summed or averaged in an
+/// aggregate query. `Output` is the type of the sum.
+pub trait DataSum {
+    type Output: FieldType;
+}
+
+macro_rules! data_sum {
+    ($output:ty, $($t:ty),+) => {
+        $(
+            impl DataSum for $t {
+                type Output = $output;
+            }
+            impl DataSum for Option<$t> {
+                type Output = $output;
+            }
+        )+
+    };
+}
+data_sum!(i64, i8, u8, i16, u16, i32, u32, i64);
+data_sum!(f64, f32, f64);
+#[cfg(feature = "decimal")]
+data_sum!(rust_decimal::Decimal, rust_decimal::Decimal);
+
+macro_rules! value_op {
+    ($func_name:ident, $bound:path, $op:ident) => {
+        pub fn $func_name<U>(self, other: ValueExpr<U>) -> BoolExpr
+        where
+            T: $bound,
+        {
+            BoolExpr::Compare(self.expr, CompareOp::$op, other.expr)
+        }
+    };
+}
+
+/// An expression of type `T` evaluated by the database, such as a
+/// column, a value, or arithmetic on them. Used to implement
+/// comparisons and arithmetic in the `filter!` macro, so that the
+/// types of both sides are checked as for [FieldExpr].
+pub struct ValueExpr<T> {
+    expr: Expr,
+    phantom: PhantomData<T>,
+}
+impl<T> ValueExpr<T> {
+    /// `expr` must evaluate to a value of type `T`.
+    pub fn new(expr: Expr) -> Self {
+        ValueExpr {
+            expr,
+            phantom: PhantomData,
+        }
+    }
+
+    /// A literal value.
+    pub fn val(val: &T) -> Self
+    where
+        T: ToSql,
+    {
+        Self::new(Expr::value(val))
+    }
+
+    pub fn into_expr(self) -> Expr {
+        self.expr
+    }
+
+    value_op!(eq, std::cmp::PartialEq<U>, Eq);
+    value_op!(ne, std::cmp::PartialEq<U>, Ne);
+    value_op!(lt, DataOrd<U>, Lt);
+    value_op!(gt, DataOrd<U>, Gt);
+    value_op!(le, DataOrd<U>, Le);
+    value_op!(ge, DataOrd<U>, Ge);
+
+    /// True if the expression is NULL.
+    pub fn is_null(self) -> BoolExpr {
+        BoolExpr::Compare(self.expr, CompareOp::Eq, Expr::Val(SqlVal::Null))
+    }
+
+    /// True if the expression is not NULL.
+    pub fn is_not_null(self) -> BoolExpr {
+        BoolExpr::Compare(self.expr, CompareOp::Ne, Expr::Val(SqlVal::Null))
+    }
+
+    /// The result of `op` on this expression and `other`.
+    pub fn arith<U>(self, op: ArithOp, other: ValueExpr<U>) -> ValueExpr<T>
+    where
+        T: DataArith<U>,
+    {
+        Self::new(Expr::Arith(Box::new(self.expr), op, Box::new(other.expr)))
+    }
+}
+
+macro_rules! json_op {
+    ($func_name:ident, $op:ident) => {
+        pub fn $func_name<U>(self, other: ValueExpr<U>) -> BoolExpr {
+            BoolExpr::Compare(self.expr, CompareOp::$op, other.expr)
+        }
+    };
+}
+
+/// A value within a [Json] field, as selected by
+/// [FieldExpr::json_get]. As the type of the value is not known, it
+/// may be compared with a value of any type. Numbers compare as
+/// numbers and strings as strings.
+pub struct JsonValueExpr {
+    expr: Expr,
+}
+impl JsonValueExpr {
+    pub fn into_expr(self) -> Expr {
+        self.expr
+    }
+
+    json_op!(eq, Eq);
+    json_op!(ne, Ne);
+    json_op!(lt, Lt);
+    json_op!(gt, Gt);
+    json_op!(le, Le);
+    json_op!(ge, Ge);
+
+    /// True if the value is NULL or absent.
+    pub fn is_null(self) -> BoolExpr {
+        BoolExpr::Compare(self.expr, CompareOp::Eq, Expr::Val(SqlVal::Null))
+    }
+
+    /// True if the value is present and not NULL.
+    pub fn is_not_null(self) -> BoolExpr {
+        BoolExpr::Compare(self.expr, CompareOp::Ne, Expr::Val(SqlVal::Null))
+    }
+}
+
 /// Used to implement the `query!` and `filter!` macros.
 pub struct FieldExpr<T>
 where
@@ -60,6 +215,31 @@ where
         self.name
     }
 
+    /// An expression referring to the field's column, for use in
+    /// [BoolExpr::Compare] and [Expr::Arith].
+    pub fn expr(&self) -> Expr {
+        Expr::Column(self.name)
+    }
+
+    /// Like [expr](FieldExpr::expr), but qualified with `table`, the
+    /// table of the model containing the field. Used to refer to the
+    /// enclosing query from within [BoolExpr::Exists].
+    pub fn qualified_expr(&self, table: &'static str) -> Expr {
+        Expr::QualifiedColumn(Cow::Borrowed(table), self.name)
+    }
+
+    /// Like [expr](FieldExpr::expr), but typed with the type of the
+    /// field so that it may only be compared with compatible values.
+    pub fn value(&self) -> ValueExpr<T> {
+        ValueExpr::new(self.expr())
+    }
+
+    /// Like [qualified_expr](FieldExpr::qualified_expr), but typed as
+    /// [value](FieldExpr::value).
+    pub fn qualified_value(&self, table: &'static str) -> ValueExpr<T> {
+        ValueExpr::new(self.qualified_expr(table))
+    }
+
     binary_op!(eq, std::cmp::PartialEq<U>, Eq);
     binary_op!(ne, std::cmp::PartialEq<U>, Ne);
     binary_op!(lt, DataOrd<U>, Lt);
@@ -67,12 +247,149 @@ where
     binary_op!(le, DataOrd<U>, Le);
     binary_op!(ge, DataOrd<U>, Ge);
 
+    // As the comparisons above, but with the column qualified with
+    // `table` as in `qualified_expr`. Used for conditions at the end
+    // of a path of foreign keys, where the column name alone may be
+    // ambiguous.
+    qualified_op!(qualified_eq, std::cmp::PartialEq<U>, Eq);
+    qualified_op!(qualified_ne, std::cmp::PartialEq<U>, Ne);
+    qualified_op!(qualified_lt, DataOrd<U>, Lt);
+    qualified_op!(qualified_gt, DataOrd<U>, Gt);
+    qualified_op!(qualified_le, DataOrd<U>, Le);
+    qualified_op!(qualified_ge, DataOrd<U>, Ge);
+
+    /// Sets the field to `val`, for use with
+    /// [Query::update](crate::query::Query::update).
+    pub fn set<U>(&self, val: U) -> Assignment
+    where
+        T: PartialEq<U>,
+        U: ToSql,
+    {
+        Assignment {
+            column: self.name,
+            value: Expr::value(&val),
+        }
+    }
+
     pub fn like<U>(&self, val: U) -> BoolExpr
     where
         U: ToSql,
     {
         BoolExpr::Like(self.name, Expr::Val(val.to_sql()))
     }
+
+    /// Case-insensitive version of [like](FieldExpr::like). `\` may
+    /// be used to escape `%` and `_` in the pattern.
+    pub fn ilike<U>(&self, val: U) -> BoolExpr
+    where
+        U: ToSql,
+    {
+        BoolExpr::ILike(self.name, Expr::Val(val.to_sql()))
+    }
+
+    /// True if the field starts with `val`. The comparison is
+    /// case-sensitive.
+    pub fn starts_with(&self, val: &str) -> BoolExpr
+    where
+        T: DataEq<String>,
+    {
+        self.like_escaped(format!("{}%", escape_like(val)))
+    }
+
+    /// True if the field ends with `val`. The comparison is
+    /// case-sensitive.
+    pub fn ends_with(&self, val: &str) -> BoolExpr
+    where
+        T: DataEq<String>,
+    {
+        self.like_escaped(format!("%{}", escape_like(val)))
+    }
+
+    /// True if the field contains `val`. The comparison is
+    /// case-sensitive.
+    pub fn contains_str(&self, val: &str) -> BoolExpr
+    where
+        T: DataEq<String>,
+    {
+        self.like_escaped(format!("%{}%", escape_like(val)))
+    }
+
+    fn like_escaped(&self, pattern: String) -> BoolExpr {
+        BoolExpr::LikeEscape(self.name, Expr::Val(SqlVal::Text(pattern)))
+    }
+
+    /// True if the field is equal to any of `vals`.
+    pub fn in_<U, I>(&self, vals: I) -> BoolExpr
+    where
+        T: DataEq<U>,
+        U: ToSql,
+        I: IntoIterator,
+        I::Item: Borrow<U>,
+    {
+        BoolExpr::In(
+            self.name,
+            vals.into_iter()
+                .map(|v| match Expr::value(v.borrow()) {
+                    Expr::Val(val) => val,
+                    // Equal to nothing, as is NULL
+                    _ => SqlVal::Null,
+                })
+                .collect(),
+        )
+    }
+
+    /// True if the field is not equal to any of `vals`. As with
+    /// [ne](FieldExpr::ne), this follows SQL's treatment of NULL: an
+    /// object whose field is NULL is not matched (unless `vals` is
+    /// empty, in which case every object is). Combine with
+    /// [is_none](FieldExpr::is_none) to match it as well.
+    pub fn not_in<U, I>(&self, vals: I) -> BoolExpr
+    where
+        T: DataEq<U>,
+        U: ToSql,
+        I: IntoIterator,
+        I::Item: Borrow<U>,
+    {
+        BoolExpr::Not(Box::new(self.in_(vals)))
+    }
+}
+impl<U> FieldExpr<Option<U>>
+where
+    Option<U>: Into<SqlVal>,
+{
+    /// True if the field is NULL.
+    pub fn is_none(&self) -> BoolExpr {
+        BoolExpr::Eq(self.name, Expr::Val(SqlVal::Null))
+    }
+
+    /// True if the field is not NULL.
+    pub fn is_some(&self) -> BoolExpr {
+        BoolExpr::Ne(self.name, Expr::Val(SqlVal::Null))
+    }
+}
+impl<T> FieldExpr<Json<T>>
+where
+    Json<T>: Into<SqlVal>,
+{
+    /// The value within this JSON field at `path`, a sequence of
+    /// object keys.
+    pub fn json_get(&self, path: Vec<String>) -> JsonValueExpr {
+        JsonValueExpr {
+            expr: Expr::JsonGet(Box::new(self.expr()), path),
+        }
+    }
+}
+impl<T> FieldExpr<Option<Json<T>>>
+where
+    Option<Json<T>>: Into<SqlVal>,
+{
+    /// The value within this JSON field at `path`, a sequence of
+    /// object keys.
+    pub fn json_get(&self, path: Vec<String>) -> JsonValueExpr {
+        JsonValueExpr {
+            expr: Expr::JsonGet(Box::new(self.expr()), path),
+        }
+    }
 }
 impl<F: DataObject> FieldExpr<ForeignKey<F>> {
     pub fn subfilter(&self, q: BoolExpr) -> BoolExpr {
@@ -84,10 +401,45 @@ impl<F: DataObject> FieldExpr<ForeignKey<F>> {
         }
     }
     pub fn subfilterpk(&self, pk: F::PKType) -> BoolExpr {
-        self.subfilter(BoolExpr::Eq(
-            F::PKCOL,
-            crate::query::Expr::Val(pk.into_sql()),
-        ))
+        self.subfilter(BoolExpr::Eq(F::PKCOL, crate::query::Expr::value(&pk)))
+    }
+    /// Like [subfilter](FieldExpr::subfilter), but `q` is evaluated on
+    /// the referent joined with `joins`, which are typically built
+    /// with [join_to](FieldExpr::join_to) to follow a path of foreign
+    /// keys. Columns in `q` should be qualified with their table.
+    pub fn subfilter_joined(&self, joins: Vec<Join>, q: BoolExpr) -> BoolExpr {
+        BoolExpr::SubqueryJoin {
+            col: self.name,
+            tbl2: Cow::Borrowed(F::TABLE),
+            col2: Column::new(F::TABLE, F::PKCOL),
+            joins,
+            expr: Box::new(q),
+        }
+    }
+    /// The join from the referent of this foreign key to the referent
+    /// of `next`, a foreign key field of `F`.
+    pub fn join_to<G: DataObject>(&self, next: &FieldExpr<ForeignKey<G>>) -> Join {
+        Join::Inner {
+            join_table: G::TABLE,
+            col1: Column::new(F::TABLE, next.name),
+            col2: Column::new(G::TABLE, G::PKCOL),
+        }
+    }
+    /// The table of the referent, `F`.
+    pub fn referent_table(&self) -> &'static str {
+        F::TABLE
+    }
+    /// An expression for the value of `expr`, which refers to the
+    /// fields of `F`, on the object referred to by this foreign
+    /// key. `table` is the table containing the foreign key.
+    pub fn related(&self, table: &'static str, expr: Expr) -> Expr {
+        Expr::Related {
+            table: Cow::Borrowed(table),
+            col: self.name,
+            tbl2: Cow::Borrowed(F::TABLE),
+            tbl2_col: F::PKCOL,
+            expr: Box::new(expr),
+        }
     }
     pub fn fields(&self) -> F::Fields {
         F::Fields::default()
@@ -132,7 +484,56 @@ where
     pub fn containspk(&self, pk: impl Borrow<<T::PKType as FieldType>::RefType>) -> BoolExpr {
         self.contains(BoolExpr::Eq(
             T::PKCOL,
-            crate::query::Expr::Val(pk.borrow().to_sql()),
+            crate::query::Expr::value(pk.borrow()),
+        ))
+    }
+    pub fn fields(&self) -> T::Fields {
+        T::Fields::default()
+    }
+}
+
+/// Field expression for a [BackRef](crate::backref::BackRef) field.
+pub struct BackRefFieldExpr<O, T>
+where
+    O: DataObject, // owner
+    T: DataObject, // referring
+{
+    name: &'static str,
+    column: &'static str,
+    phantomo: PhantomData<O>,
+    phantomt: PhantomData<T>,
+}
+impl<O, T> BackRefFieldExpr<O, T>
+where
+    O: DataObject,
+    T: DataObject,
+{
+    /// `name` is the name of the backref field in `O` and `fkey` the
+    /// foreign key field in `T` which refers to `O`.
+    pub fn new(name: &'static str, fkey: FieldExpr<ForeignKey<O>>) -> Self {
+        BackRefFieldExpr {
+            name,
+            column: fkey.name,
+            phantomo: PhantomData,
+            phantomt: PhantomData,
+        }
+    }
+    /// The name of the backref field in `O`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+    pub fn contains(&self, q: BoolExpr) -> BoolExpr {
+        BoolExpr::Subquery {
+            col: O::PKCOL,
+            tbl2: Cow::Borrowed(T::TABLE),
+            tbl2_col: self.column,
+            expr: Box::new(q),
+        }
+    }
+    pub fn containspk(&self, pk: impl Borrow<<T::PKType as FieldType>::RefType>) -> BoolExpr {
+        self.contains(BoolExpr::Eq(
+            T::PKCOL,
+            crate::query::Expr::value(pk.borrow()),
         ))
     }
     pub fn fields(&self) -> T::Fields {
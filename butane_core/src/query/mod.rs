@@ -2,15 +2,30 @@
 //! the `query!`, `filter!`, and `find!` macros instead of using this
 //! module directly.
 
-use crate::db::{BackendRows, ConnectionMethods, QueryResult};
-use crate::{DataResult, Result, SqlVal};
-use fallible_iterator::FallibleIterator;
+use crate::db::{
+    Backend, BackendRow, BackendRows, ConnectionMethods, OffsetRow, QueryResult, RawQueryResult,
+};
+use crate::fkey::ForeignKey;
+use crate::{
+    DataObject, DataResult, FieldType, FromSql, Result, SqlType, SqlVal, SqlValRef, ToSql,
+};
 use std::borrow::Cow;
 use std::marker::PhantomData;
 
+mod cursor;
 mod fieldexpr;
+mod prepared;
+mod select;
 
-pub use fieldexpr::{DataOrd, FieldExpr, ManyFieldExpr};
+pub use cursor::{Cursor, Page};
+/// Re-exported for use with [Query::iter].
+pub use fallible_iterator::FallibleIterator;
+pub use fieldexpr::{
+    BackRefFieldExpr, DataArith, DataOrd, DataSum, FieldExpr, JsonValueExpr, ManyFieldExpr,
+    ValueExpr,
+};
+pub use prepared::{BoundQuery, PreparedQuery};
+pub use select::{Select, SelectColumns};
 
 type TblName = Cow<'static, str>;
 
@@ -19,12 +34,75 @@ type TblName = Cow<'static, str>;
 pub enum Expr {
     /// A column, referenced by name.
     Column(&'static str),
+    /// A column of the given table, referenced by name qualified with
+    /// the table name. Used within a subquery to refer to the row of
+    /// the enclosing query, as in [BoolExpr::Exists].
+    QualifiedColumn(TblName, &'static str),
     /// A value.
     Val(SqlVal),
+    /// A value which cannot be represented in SQL, such as a `u64`
+    /// larger than any integer a column can store. Like NULL, it
+    /// compares as neither equal nor unequal to anything, and
+    /// assigning it to a column fails with
+    /// [OutOfRange](crate::Error::OutOfRange).
+    OutOfRange,
     /// A placeholder for a value.
     Placeholder,
     /// A boolean condition.
     Condition(Box<BoolExpr>),
+    /// An arithmetic operation on two expressions.
+    Arith(Box<Expr>, ArithOp, Box<Expr>),
+    /// A call of the SQL function with the given name, such as
+    /// `lower` or `random`, on the argument expressions.
+    Function(&'static str, Vec<Expr>),
+    /// The value of `expr` evaluated on the row of `tbl2` whose
+    /// `tbl2_col` equals the `col` column of the row of `table`
+    /// being evaluated. Typically `col` is a foreign key to `tbl2`,
+    /// so that `expr` refers to a column of the related object.
+    Related {
+        table: TblName,
+        col: &'static str,
+        tbl2: TblName,
+        tbl2_col: &'static str,
+        expr: Box<Expr>,
+    },
+    /// The value within the JSON `expr` at the path given by a
+    /// sequence of object keys. Numbers compare as numbers and
+    /// strings as strings.
+    JsonGet(Box<Expr>, Vec<String>),
+}
+
+impl Expr {
+    /// The expression for the value `val`, or [Expr::OutOfRange] if
+    /// it cannot be represented in SQL.
+    pub fn value<T: ToSql + ?Sized>(val: &T) -> Expr {
+        match val.try_to_sql_ref() {
+            Ok(val) => Expr::Val(val.into()),
+            Err(crate::Error::OutOfRange) => Expr::OutOfRange,
+            Err(e) => panic!("cannot use value in a query: {}", e),
+        }
+    }
+}
+
+/// An arithmetic operator, used in [Expr::Arith].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArithOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+/// A comparison operator, used in [BoolExpr::Compare].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
 }
 
 /// Abstract representation of a boolean expression.
@@ -38,6 +116,12 @@ pub enum BoolExpr {
     Le(&'static str, Expr),
     Ge(&'static str, Expr),
     Like(&'static str, Expr),
+    /// As `Like`, but with `\` as the escape character in the
+    /// pattern, and case-sensitive on every backend.
+    LikeEscape(&'static str, Expr),
+    /// Case-insensitive `Like`, with `\` as the escape character in
+    /// the pattern.
+    ILike(&'static str, Expr),
     AllOf(Vec<BoolExpr>),
     And(Box<BoolExpr>, Box<BoolExpr>),
     Or(Box<BoolExpr>, Box<BoolExpr>),
@@ -52,6 +136,14 @@ pub enum BoolExpr {
         expr: Box<BoolExpr>,
     },
     In(&'static str, Vec<SqlVal>),
+    /// Expression which is true if `expr` is true for any row of
+    /// `tbl2`. `expr` may refer to the row of the enclosing query with
+    /// [Expr::QualifiedColumn]. `tbl2` is aliased within the subquery,
+    /// so it may be the same table as the enclosing one.
+    Exists {
+        tbl2: TblName,
+        expr: Box<BoolExpr>,
+    },
     /// Expression which is true if the value of `col` is present in
     /// the set of values of `col2` where `expr` evaluated on a row
     /// in `tbl2` with the specified joins is true.
@@ -62,6 +154,77 @@ pub enum BoolExpr {
         joins: Vec<Join>,
         expr: Box<BoolExpr>,
     },
+    /// A fragment of raw SQL, with `?` as the placeholder for each of
+    /// the values, in order. The number of placeholders must match
+    /// the number of values.
+    Raw(String, Vec<SqlVal>),
+    /// Compares two arbitrary expressions, such as a column with
+    /// another column or with the result of an arithmetic
+    /// expression.
+    Compare(Expr, CompareOp, Expr),
+}
+
+/// Escapes the LIKE wildcards `%` and `_` in `s` (and the escape
+/// character `\` itself), so that it matches only itself in a
+/// [BoolExpr::LikeEscape] or [BoolExpr::ILike] pattern.
+pub fn escape_like(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// The byte offsets of the `?` placeholders in `sql`, a fragment of
+/// hand-written SQL. A `?` is not a placeholder within a quoted
+/// string or identifier, a comment, or a Postgres dollar-quoted
+/// string such as `$$...$$`.
+pub fn raw_sql_placeholders(sql: &str) -> Vec<usize> {
+    // Skips past the next occurrence of `end` at or after `start`.
+    let skip_past = |start: usize, end: &str| match sql[start..].find(end) {
+        Some(n) => start + n + end.len(),
+        None => sql.len(),
+    };
+    let bytes = sql.as_bytes();
+    let mut positions = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        i = match (bytes[i], bytes.get(i + 1)) {
+            (b'?', _) => {
+                positions.push(i);
+                i + 1
+            }
+            (b'\'', _) => skip_past(i + 1, "'"),
+            (b'"', _) => skip_past(i + 1, "\""),
+            (b'-', Some(b'-')) => skip_past(i + 2, "\n"),
+            (b'/', Some(b'*')) => skip_past(i + 2, "*/"),
+            (b'$', _) => match dollar_quote(&sql[i..]) {
+                Some(tag) => skip_past(i + tag.len(), tag),
+                None => i + 1,
+            },
+            _ => i + 1,
+        };
+    }
+    positions
+}
+
+/// The opening delimiter of the dollar-quoted string at the start of
+/// `sql`, like `$$` or `$tag$`, if there is one. `$1` is a numbered
+/// parameter rather than a delimiter.
+fn dollar_quote(sql: &str) -> Option<&str> {
+    let end = sql[1..].find('$')? + 1;
+    let mut tag = sql[1..end].chars();
+    let valid = match tag.next() {
+        None => true,
+        Some(c) => {
+            (c.is_ascii_alphabetic() || c == '_')
+                && tag.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+    };
+    valid.then(|| &sql[..=end])
 }
 
 /// Represents the direction of a sort.
@@ -71,11 +234,189 @@ pub enum OrderDirection {
     Descending,
 }
 
+/// Represents the placement of null values in a sort.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NullsOrder {
+    First,
+    Last,
+}
+
 /// Represents a sorting term (ORDER BY in SQL).
 #[derive(Clone)]
 pub struct Order {
     pub direction: OrderDirection,
+    /// The expression to sort by, typically a column.
+    pub expr: Expr,
+    /// Where null values are placed. If `None`, the backend's default
+    /// placement is used.
+    pub nulls: Option<NullsOrder>,
+}
+impl Order {
+    pub fn new(expr: Expr, direction: OrderDirection) -> Self {
+        Order {
+            direction,
+            expr,
+            nulls: None,
+        }
+    }
+    /// Sorts by `column` in ascending order.
+    pub fn asc(column: &'static str) -> Self {
+        Order::new(Expr::Column(column), OrderDirection::Ascending)
+    }
+    /// Sorts by `column` in descending order.
+    pub fn desc(column: &'static str) -> Self {
+        Order::new(Expr::Column(column), OrderDirection::Descending)
+    }
+    /// Sorts in random order (`random()` in SQL).
+    pub fn random() -> Self {
+        Order::new(
+            Expr::Function("random", Vec::new()),
+            OrderDirection::Ascending,
+        )
+    }
+    /// Places null values before all others.
+    pub fn nulls_first(mut self) -> Self {
+        self.nulls = Some(NullsOrder::First);
+        self
+    }
+    /// Places null values after all others.
+    pub fn nulls_last(mut self) -> Self {
+        self.nulls = Some(NullsOrder::Last);
+        self
+    }
+    /// The column sorted by, if the order expression is a plain column.
+    pub fn column(&self) -> Option<&'static str> {
+        match self.expr {
+            Expr::Column(column) => Some(column),
+            _ => None,
+        }
+    }
+    /// The same term with the direction (and placement of nulls) reversed.
+    fn reversed(mut self) -> Self {
+        self.direction = match self.direction {
+            OrderDirection::Ascending => OrderDirection::Descending,
+            OrderDirection::Descending => OrderDirection::Ascending,
+        };
+        self.nulls = self.nulls.map(|nulls| match nulls {
+            NullsOrder::First => NullsOrder::Last,
+            NullsOrder::Last => NullsOrder::First,
+        });
+        self
+    }
+}
+
+/// A set operation combining the results of two queries, used in
+/// [QuerySource::Compound].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetOperation {
+    /// Rows in either result (UNION in SQL).
+    Union,
+    /// Rows in both results (INTERSECT in SQL).
+    Intersect,
+    /// Rows in the first result but not the second (EXCEPT in SQL).
+    Except,
+}
+
+/// The rows a [SelectQuery] selects from.
+#[derive(Clone)]
+pub enum QuerySource {
+    Table(TblName),
+    /// The combination of the results of two queries, which select
+    /// the same columns. The combined rows are referred to by the
+    /// given table name, so that columns qualified with the table
+    /// name (as in [Expr::Related]) refer to them.
+    Compound(TblName, Box<SelectQuery>, SetOperation, Box<SelectQuery>),
+}
+
+/// Describes a SELECT query, other than the columns it selects. Used
+/// for compound queries; see [Query::union].
+#[derive(Clone)]
+pub struct SelectQuery {
+    pub source: QuerySource,
+    pub distinct: bool,
+    pub expr: Option<BoolExpr>,
+    pub group: Option<GroupBy>,
+    pub limit: Option<i32>,
+    pub offset: Option<i32>,
+    pub sort: Vec<Order>,
+}
+
+/// Represents an aggregate SQL function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+/// Represents an aggregate function applied to a column, such as
+/// `SUM(likes)`.
+#[derive(Clone)]
+pub struct Aggregate {
+    pub function: AggregateFunction,
+    /// The column to aggregate. `None` is only meaningful for
+    /// `Count`, in which case all rows are counted.
+    pub column: Option<&'static str>,
+    /// The type of the aggregate's result.
+    pub ty: SqlType,
+}
+impl Aggregate {
+    pub fn new(function: AggregateFunction, column: &'static str, ty: SqlType) -> Self {
+        Aggregate {
+            function,
+            column: Some(column),
+            ty,
+        }
+    }
+    /// Counts all rows (`COUNT(*)` in SQL).
+    pub fn count() -> Self {
+        Aggregate {
+            function: AggregateFunction::Count,
+            column: None,
+            ty: SqlType::BigInt,
+        }
+    }
+}
+
+/// A relationship of `T` which can be loaded along with the results
+/// of a query. See [Query::prefetch].
+pub struct Relation<T> {
+    name: &'static str,
+    phantom: PhantomData<T>,
+}
+impl<T> Relation<T> {
+    pub fn new(name: &'static str) -> Self {
+        Relation {
+            name,
+            phantom: PhantomData,
+        }
+    }
+    /// The name of the relationship within `T`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// Represents setting a column to a new value (SET in a SQL UPDATE).
+#[derive(Clone)]
+pub struct Assignment {
     pub column: &'static str,
+    pub value: Expr,
+}
+impl From<Assignment> for Vec<Assignment> {
+    fn from(assignment: Assignment) -> Self {
+        vec![assignment]
+    }
+}
+
+/// Represents grouping of query results (GROUP BY in SQL), with an
+/// optional condition on the groups (HAVING in SQL).
+#[derive(Clone, Default)]
+pub struct GroupBy {
+    pub columns: Vec<&'static str>,
+    pub having: Option<BoolExpr>,
 }
 
 #[derive(Clone)]
@@ -87,6 +428,22 @@ pub enum Join {
         col1: Column,
         col2: Column,
     },
+    /// Left (outer) join `join_table` where `col1` is equal to
+    /// `col2`. Rows without a match in `join_table` are kept, with
+    /// NULL for each of its columns.
+    Left {
+        join_table: &'static str,
+        col1: Column,
+        col2: Column,
+    },
+}
+
+/// A table joined to the table of a query along with the columns to
+/// select from it. See [ConnectionMethods::query_join].
+#[derive(Clone)]
+pub struct SelectJoin {
+    pub join: Join,
+    pub columns: &'static [crate::db::Column],
 }
 
 #[derive(Clone)]
@@ -112,14 +469,27 @@ impl Column {
     }
 }
 
+/// The queries combined by a compound [Query].
+#[derive(Clone)]
+struct Compound<T: DataResult> {
+    left: Query<T>,
+    op: SetOperation,
+    right: Query<T>,
+}
+
 /// Representation of a database query.
 #[derive(Clone)]
 pub struct Query<T: DataResult> {
     table: TblName,
     filter: Option<BoolExpr>,
+    distinct: bool,
+    group: Option<GroupBy>,
     limit: Option<i32>,
     offset: Option<i32>,
     sort: Vec<Order>,
+    prefetch: Vec<&'static str>,
+    seek: Option<cursor::Seek>,
+    compound: Option<Box<Compound<T>>>,
     phantom: PhantomData<T>,
 }
 impl<T: DataResult> Query<T> {
@@ -127,12 +497,21 @@ impl<T: DataResult> Query<T> {
     /// of matched objects can be restricted with `filter` and
     /// `limit`.
     pub fn new(table: &'static str) -> Query<T> {
+        Self::with_table(Cow::Borrowed(table))
+    }
+
+    fn with_table(table: TblName) -> Query<T> {
         Query {
-            table: Cow::Borrowed(table),
+            table,
             filter: None,
+            distinct: false,
+            group: None,
             limit: None,
             offset: None,
             sort: Vec::new(),
+            prefetch: Vec::new(),
+            seek: None,
+            compound: None,
             phantom: PhantomData,
         }
     }
@@ -145,6 +524,61 @@ impl<T: DataResult> Query<T> {
         self
     }
 
+    /// Groups the query results by the given columns. Typically used
+    /// with a [DataResult] containing aggregate fields, such as
+    /// `#[count]` or `#[sum(column)]` (see the `dataresult` macro). May
+    /// be called multiple times to group by additional columns. It is
+    /// recommended to use the `colname!` macro to construct the column
+    /// names in a typesafe manner. Returns `self` as this method is
+    /// expected to be chained.
+    pub fn group_by(mut self, columns: &[&'static str]) -> Query<T> {
+        self.group
+            .get_or_insert_with(GroupBy::default)
+            .columns
+            .extend_from_slice(columns);
+        self
+    }
+
+    /// Restricts the groups created by `group_by` to those for which
+    /// `expr` is true. Aggregate fields of a [DataResult] may be used
+    /// in `expr` through its `fields()` method. Returns `self` as this
+    /// method is expected to be chained.
+    pub fn having(mut self, expr: BoolExpr) -> Query<T> {
+        self.group.get_or_insert_with(GroupBy::default).having = Some(expr);
+        self
+    }
+
+    /// Loads a relationship of the matched objects when the query is
+    /// loaded, so that it may be accessed without further queries. For
+    /// example, `prefetch(|f| f.blog())` loads the value of the `blog`
+    /// [ForeignKey](crate::ForeignKey) of each object so that its
+    /// `get` method succeeds. [Many](crate::many::Many) fields may be
+    /// prefetched in the same way. The closure is given a value with a
+    /// method for each relationship of `T`, so naming a relationship
+    /// which `T` does not have is a compile error. This costs one
+    /// additional query per [ForeignKey](crate::ForeignKey) or
+    /// [BackRef](crate::backref::BackRef) relationship, and two per
+    /// [Many](crate::many::Many) relationship (for the many table and
+    /// then the values), rather than one per object. May be called
+    /// multiple times to prefetch several relationships. Returns
+    /// `self` as this method is expected to be chained.
+    pub fn prefetch<F>(mut self, relation: F) -> Query<T>
+    where
+        F: FnOnce(T::Relations) -> Relation<T>,
+    {
+        self.prefetch.push(relation(Default::default()).name());
+        self
+    }
+
+    /// Omits duplicate results (SELECT DISTINCT in SQL). Only useful
+    /// if `T` does not include the primary key, as otherwise every
+    /// result is distinct. Returns `self` as this method is expected
+    /// to be chained.
+    pub fn distinct(mut self) -> Query<T> {
+        self.distinct = true;
+        self
+    }
+
     /// Limits the query to matching the first `lim` objects. Returns
     /// `self` as this method is expected to be chained.
     pub fn limit(mut self, lim: i32) -> Query<T> {
@@ -164,7 +598,17 @@ impl<T: DataResult> Query<T> {
     /// It is recommended to use the `colname!`
     /// macro to construct the column name in a typesafe manner.
     pub fn order(mut self, column: &'static str, direction: OrderDirection) -> Query<T> {
-        self.sort.push(Order { direction, column });
+        self.sort.push(Order::new(Expr::Column(column), direction));
+        self
+    }
+
+    /// Order the query results by the given terms, after any existing
+    /// ones. Unlike [order](Query::order), the terms may be arbitrary
+    /// expressions and may specify the placement of nulls. It is
+    /// recommended to use the `order_by!` macro to construct the
+    /// terms.
+    pub fn order_by(mut self, order: impl IntoIterator<Item = Order>) -> Query<T> {
+        self.sort.extend(order);
         self
     }
 
@@ -178,27 +622,587 @@ impl<T: DataResult> Query<T> {
         self.order(column, OrderDirection::Descending)
     }
 
-    /// Executes the query against `conn` and returns the first result (if any).
-    pub fn load_first(self, conn: &impl ConnectionMethods) -> Result<Option<T>> {
-        conn.query(&self.table, T::COLUMNS, self.filter, Some(1), None, None)?
-            .mapped(T::from_row)
-            .nth(0)
+    /// Restricts the query to objects after `cursor` in the query's
+    /// order, for keyset pagination. Unlike `offset`, this does not
+    /// require the database to read the skipped rows. The cursor is
+    /// usually obtained from [load_page](Query::load_page) with the
+    /// same filter and order. The primary key is implicitly used as
+    /// the final order term so that the order is total. Order columns
+    /// must not be nullable. The cursor also restricts the objects
+    /// counted, aggregated, updated or deleted by the query. Returns
+    /// `self` as this method is expected to be chained.
+    pub fn after(mut self, cursor: Cursor) -> Query<T> {
+        self.seek = Some(cursor::Seek::After(cursor));
+        self
+    }
+
+    /// Restricts the query to objects before `cursor` in the query's
+    /// order, as for [after](Query::after). Combined with `limit`, the
+    /// objects immediately preceding the cursor are loaded. Results
+    /// are still returned in the query's order, except by
+    /// [iter](Query::iter), which yields them in reverse order.
+    pub fn before(mut self, cursor: Cursor) -> Query<T> {
+        self.seek = Some(cursor::Seek::Before(cursor));
+        self
+    }
+
+    /// Executes the query against `conn` and returns the first result
+    /// (if any) in the query's order. With [before](Query::before),
+    /// this is the object immediately preceding the cursor.
+    pub fn load_first(mut self, conn: &impl ConnectionMethods) -> Result<Option<T>> {
+        self.resolve_seek()?;
+        self.limit = Some(1);
+        self.offset = None;
+        let prefetch = self.prefetch.clone();
+        let obj = self.rows(conn)?.mapped(T::from_row).nth(0)?;
+        if let Some(obj) = &obj {
+            Self::load_prefetched(conn, std::slice::from_ref(obj), &prefetch)?;
+        }
+        Ok(obj)
     }
 
     /// Executes the query against `conn`.
-    pub fn load(self, conn: &impl ConnectionMethods) -> Result<QueryResult<T>> {
+    pub fn load(mut self, conn: &impl ConnectionMethods) -> Result<QueryResult<T>> {
+        let reverse = self.resolve_seek()?;
+        let prefetch = self.prefetch.clone();
+        let mut objs = self
+            .rows(conn)?
+            .mapped(T::from_row)
+            .collect::<QueryResult<T>>()?;
+        if reverse {
+            objs.reverse();
+        }
+        Self::load_prefetched(conn, &objs, &prefetch)?;
+        Ok(objs)
+    }
+
+    /// Executes the query against `conn`, returning the matching
+    /// objects along with [Cursor]s for the first and last of them,
+    /// which may be passed to [after](Query::after) and
+    /// [before](Query::before) to load the adjacent pages. Typically
+    /// used with `limit`. The primary key is implicitly used as the
+    /// final order term. Each order column must be loaded by `T`.
+    pub fn load_page(mut self, conn: &impl ConnectionMethods) -> Result<Page<T>> {
+        self.sort = self.keyset_sort();
+        let reverse = self.resolve_seek()?;
+        let indices = self
+            .sort
+            .iter()
+            .map(|order| {
+                let column = order.column().ok_or_else(|| {
+                    crate::Error::Internal("Cannot paginate by an expression".to_string())
+                })?;
+                T::COLUMNS
+                    .iter()
+                    .position(|col| col.name() == column)
+                    .ok_or_else(|| {
+                        crate::Error::Internal(format!(
+                            "Cannot paginate by column {} which is not loaded",
+                            column
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<usize>>>()?;
+        let prefetch = self.prefetch.clone();
+        let mut rows = self.rows(conn)?;
+        let mut objects = Vec::new();
+        let mut first = None;
+        let mut last = None;
+        while let Some(row) = rows.next()? {
+            let values = indices
+                .iter()
+                .map(|&i| Ok(row.get(i, T::COLUMNS[i].ty().clone())?.into()))
+                .collect::<Result<Vec<SqlVal>>>()?;
+            if first.is_none() {
+                first = Some(Cursor::new(values.clone()));
+            }
+            last = Some(Cursor::new(values));
+            objects.push(T::from_row(row)?);
+        }
+        if reverse {
+            objects.reverse();
+            std::mem::swap(&mut first, &mut last);
+        }
+        Self::load_prefetched(conn, &objects, &prefetch)?;
+        Ok(Page {
+            objects,
+            first,
+            last,
+        })
+    }
+
+    /// Executes the query against `conn` and returns an iterator
+    /// over the results. Unlike [load](Query::load), objects are
+    /// constructed lazily as rows are read from the database, so the
+    /// full result need not be held in memory. On Postgres, the rows
+    /// are read in batches through a server-side cursor within a
+    /// transaction (or a savepoint, within a
+    /// [Transaction](crate::db::Transaction)).
+    ///
+    /// The connection may not be used for anything else until the
+    /// iterator is dropped: other queries, including loading a
+    /// [ForeignKey](crate::ForeignKey) which has not been prefetched,
+    /// return an error. For the same reason prefetching is not
+    /// supported, and is an
+    /// [UnsupportedForPrefetch](crate::Error::UnsupportedForPrefetch)
+    /// error.
+    pub fn iter<'c>(
+        self,
+        conn: &'c impl ConnectionMethods,
+    ) -> Result<impl FallibleIterator<Item = T, Error = crate::Error> + 'c>
+    where
+        T: 'c,
+    {
+        if !self.prefetch.is_empty() {
+            return Err(crate::Error::UnsupportedForPrefetch("Query::iter"));
+        }
+        Ok(conn
+            .query_stream(T::COLUMNS, self.into_select_query()?)?
+            .mapped(T::from_row))
+    }
+
+    /// Renders the query for `backend` without executing it,
+    /// returning the SQL and the values for its placeholders (values
+    /// which the backend writes inline, such as integers, are not
+    /// included). Intended for debugging and tests. Prefetching is
+    /// ignored.
+    pub fn to_sql(self, backend: &(impl Backend + ?Sized)) -> Result<(String, Vec<SqlVal>)> {
+        Ok(backend.sql_for_select(T::COLUMNS, self.into_select_query()?))
+    }
+
+    /// Returns the database's plan for executing the query (EXPLAIN in
+    /// SQL), as text. The format of the plan depends on the
+    /// backend. The query is not executed.
+    pub fn explain(self, conn: &impl ConnectionMethods) -> Result<String> {
+        conn.explain(T::COLUMNS, self.into_select_query()?)
+    }
+
+    /// Combines the results of this query with those of `other`,
+    /// omitting duplicates (UNION in SQL). Each query keeps its own
+    /// filter, order, limit and offset, while those set on the
+    /// returned query apply to the combined results. For example,
+    /// `a.union(b).order_asc(..).limit(10)` loads the first 10 of the
+    /// combined results. Relationships prefetched for either query
+    /// are prefetched for the combined results. Counting,
+    /// aggregates, updates, deletes, joins and prepared queries are
+    /// not supported for the combined query.
+    pub fn union(self, other: Query<T>) -> Query<T> {
+        self.compound(SetOperation::Union, other)
+    }
+
+    /// Like [union](Query::union), but the results are those of both
+    /// this query and `other` (INTERSECT in SQL).
+    pub fn intersect(self, other: Query<T>) -> Query<T> {
+        self.compound(SetOperation::Intersect, other)
+    }
+
+    /// Like [union](Query::union), but the results are those of this
+    /// query which are not results of `other` (EXCEPT in SQL).
+    pub fn except(self, other: Query<T>) -> Query<T> {
+        self.compound(SetOperation::Except, other)
+    }
+
+    fn compound(mut self, op: SetOperation, mut other: Query<T>) -> Query<T> {
+        let mut query = Query::with_table(self.table.clone());
+        // Relationships prefetched for either operand are prefetched
+        // for the combined results.
+        query.prefetch = std::mem::take(&mut self.prefetch);
+        for relation in std::mem::take(&mut other.prefetch) {
+            if !query.prefetch.contains(&relation) {
+                query.prefetch.push(relation);
+            }
+        }
+        query.compound = Some(Box::new(Compound {
+            left: self,
+            op,
+            right: other,
+        }));
+        query
+    }
+
+    /// Fails if the query is compound, for operations which support
+    /// only a single table.
+    fn check_not_compound(&self, operation: &'static str) -> Result<()> {
+        match self.compound {
+            Some(_) => Err(crate::Error::UnsupportedForCompound(operation)),
+            None => Ok(()),
+        }
+    }
+
+    /// Executes the query, whose cursor (if any) must already have
+    /// been resolved, and returns the rows of `T::COLUMNS`.
+    fn rows<'c>(self, conn: &'c impl ConnectionMethods) -> Result<RawQueryResult<'c>> {
+        if self.compound.is_some() {
+            return conn.query_compound(T::COLUMNS, self.into_select_query()?);
+        }
         let sort = if self.sort.is_empty() {
             None
         } else {
             Some(self.sort.as_slice())
         };
-        conn.query(&self.table, T::COLUMNS, self.filter, self.limit, self.offset, sort)?
-            .mapped(T::from_row)
-            .collect()
+        conn.query(
+            &self.table,
+            T::COLUMNS,
+            self.distinct,
+            self.filter,
+            self.group,
+            self.limit,
+            self.offset,
+            sort,
+        )
+    }
+
+    fn into_select_query(mut self) -> Result<SelectQuery> {
+        // The order of an operand's results does not matter, so the
+        // reversal for `before` can be ignored.
+        self.resolve_seek()?;
+        let source = match self.compound.take() {
+            Some(compound) => QuerySource::Compound(
+                self.table,
+                Box::new(compound.left.into_select_query()?),
+                compound.op,
+                Box::new(compound.right.into_select_query()?),
+            ),
+            None => QuerySource::Table(self.table),
+        };
+        Ok(SelectQuery {
+            source,
+            distinct: self.distinct,
+            expr: self.filter,
+            group: self.group,
+            limit: self.limit,
+            offset: self.offset,
+            sort: self.sort,
+        })
+    }
+
+    /// The order terms of the query with the primary key appended
+    /// (unless already present), so that the order is total.
+    fn keyset_sort(&self) -> Vec<Order> {
+        let pkcol = <T::DBO as DataObject>::PKCOL;
+        let mut sort = self.sort.clone();
+        if !sort.iter().any(|order| order.column() == Some(pkcol)) {
+            sort.push(Order::asc(pkcol));
+        }
+        sort
+    }
+
+    /// Applies the cursor set with `after` or `before` (if any) to
+    /// the filter and order of the query. Returns true if the order
+    /// was reversed, in which case results must be reversed after
+    /// loading.
+    fn resolve_seek(&mut self) -> Result<bool> {
+        let (cursor, before) = match self.seek.take() {
+            Some(cursor::Seek::After(cursor)) => (cursor, false),
+            Some(cursor::Seek::Before(cursor)) => (cursor, true),
+            None => return Ok(false),
+        };
+        let sort = self.keyset_sort();
+        let seek = cursor.seek_expr(&sort, before)?;
+        self.filter = Some(match self.filter.take() {
+            Some(filter) => BoolExpr::And(Box::new(filter), Box::new(seek)),
+            None => seek,
+        });
+        self.sort = if before {
+            sort.into_iter().map(Order::reversed).collect()
+        } else {
+            sort
+        };
+        Ok(before)
+    }
+
+    /// Prepares the query for repeated execution against `conn`. The
+    /// SQL is rendered and the statement prepared only once. The
+    /// query's filter may contain [Expr::Placeholder]s, whose values
+    /// are given with [PreparedQuery::bind] each time the query is
+    /// executed. Prefetching is not supported and is ignored.
+    pub fn prepare<'c>(mut self, conn: &'c impl ConnectionMethods) -> Result<PreparedQuery<'c, T>> {
+        self.check_not_compound("prepare")?;
+        let reverse = self.resolve_seek()?;
+        let sort = if self.sort.is_empty() {
+            None
+        } else {
+            Some(self.sort.as_slice())
+        };
+        let stmt = conn.prepare_query(
+            &self.table,
+            T::COLUMNS,
+            self.distinct,
+            self.filter,
+            self.group,
+            self.limit,
+            self.offset,
+            sort,
+        )?;
+        Ok(PreparedQuery::new(stmt, reverse))
+    }
+
+    fn load_prefetched(
+        conn: &impl ConnectionMethods,
+        objs: &[T],
+        relations: &[&'static str],
+    ) -> Result<()> {
+        if objs.is_empty() {
+            return Ok(());
+        }
+        for relation in relations {
+            T::prefetch(conn, objs, relation)?;
+        }
+        Ok(())
+    }
+
+    /// Executes the query against `conn` and returns whether any
+    /// objects match, without loading them. Grouping, limit, offset,
+    /// and ordering are ignored.
+    pub fn exists(mut self, conn: &impl ConnectionMethods) -> Result<bool> {
+        self.check_not_compound("exists")?;
+        self.resolve_seek()?;
+        conn.exists(&self.table, self.filter)
+    }
+
+    /// Executes the query against `conn` and returns the number of
+    /// matching objects. Grouping, limit, offset, and ordering are
+    /// ignored, as is [distinct](Self::distinct): the count is of
+    /// objects, not of distinct results of `T`.
+    pub fn count(self, conn: &impl ConnectionMethods) -> Result<i64> {
+        i64::from_sql(self.aggregate(conn, Aggregate::count())?)
+    }
+
+    /// Executes the query against `conn` and returns the sum of
+    /// `field` over all matching objects, or `None` if there are
+    /// none. Grouping, limit, offset, and ordering are ignored.
+    pub fn sum<F>(
+        self,
+        conn: &impl ConnectionMethods,
+        field: FieldExpr<F>,
+    ) -> Result<Option<F::Output>>
+    where
+        F: DataSum + Into<SqlVal>,
+    {
+        let ty = <F::Output as FieldType>::SQLTYPE;
+        Option::from_sql(self.aggregate(
+            conn,
+            Aggregate::new(AggregateFunction::Sum, field.name(), ty),
+        )?)
+    }
+
+    /// Executes the query against `conn` and returns the average of
+    /// `field` over all matching objects, or `None` if there are
+    /// none. Grouping, limit, offset, and ordering are ignored.
+    pub fn avg<F>(self, conn: &impl ConnectionMethods, field: FieldExpr<F>) -> Result<Option<f64>>
+    where
+        F: DataSum + Into<SqlVal>,
+    {
+        let agg = Aggregate::new(AggregateFunction::Avg, field.name(), SqlType::Real);
+        Option::from_sql(self.aggregate(conn, agg)?)
+    }
+
+    /// Executes the query against `conn` and returns the smallest
+    /// value of `field` among matching objects, or `None` if there
+    /// are none. Grouping, limit, offset, and ordering are ignored.
+    pub fn min<F, U>(self, conn: &impl ConnectionMethods, field: FieldExpr<F>) -> Result<Option<U>>
+    where
+        F: DataOrd<U> + FieldType + Into<SqlVal>,
+        U: FromSql,
+    {
+        let agg = Aggregate::new(AggregateFunction::Min, field.name(), F::SQLTYPE);
+        Option::from_sql(self.aggregate(conn, agg)?)
+    }
+
+    /// Executes the query against `conn` and returns the largest
+    /// value of `field` among matching objects, or `None` if there
+    /// are none. Grouping, limit, offset, and ordering are ignored.
+    pub fn max<F, U>(self, conn: &impl ConnectionMethods, field: FieldExpr<F>) -> Result<Option<U>>
+    where
+        F: DataOrd<U> + FieldType + Into<SqlVal>,
+        U: FromSql,
+    {
+        let agg = Aggregate::new(AggregateFunction::Max, field.name(), F::SQLTYPE);
+        Option::from_sql(self.aggregate(conn, agg)?)
+    }
+
+    fn aggregate(mut self, conn: &impl ConnectionMethods, agg: Aggregate) -> Result<SqlVal> {
+        self.check_not_compound("aggregate")?;
+        self.resolve_seek()?;
+        conn.aggregate(&self.table, agg, self.filter)
+    }
+
+    /// Executes the query against `conn` and updates all matching
+    /// objects without loading them. `assignments` is given the
+    /// fields of the model and returns the assignments to make, for
+    /// example `|f| f.published().set(true)`. Multiple assignments
+    /// may be returned as an array. Returns the number of objects
+    /// updated.
+    pub fn update<F, A>(mut self, conn: &impl ConnectionMethods, assignments: F) -> Result<usize>
+    where
+        F: FnOnce(<T::DBO as DataObject>::Fields) -> A,
+        A: Into<Vec<Assignment>>,
+    {
+        self.check_not_compound("update")?;
+        self.resolve_seek()?;
+        let assignments: Vec<Assignment> = assignments(Default::default()).into();
+        if assignments.is_empty() {
+            return Ok(0);
+        }
+        if assignments
+            .iter()
+            .any(|a| matches!(a.value, Expr::OutOfRange))
+        {
+            return Err(crate::Error::OutOfRange);
+        }
+        conn.update_where(
+            &self.table,
+            assignments,
+            self.filter.unwrap_or(BoolExpr::True),
+        )
     }
 
     /// Executes the query against `conn` and deletes all matching objects.
-    pub fn delete(self, conn: &impl ConnectionMethods) -> Result<usize> {
+    pub fn delete(mut self, conn: &impl ConnectionMethods) -> Result<usize> {
+        self.check_not_compound("delete")?;
+        self.resolve_seek()?;
         conn.delete_where(&self.table, self.filter.unwrap_or(BoolExpr::True))
     }
 }
+
+impl<T: DataObject> Query<T> {
+    /// Joins the query with the model referred to by the
+    /// [ForeignKey](crate::ForeignKey) field selected by `fkey`, for
+    /// example `join::<Blog>(|f| f.blog())`, so that each object is
+    /// loaded together with its referent in a single query. Objects
+    /// whose referent does not exist are omitted; see
+    /// [left_join](Query::left_join) to include them. Distinct,
+    /// grouping and prefetching are not supported, and loading such a
+    /// query is an [UnsupportedForJoin](crate::Error::UnsupportedForJoin)
+    /// error.
+    pub fn join<U: DataObject>(
+        self,
+        fkey: impl FnOnce(T::Fields) -> FieldExpr<ForeignKey<U>>,
+    ) -> JoinQuery<T, U> {
+        JoinQuery::new(self, fkey(Default::default()))
+    }
+
+    /// Like [join](Query::join), but performs a left join so that
+    /// every matched object is loaded, along with `None` if its
+    /// referent does not exist.
+    pub fn left_join<U: DataObject>(
+        self,
+        fkey: impl FnOnce(T::Fields) -> FieldExpr<ForeignKey<U>>,
+    ) -> JoinQuery<T, Option<U>> {
+        JoinQuery::new(self, fkey(Default::default()))
+    }
+}
+
+/// The type loaded for the joined model of a [JoinQuery]. This is
+/// either the model itself, for an inner join, or an `Option` of it,
+/// for a left join.
+pub trait JoinResult: Sized {
+    /// The joined model.
+    type DBO: DataObject;
+    /// Whether this is the result of a left join.
+    const LEFT: bool;
+    fn from_row<'a>(row: &(dyn BackendRow + 'a)) -> Result<Self>;
+}
+impl<U: DataObject> JoinResult for U {
+    type DBO = U;
+    const LEFT: bool = false;
+    fn from_row<'a>(row: &(dyn BackendRow + 'a)) -> Result<Self> {
+        <U as DataResult>::from_row(row)
+    }
+}
+impl<U: DataObject> JoinResult for Option<U> {
+    type DBO = U;
+    const LEFT: bool = true;
+    fn from_row<'a>(row: &(dyn BackendRow + 'a)) -> Result<Self> {
+        // The primary key is null only if there was no match
+        let pkidx = U::COLUMNS
+            .iter()
+            .position(|col| col.name() == U::PKCOL)
+            .ok_or_else(|| crate::Error::Internal(format!("No column for pk {}", U::PKCOL)))?;
+        match row.get(pkidx, U::COLUMNS[pkidx].ty().clone())? {
+            SqlValRef::Null => Ok(None),
+            _ => U::from_row(row).map(Some),
+        }
+    }
+}
+
+/// A query which loads objects of type `T` together with the objects
+/// of type `U` their foreign key refers to. Created by
+/// [Query::join] or [Query::left_join].
+pub struct JoinQuery<T: DataObject, U: JoinResult> {
+    query: Query<T>,
+    fkey: &'static str,
+    phantom: PhantomData<U>,
+}
+impl<T: DataObject, U: JoinResult> JoinQuery<T, U> {
+    fn new(query: Query<T>, fkey: FieldExpr<ForeignKey<U::DBO>>) -> Self {
+        JoinQuery {
+            query,
+            fkey: fkey.name(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Executes the query against `conn`.
+    pub fn load(self, conn: &impl ConnectionMethods) -> Result<QueryResult<(T, U)>> {
+        let mut query = self.query;
+        query.check_not_compound("join")?;
+        if query.distinct {
+            return Err(crate::Error::UnsupportedForJoin("distinct"));
+        }
+        if query.group.is_some() {
+            return Err(crate::Error::UnsupportedForJoin("group_by"));
+        }
+        if !query.prefetch.is_empty() {
+            return Err(crate::Error::UnsupportedForJoin("prefetch"));
+        }
+        let reverse = query.resolve_seek()?;
+        let col1 = Column::new(T::TABLE, self.fkey);
+        let col2 = Column::new(<U::DBO as DataObject>::TABLE, <U::DBO as DataObject>::PKCOL);
+        let join_table = <U::DBO as DataObject>::TABLE;
+        let join = if U::LEFT {
+            Join::Left {
+                join_table,
+                col1,
+                col2,
+            }
+        } else {
+            Join::Inner {
+                join_table,
+                col1,
+                col2,
+            }
+        };
+        let sort = if query.sort.is_empty() {
+            None
+        } else {
+            Some(query.sort.as_slice())
+        };
+        let ncols = T::COLUMNS.len();
+        let join_ncols = <U::DBO as DataResult>::COLUMNS.len();
+        let mut pairs = conn
+            .query_join(
+                &query.table,
+                T::COLUMNS,
+                SelectJoin {
+                    join,
+                    columns: <U::DBO as DataResult>::COLUMNS,
+                },
+                query.filter,
+                query.limit,
+                query.offset,
+                sort,
+            )?
+            .mapped(|row| {
+                Ok((
+                    T::from_row(&OffsetRow::new(row, 0, ncols))?,
+                    U::from_row(&OffsetRow::new(row, ncols, join_ncols))?,
+                ))
+            })
+            .collect::<QueryResult<(T, U)>>()?;
+        if reverse {
+            pairs.reverse();
+        }
+        Ok(pairs)
+    }
+}
@@ -0,0 +1,66 @@
+//! Queries which are prepared once and executed repeatedly. See
+//! [Query::prepare](super::Query::prepare).
+
+use crate::db::{BackendRows, PreparedStatement, QueryResult};
+use crate::{DataResult, Result, SqlVal};
+use fallible_iterator::FallibleIterator;
+use std::marker::PhantomData;
+
+/// A [Query](super::Query) which has been prepared for repeated
+/// execution with [Query::prepare](super::Query::prepare). The SQL is
+/// rendered and the statement prepared by the database only once.
+///
+/// Values for the query's placeholders ([Expr::Placeholder](super::Expr::Placeholder))
+/// are supplied with [bind](PreparedQuery::bind) each time it is executed.
+pub struct PreparedQuery<'c, T: DataResult> {
+    stmt: Box<dyn PreparedStatement + 'c>,
+    reverse: bool,
+    phantom: PhantomData<T>,
+}
+impl<'c, T: DataResult> PreparedQuery<'c, T> {
+    pub(super) fn new(stmt: Box<dyn PreparedStatement + 'c>, reverse: bool) -> Self {
+        PreparedQuery {
+            stmt,
+            reverse,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Supplies the values for the query's placeholders, in the order
+    /// in which they appear in the query.
+    pub fn bind(&mut self, args: impl IntoIterator<Item = SqlVal>) -> BoundQuery<'_, 'c, T> {
+        BoundQuery {
+            query: self,
+            args: args.into_iter().collect(),
+        }
+    }
+
+    /// Executes a query which has no placeholders. Shorthand for
+    /// `bind([]).load()`.
+    pub fn load(&mut self) -> Result<QueryResult<T>> {
+        self.bind([]).load()
+    }
+}
+
+/// A [PreparedQuery] with values for its placeholders, as returned
+/// by [PreparedQuery::bind].
+pub struct BoundQuery<'p, 'c, T: DataResult> {
+    query: &'p mut PreparedQuery<'c, T>,
+    args: Vec<SqlVal>,
+}
+impl<T: DataResult> BoundQuery<'_, '_, T> {
+    /// Executes the query.
+    pub fn load(self) -> Result<QueryResult<T>> {
+        let reverse = self.query.reverse;
+        let mut objs = self
+            .query
+            .stmt
+            .query(self.args)?
+            .mapped(T::from_row)
+            .collect::<QueryResult<T>>()?;
+        if reverse {
+            objs.reverse();
+        }
+        Ok(objs)
+    }
+}
@@ -0,0 +1,152 @@
+//! Queries for a subset of a model's columns, without a
+//! `DataResult` type. Most users will use the `select!` macro.
+
+use super::{BoolExpr, Expr, FieldExpr, Order, OrderDirection, TblName};
+use crate::db::{BackendRow, BackendRows, Column, ConnectionMethods, QueryResult};
+use crate::{Error, FieldType, Result};
+use fallible_iterator::FallibleIterator;
+use std::borrow::Cow;
+
+/// A tuple of [FieldExpr]s which may be selected by a [Select]
+/// query. Implemented for tuples of up to 12 fields.
+pub trait SelectColumns {
+    /// The tuple of field values loaded for each row.
+    type Output;
+    /// The columns to select.
+    fn columns(&self) -> Vec<Column>;
+    /// Loads the field values from a row.
+    fn from_row<'a>(row: &(dyn BackendRow + 'a)) -> Result<Self::Output>;
+}
+
+macro_rules! select_columns_tuple {
+    ($len:expr, $($t:ident $i:tt),+) => {
+        impl<$($t: FieldType),+> SelectColumns for ($(FieldExpr<$t>,)+) {
+            type Output = ($($t,)+);
+            fn columns(&self) -> Vec<Column> {
+                vec![$(Column::new(self.$i.name(), $t::SQLTYPE)),+]
+            }
+            fn from_row<'a>(row: &(dyn BackendRow + 'a)) -> Result<Self::Output> {
+                if row.len() != $len {
+                    return Err(Error::BoundsError(
+                        "Found unexpected number of columns in row for select".to_string(),
+                    ));
+                }
+                Ok(($($t::from_sql_ref(row.get($i, $t::SQLTYPE)?)?,)+))
+            }
+        }
+    };
+}
+
+select_columns_tuple!(1, A 0);
+select_columns_tuple!(2, A 0, B 1);
+select_columns_tuple!(3, A 0, B 1, C 2);
+select_columns_tuple!(4, A 0, B 1, C 2, D 3);
+select_columns_tuple!(5, A 0, B 1, C 2, D 3, E 4);
+select_columns_tuple!(6, A 0, B 1, C 2, D 3, E 4, F 5);
+select_columns_tuple!(7, A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+select_columns_tuple!(8, A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+select_columns_tuple!(9, A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+select_columns_tuple!(10, A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+select_columns_tuple!(11, A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+select_columns_tuple!(12, A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+
+/// Representation of a database query selecting some of the columns
+/// of a table and loading each row as a tuple. Usually constructed
+/// with the `select!` macro.
+pub struct Select<S: SelectColumns> {
+    table: TblName,
+    columns: S,
+    filter: Option<BoolExpr>,
+    distinct: bool,
+    limit: Option<i32>,
+    offset: Option<i32>,
+    sort: Vec<Order>,
+}
+impl<S: SelectColumns> Select<S> {
+    /// Creates a query selecting `columns` from all rows of
+    /// `table`. The set of matched rows can be restricted with
+    /// `filter` and `limit`.
+    pub fn new(table: &'static str, columns: S) -> Select<S> {
+        Select {
+            table: Cow::Borrowed(table),
+            columns,
+            filter: None,
+            distinct: false,
+            limit: None,
+            offset: None,
+            sort: Vec::new(),
+        }
+    }
+
+    /// Restricts the query to matching only rows for which `expr` is
+    /// true. Returns `self` as this method is expected to be chained.
+    pub fn filter(mut self, expr: BoolExpr) -> Select<S> {
+        self.filter = Some(expr);
+        self
+    }
+
+    /// Omits duplicate rows (SELECT DISTINCT in SQL). Returns `self`
+    /// as this method is expected to be chained.
+    pub fn distinct(mut self) -> Select<S> {
+        self.distinct = true;
+        self
+    }
+
+    /// Limits the query to matching the first `lim` rows. Returns
+    /// `self` as this method is expected to be chained.
+    pub fn limit(mut self, lim: i32) -> Select<S> {
+        self.limit = Some(lim);
+        self
+    }
+
+    /// Skips the first `off` rows before returning them. Returns
+    /// `self` as this method is expected to be chained.
+    pub fn offset(mut self, off: i32) -> Select<S> {
+        self.offset = Some(off);
+        self
+    }
+
+    /// Orders the results as with [Query::order](super::Query::order).
+    pub fn order(mut self, column: &'static str, direction: OrderDirection) -> Select<S> {
+        self.sort.push(Order::new(Expr::Column(column), direction));
+        self
+    }
+
+    /// Orders the results as with [Query::order_by](super::Query::order_by).
+    pub fn order_by(mut self, order: impl IntoIterator<Item = Order>) -> Select<S> {
+        self.sort.extend(order);
+        self
+    }
+
+    /// Shorthand for `order(column, OrderDirection::Ascending)`
+    pub fn order_asc(self, column: &'static str) -> Select<S> {
+        self.order(column, OrderDirection::Ascending)
+    }
+
+    /// Shorthand for `order(column, OrderDirection::Descending)`
+    pub fn order_desc(self, column: &'static str) -> Select<S> {
+        self.order(column, OrderDirection::Descending)
+    }
+
+    /// Executes the query against `conn`.
+    pub fn load(self, conn: &impl ConnectionMethods) -> Result<QueryResult<S::Output>> {
+        let columns = self.columns.columns();
+        let sort = if self.sort.is_empty() {
+            None
+        } else {
+            Some(self.sort.as_slice())
+        };
+        conn.query(
+            &self.table,
+            &columns,
+            self.distinct,
+            self.filter,
+            None,
+            self.limit,
+            self.offset,
+            sort,
+        )?
+        .mapped(S::from_row)
+        .collect()
+    }
+}
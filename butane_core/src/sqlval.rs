@@ -7,13 +7,17 @@ use std::fmt;
 #[cfg(feature = "pg")]
 use crate::custom::SqlTypeCustom;
 
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
+
 #[cfg(feature = "datetime")]
-use chrono::naive::NaiveDateTime;
+use chrono::{naive::NaiveDateTime, DateTime, NaiveDate, NaiveTime, Utc};
 
 #[derive(Clone, Debug)]
 pub enum SqlValRef<'a> {
     Null,
     Bool(bool),
+    SmallInt(i16),
     Int(i32),
     BigInt(i64),
     Real(f64),
@@ -21,6 +25,15 @@ pub enum SqlValRef<'a> {
     Blob(&'a [u8]),
     #[cfg(feature = "datetime")]
     Timestamp(NaiveDateTime), // NaiveDateTime is Copy
+    #[cfg(feature = "datetime")]
+    Date(NaiveDate),
+    #[cfg(feature = "datetime")]
+    Time(NaiveTime),
+    #[cfg(feature = "datetime")]
+    TimestampTz(DateTime<Utc>),
+    #[cfg(feature = "decimal")]
+    Numeric(Decimal),
+    Json(serde_json::Value),
     Custom(SqlValRefCustom<'a>),
 }
 impl SqlValRef<'_> {
@@ -29,13 +42,23 @@ impl SqlValRef<'_> {
         match self {
             SqlValRef::Null => None,
             SqlValRef::Bool(_) => Some(SqlType::Bool),
+            SqlValRef::SmallInt(_) => Some(SqlType::SmallInt),
             SqlValRef::Int(_) => Some(SqlType::Int),
             SqlValRef::BigInt(_) => Some(SqlType::BigInt),
             SqlValRef::Real(_) => Some(SqlType::Real),
             SqlValRef::Text(_) => Some(SqlType::Text),
             #[cfg(feature = "datetime")]
             SqlValRef::Timestamp(_) => Some(SqlType::Timestamp),
+            #[cfg(feature = "datetime")]
+            SqlValRef::Date(_) => Some(SqlType::Date),
+            #[cfg(feature = "datetime")]
+            SqlValRef::Time(_) => Some(SqlType::Time),
+            #[cfg(feature = "datetime")]
+            SqlValRef::TimestampTz(_) => Some(SqlType::TimestampTz),
+            #[cfg(feature = "decimal")]
+            SqlValRef::Numeric(_) => Some(SqlType::Numeric(None)),
             SqlValRef::Blob(_) => Some(SqlType::Blob),
+            SqlValRef::Json(_) => Some(SqlType::Json),
             #[cfg(feature = "pg")]
             SqlValRef::Custom(c) => match c {
                 SqlValRefCustom::PgToSql { ty, .. } => {
@@ -61,6 +84,7 @@ impl SqlValRef<'_> {
 pub enum SqlVal {
     Null,
     Bool(bool),
+    SmallInt(i16),
     Int(i32),
     BigInt(i64),
     Real(f64),
@@ -68,6 +92,15 @@ pub enum SqlVal {
     Blob(Vec<u8>),
     #[cfg(feature = "datetime")]
     Timestamp(NaiveDateTime),
+    #[cfg(feature = "datetime")]
+    Date(NaiveDate),
+    #[cfg(feature = "datetime")]
+    Time(NaiveTime),
+    #[cfg(feature = "datetime")]
+    TimestampTz(DateTime<Utc>),
+    #[cfg(feature = "decimal")]
+    Numeric(Decimal),
+    Json(serde_json::Value),
     Custom(Box<SqlValCustom>),
 }
 impl SqlVal {
@@ -83,12 +116,14 @@ impl SqlVal {
     }
     pub fn integer(&self) -> Result<i32> {
         match self {
+            SqlVal::SmallInt(val) => Ok(*val as i32),
             SqlVal::Int(val) => Ok(*val),
             _ => Err(CannotConvertSqlVal(SqlType::Int, self.clone())),
         }
     }
     pub fn bigint(&self) -> Result<i64> {
         match self {
+            SqlVal::SmallInt(val) => Ok(*val as i64),
             SqlVal::Int(val) => Ok(*val as i64),
             SqlVal::BigInt(val) => Ok(*val),
             _ => Err(CannotConvertSqlVal(SqlType::BigInt, self.clone())),
@@ -134,6 +169,9 @@ impl SqlVal {
     pub fn is_compatible(&self, t: &SqlType, null_allowed: bool) -> bool {
         match self.sqltype() {
             None => null_allowed,
+            // A value doesn't know the precision of its column
+            #[cfg(feature = "decimal")]
+            Some(SqlType::Numeric(_)) => matches!(t, SqlType::Numeric(_)),
             Some(self_ty) => *t == self_ty,
         }
     }
@@ -144,13 +182,23 @@ impl SqlVal {
         match self {
             SqlVal::Null => None,
             SqlVal::Bool(_) => Some(SqlType::Bool),
+            SqlVal::SmallInt(_) => Some(SqlType::SmallInt),
             SqlVal::Int(_) => Some(SqlType::Int),
             SqlVal::BigInt(_) => Some(SqlType::BigInt),
             SqlVal::Real(_) => Some(SqlType::Real),
             SqlVal::Text(_) => Some(SqlType::Text),
             #[cfg(feature = "datetime")]
             SqlVal::Timestamp(_) => Some(SqlType::Timestamp),
+            #[cfg(feature = "datetime")]
+            SqlVal::Date(_) => Some(SqlType::Date),
+            #[cfg(feature = "datetime")]
+            SqlVal::Time(_) => Some(SqlType::Time),
+            #[cfg(feature = "datetime")]
+            SqlVal::TimestampTz(_) => Some(SqlType::TimestampTz),
+            #[cfg(feature = "decimal")]
+            SqlVal::Numeric(_) => Some(SqlType::Numeric(None)),
             SqlVal::Blob(_) => Some(SqlType::Blob),
+            SqlVal::Json(_) => Some(SqlType::Json),
             #[cfg(feature = "pg")]
             SqlVal::Custom(c) => match c.as_ref() {
                 SqlValCustom::Pg { ty, .. } => Some(SqlType::Custom(SqlTypeCustom::Pg(ty.clone()))),
@@ -166,6 +214,7 @@ impl fmt::Display for SqlVal {
         match &self {
             SqlVal::Null => f.write_str("NULL"),
             SqlVal::Bool(val) => val.fmt(f),
+            SmallInt(val) => val.fmt(f),
             Int(val) => val.fmt(f),
             BigInt(val) => val.fmt(f),
             Real(val) => val.fmt(f),
@@ -173,15 +222,53 @@ impl fmt::Display for SqlVal {
             Blob(val) => f.write_str(&hex::encode(val)),
             #[cfg(feature = "datetime")]
             Timestamp(val) => val.format("%+").fmt(f),
+            #[cfg(feature = "datetime")]
+            Date(val) => val.fmt(f),
+            #[cfg(feature = "datetime")]
+            Time(val) => val.fmt(f),
+            #[cfg(feature = "datetime")]
+            TimestampTz(val) => val.to_rfc3339().fmt(f),
+            #[cfg(feature = "decimal")]
+            Numeric(val) => val.fmt(f),
+            Json(val) => val.fmt(f),
             Custom(val) => val.fmt(f),
         }
     }
 }
 
+/// A `SqlVal` which may be used as the key of a `HashMap` or
+/// `HashSet`, for matching primary keys with the rows they identify.
+#[derive(PartialEq)]
+pub(crate) struct SqlValKey(pub(crate) SqlVal);
+impl Eq for SqlValKey {}
+impl std::hash::Hash for SqlValKey {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(&self.0).hash(state);
+        match &self.0 {
+            SqlVal::Bool(val) => val.hash(state),
+            SqlVal::SmallInt(val) => val.hash(state),
+            SqlVal::Int(val) => val.hash(state),
+            SqlVal::BigInt(val) => val.hash(state),
+            SqlVal::Text(val) => val.hash(state),
+            SqlVal::Blob(val) => val.hash(state),
+            // Values of other types are rarely primary keys. Equal
+            // values need only hash alike, so the discriminant is
+            // enough.
+            _ => (),
+        }
+    }
+}
+
 /// Used to convert another type to a `SqlVal` or `SqlValRef`.
 pub trait ToSql {
     fn to_sql(&self) -> SqlVal;
     fn to_sql_ref(&self) -> SqlValRef<'_>;
+    /// Like `to_sql_ref`, but fails rather than panicking if the value
+    /// cannot be stored. Used when saving an object. The default
+    /// implementation simply calls `to_sql_ref`.
+    fn try_to_sql_ref(&self) -> Result<SqlValRef<'_>> {
+        Ok(self.to_sql_ref())
+    }
     /// The default implementation simply calls `to_sql`. Provide an
     /// alternative implementation if greater efficiency can be
     /// realized by consuming self.
@@ -230,6 +317,7 @@ impl From<SqlValRef<'_>> for SqlVal {
         match vref {
             Null => SqlVal::Null,
             Bool(v) => SqlVal::Bool(v),
+            SmallInt(v) => SqlVal::SmallInt(v),
             Int(v) => SqlVal::Int(v),
             BigInt(v) => SqlVal::BigInt(v),
             Real(v) => SqlVal::Real(v),
@@ -237,6 +325,15 @@ impl From<SqlValRef<'_>> for SqlVal {
             Blob(v) => SqlVal::Blob(v.into()),
             #[cfg(feature = "datetime")]
             Timestamp(v) => SqlVal::Timestamp(v),
+            #[cfg(feature = "datetime")]
+            Date(v) => SqlVal::Date(v),
+            #[cfg(feature = "datetime")]
+            Time(v) => SqlVal::Time(v),
+            #[cfg(feature = "datetime")]
+            TimestampTz(v) => SqlVal::TimestampTz(v),
+            #[cfg(feature = "decimal")]
+            Numeric(v) => SqlVal::Numeric(v),
+            Json(v) => SqlVal::Json(v),
             Custom(v) => SqlVal::Custom(Box::new(v.into())),
         }
     }
@@ -248,6 +345,7 @@ impl<'a> From<&'a SqlVal> for SqlValRef<'a> {
         match val {
             Null => SqlValRef::Null,
             Bool(v) => SqlValRef::Bool(*v),
+            SmallInt(v) => SqlValRef::SmallInt(*v),
             Int(v) => SqlValRef::Int(*v),
             BigInt(v) => SqlValRef::BigInt(*v),
             Real(v) => SqlValRef::Real(*v),
@@ -255,6 +353,15 @@ impl<'a> From<&'a SqlVal> for SqlValRef<'a> {
             Blob(v) => SqlValRef::Blob(v.as_ref()),
             #[cfg(feature = "datetime")]
             Timestamp(v) => SqlValRef::Timestamp(*v),
+            #[cfg(feature = "datetime")]
+            Date(v) => SqlValRef::Date(*v),
+            #[cfg(feature = "datetime")]
+            Time(v) => SqlValRef::Time(*v),
+            #[cfg(feature = "datetime")]
+            TimestampTz(v) => SqlValRef::TimestampTz(*v),
+            #[cfg(feature = "decimal")]
+            Numeric(v) => SqlValRef::Numeric(*v),
+            Json(v) => SqlValRef::Json(v.clone()),
             Custom(v) => SqlValRef::Custom(v.as_valref()),
         }
     }
@@ -343,18 +450,83 @@ macro_rules! impl_prim_sql {
     };
 }
 
+/// Implements `FromSql` for an integer type, accepting a value of
+/// any integer SqlType. Values which do not fit in the type are an
+/// `OutOfRange` error rather than being truncated.
+macro_rules! impl_int_from_sql {
+    ($prim:ty, $sqltype:ident) => {
+        impl FromSql for $prim {
+            fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+                use std::convert::TryFrom;
+                let val: i64 = match valref {
+                    SqlValRef::SmallInt(val) => val.into(),
+                    SqlValRef::Int(val) => val.into(),
+                    SqlValRef::BigInt(val) => val,
+                    _ => return sql_conv_err!(valref, $sqltype),
+                };
+                <$prim>::try_from(val).map_err(|_| crate::Error::OutOfRange)
+            }
+        }
+    };
+}
+
+macro_rules! impl_int_sql {
+    ($prim:ty, $variant:ident) => {
+        impl_int_from_sql!($prim, $variant);
+        impl ToSql for $prim {
+            fn to_sql(&self) -> SqlVal {
+                SqlVal::$variant((*self).into())
+            }
+            fn to_sql_ref(&self) -> SqlValRef<'_> {
+                SqlValRef::$variant((*self).into())
+            }
+        }
+        impl FieldType for $prim {
+            const SQLTYPE: SqlType = SqlType::$variant;
+            type RefType = $prim;
+        }
+
+        impl PrimaryKeyType for $prim {}
+    };
+}
+
 impl_prim_sql!(bool, Bool, Bool);
-impl_prim_sql!(i64, BigInt, BigInt);
-impl_prim_sql!(i32, Int, Int);
-impl_prim_sql!(u32, BigInt, BigInt);
-// TODO need a small int type
-impl_prim_sql!(u16, Int, Int);
-impl_prim_sql!(i16, Int, Int);
-impl_prim_sql!(u8, Int, Int);
-impl_prim_sql!(i8, Int, Int);
+impl_int_sql!(i64, BigInt);
+impl_int_sql!(u32, BigInt);
+impl_int_sql!(i32, Int);
+impl_int_sql!(u16, Int);
+impl_int_sql!(i16, SmallInt);
+impl_int_sql!(u8, SmallInt);
+impl_int_sql!(i8, SmallInt);
 impl_prim_sql!(f64, Real, Real);
 impl_prim_sql!(f32, Real, Real);
 
+// There is no unsigned 64 bit type in SQL, so u64 is stored as a
+// BigInt. Values too large for an i64 cannot be stored at all:
+// saving or assigning one fails with OutOfRange, and in a filter one
+// equals nothing (see query::Expr::OutOfRange).
+impl_int_from_sql!(u64, BigInt);
+impl ToSql for u64 {
+    fn to_sql(&self) -> SqlVal {
+        self.to_sql_ref().into()
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        self.try_to_sql_ref()
+            .expect("u64 value is out of range for a BigInt")
+    }
+    fn try_to_sql_ref(&self) -> Result<SqlValRef<'_>> {
+        use std::convert::TryFrom;
+        i64::try_from(*self)
+            .map(SqlValRef::BigInt)
+            .map_err(|_| crate::Error::OutOfRange)
+    }
+}
+impl FieldType for u64 {
+    const SQLTYPE: SqlType = SqlType::BigInt;
+    type RefType = u64;
+}
+impl PrimaryKeyType for u64 {}
+
 impl FromSql for String {
     fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
         if let SqlValRef::Text(val) = valref {
@@ -443,6 +615,57 @@ impl FieldType for NaiveDateTime {
 #[cfg(feature = "datetime")]
 impl PrimaryKeyType for NaiveDateTime {}
 
+#[cfg(feature = "datetime")]
+macro_rules! impl_datetime_sql {
+    ($ty:ty, $variant:ident) => {
+        impl_basic_from_sql!($ty, $variant, $variant);
+        impl ToSql for $ty {
+            fn to_sql(&self) -> SqlVal {
+                SqlVal::$variant(*self)
+            }
+            fn to_sql_ref(&self) -> SqlValRef<'_> {
+                SqlValRef::$variant(*self)
+            }
+        }
+        impl FieldType for $ty {
+            const SQLTYPE: SqlType = SqlType::$variant;
+            type RefType = Self;
+        }
+        impl PrimaryKeyType for $ty {}
+    };
+}
+#[cfg(feature = "datetime")]
+impl_datetime_sql!(NaiveDate, Date);
+#[cfg(feature = "datetime")]
+impl_datetime_sql!(NaiveTime, Time);
+#[cfg(feature = "datetime")]
+impl_datetime_sql!(DateTime<Utc>, TimestampTz);
+
+#[cfg(feature = "decimal")]
+impl FromSql for Decimal {
+    fn from_sql_ref(valref: SqlValRef) -> Result<Self> {
+        if let SqlValRef::Numeric(val) = valref {
+            Ok(val)
+        } else {
+            Err(CannotConvertSqlVal(SqlType::Numeric(None), valref.into()))
+        }
+    }
+}
+#[cfg(feature = "decimal")]
+impl ToSql for Decimal {
+    fn to_sql(&self) -> SqlVal {
+        SqlVal::Numeric(*self)
+    }
+    fn to_sql_ref(&self) -> SqlValRef<'_> {
+        SqlValRef::Numeric(*self)
+    }
+}
+#[cfg(feature = "decimal")]
+impl FieldType for Decimal {
+    const SQLTYPE: SqlType = SqlType::Numeric(None);
+    type RefType = Self;
+}
+
 impl ToSql for &str {
     fn to_sql(&self) -> SqlVal {
         SqlVal::Text((*self).to_string())
@@ -476,6 +699,12 @@ where
             Some(v) => v.to_sql_ref(),
         }
     }
+    fn try_to_sql_ref(&self) -> Result<SqlValRef<'_>> {
+        match self {
+            None => Ok(SqlValRef::Null),
+            Some(v) => v.try_to_sql_ref(),
+        }
+    }
     fn into_sql(self) -> SqlVal {
         match self {
             None => SqlVal::Null,